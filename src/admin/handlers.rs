@@ -0,0 +1,150 @@
+use crate::admin::requests::{AnnouncementRequestBody, ServerBanRequestBody};
+use crate::admin::responses::{
+    AdminRoomDetailsResponse, AdminRoomsResponse, AnnouncementResponse, CloseRoomResponse,
+    CreateServerBanResponse, KickUserEverywhereResponse, LiftServerBanResponse,
+    RestoreRoomResponse, RoomSnapshotResponse, ServerBansResponse,
+};
+use crate::admin::services::AdminHttpHandler;
+use crate::app_context::AppContext;
+use crate::auth::extractors::Admin;
+use crate::rooms::snapshot::RoomSnapshot;
+use crate::storage::interface::IRoomStorage;
+use axum::extract::{Path, State};
+use axum::response::Json;
+use std::time::Duration;
+
+pub async fn rooms<RS>(
+    _admin: Admin,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<AdminRoomsResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context).rooms().await;
+    Json(response)
+}
+
+pub async fn room_details<RS>(
+    _admin: Admin,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<AdminRoomDetailsResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context)
+        .room_details(&room_id)
+        .await;
+    Json(response)
+}
+
+pub async fn close_room<RS>(
+    _admin: Admin,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<CloseRoomResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context)
+        .close_room(&room_id)
+        .await;
+    Json(response)
+}
+
+pub async fn kick_user_everywhere<RS>(
+    _admin: Admin,
+    Path(user_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<KickUserEverywhereResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context)
+        .kick_user_everywhere(user_id)
+        .await;
+    Json(response)
+}
+
+pub async fn announce<RS>(
+    _admin: Admin,
+    State(app_context): State<AppContext<RS>>,
+    Json(body): Json<AnnouncementRequestBody>,
+) -> Json<AnnouncementResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context)
+        .announce(body.content)
+        .await;
+    Json(response)
+}
+
+pub async fn server_bans<RS>(
+    _admin: Admin,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<ServerBansResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context).server_bans();
+    Json(response)
+}
+
+pub async fn ban<RS>(
+    _admin: Admin,
+    State(app_context): State<AppContext<RS>>,
+    Json(body): Json<ServerBanRequestBody>,
+) -> Json<CreateServerBanResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context)
+        .ban(
+            body.target,
+            body.duration_secs.map(Duration::from_secs),
+            body.reason,
+        )
+        .await;
+    Json(response)
+}
+
+pub async fn lift_ban<RS>(
+    _admin: Admin,
+    Path(ban_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<LiftServerBanResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context).lift_ban(&ban_id);
+    Json(response)
+}
+
+pub async fn room_snapshot<RS>(
+    _admin: Admin,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<RoomSnapshotResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context)
+        .room_snapshot(&room_id)
+        .await;
+    Json(response)
+}
+
+pub async fn restore_room<RS>(
+    _admin: Admin,
+    State(app_context): State<AppContext<RS>>,
+    Json(snapshot): Json<RoomSnapshot>,
+) -> Json<RestoreRoomResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = AdminHttpHandler::new(app_context)
+        .restore_room(snapshot)
+        .await;
+    Json(response)
+}
@@ -0,0 +1,17 @@
+use crate::bans::BanTarget;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnouncementRequestBody {
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerBanRequestBody {
+    pub target: BanTarget,
+    /// Permanent if not set.
+    pub duration_secs: Option<u64>,
+    pub reason: Option<String>,
+}
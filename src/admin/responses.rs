@@ -0,0 +1,152 @@
+use crate::bans::ServerBan;
+use crate::rooms::models::{BannedUserInfo, RoomSummary};
+use crate::rooms::snapshot::RoomSnapshot;
+use crate::users::models::User;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRoomsResponse {
+    pub rooms: Vec<RoomSummary>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminRoomDetailsResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<AdminRoomError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room: Option<RoomSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub users: Option<Vec<User>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bans: Option<Vec<BannedUserInfo>>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AdminRoomError {
+    RoomNotFound,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseRoomResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<AdminRoomError>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KickUserEverywhereResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<KickUserEverywhereError>,
+    /// How many rooms the user was removed from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rooms_count: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KickUserEverywhereError {
+    /// The user isn't in any room.
+    UserNotFound,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnouncementResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<AnnouncementError>,
+    /// How many rooms the announcement was sent to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rooms_count: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AnnouncementError {
+    EmptyContent,
+    ContentTooLong,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerBansResponse {
+    pub bans: Vec<ServerBan>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateServerBanResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ServerBanError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban: Option<ServerBan>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerBanError {
+    BanDurationTooLong,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiftServerBanResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ServerBanLiftingError>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerBanLiftingError {
+    BanNotFound,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSnapshotResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoomSnapshotError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<RoomSnapshot>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomSnapshotError {
+    RoomNotFound,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreRoomResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoomRestoreError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomRestoreError {
+    /// The snapshot was made by a version of the server that used a different format.
+    UnsupportedSnapshotVersion,
+    DuplicateUser,
+    InvalidHost,
+    BanDurationTooLong,
+    InvalidRoundsLeft,
+    InvalidMaxPlayers,
+    /// Chat message IDs must be unique and in ascending order.
+    UnorderedChatHistory,
+    ChatMessageIdTooLarge,
+}
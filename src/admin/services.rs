@@ -0,0 +1,260 @@
+use crate::admin::responses::{
+    AdminRoomDetailsResponse, AdminRoomError, AdminRoomsResponse, AnnouncementError,
+    AnnouncementResponse, CloseRoomResponse, CreateServerBanResponse, KickUserEverywhereError,
+    KickUserEverywhereResponse, LiftServerBanResponse, RestoreRoomResponse, RoomRestoreError,
+    RoomSnapshotError, RoomSnapshotResponse, ServerBanError, ServerBanLiftingError,
+    ServerBansResponse,
+};
+use crate::app_context::AppContext;
+use crate::bans::{self, BanTarget};
+use crate::rooms::consts::{MAX_BAN_DURATION_SECS, MAX_MESSAGE_LENGTH};
+use crate::rooms::message_types::{
+    self, AnnouncementBotMessagePayload, AnnouncementBotMsg, BotMessagePayload, RoomClosingReason,
+    ServerSentSocketMessage,
+};
+use crate::rooms::models::{ChatMessage, Room};
+use crate::rooms::snapshot::{RoomSnapshot, SnapshotRestoreError};
+use crate::storage::interface::IRoomStorage;
+use crate::storage::sockets::Audience;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub struct AdminHttpHandler<RS: IRoomStorage> {
+    app_context: AppContext<RS>,
+}
+
+impl<RS> AdminHttpHandler<RS>
+where
+    RS: IRoomStorage,
+{
+    pub fn new(app_context: AppContext<RS>) -> Self {
+        Self { app_context }
+    }
+
+    pub async fn rooms(&self) -> AdminRoomsResponse {
+        AdminRoomsResponse {
+            rooms: self.app_context.rooms.summaries().await,
+        }
+    }
+
+    pub async fn room_details(&self, room_id: &str) -> AdminRoomDetailsResponse {
+        let Some((summary, users, bans)) = self.app_context.rooms.details(room_id).await else {
+            return AdminRoomDetailsResponse {
+                error: true,
+                error_code: Some(AdminRoomError::RoomNotFound),
+                room: None,
+                users: None,
+                bans: None,
+            };
+        };
+        AdminRoomDetailsResponse {
+            error: false,
+            error_code: None,
+            room: Some(summary),
+            users: Some(users),
+            bans: Some(bans),
+        }
+    }
+
+    pub async fn close_room(&self, room_id: &str) -> CloseRoomResponse {
+        let Some(removed_room) = self.app_context.rooms.remove(room_id).await else {
+            return CloseRoomResponse {
+                error: true,
+                error_code: Some(AdminRoomError::RoomNotFound),
+            };
+        };
+        tracing::info!("Closing room {room_id} on an operator's request.");
+        self.app_context
+            .close_removed_room(removed_room, RoomClosingReason::ClosedByAdmin)
+            .await;
+        CloseRoomResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    pub async fn kick_user_everywhere(&self, public_id: String) -> KickUserEverywhereResponse {
+        let removed_users = self.app_context.rooms.kick_everywhere(&public_id).await;
+        if removed_users.is_empty() {
+            return KickUserEverywhereResponse {
+                error: true,
+                error_code: Some(KickUserEverywhereError::UserNotFound),
+                rooms_count: None,
+            };
+        }
+        let rooms_count = removed_users.len();
+        for removed_user in removed_users {
+            self.app_context.announce_kicked_user(removed_user).await;
+        }
+        KickUserEverywhereResponse {
+            error: false,
+            error_code: None,
+            rooms_count: Some(rooms_count),
+        }
+    }
+
+    pub fn server_bans(&self) -> ServerBansResponse {
+        ServerBansResponse {
+            bans: bans::ban_list().active(),
+        }
+    }
+
+    /// Users banned by their public ID are also removed from the rooms they are in. There is no
+    /// telling which users an IP ban applies to, so they are only kept out of new rooms.
+    pub async fn ban(
+        &self,
+        target: BanTarget,
+        duration: Option<Duration>,
+        reason: Option<String>,
+    ) -> CreateServerBanResponse {
+        let too_long = CreateServerBanResponse {
+            error: true,
+            error_code: Some(ServerBanError::BanDurationTooLong),
+            ban: None,
+        };
+        if duration.is_some_and(|duration| duration.as_secs() > MAX_BAN_DURATION_SECS) {
+            return too_long;
+        }
+        let Some(ban) = bans::ban_list().add(target, duration, reason) else {
+            return too_long;
+        };
+        if let BanTarget::PublicId { public_id } = &ban.target {
+            self.kick_user_everywhere(public_id.clone()).await;
+        }
+        CreateServerBanResponse {
+            error: false,
+            error_code: None,
+            ban: Some(ban),
+        }
+    }
+
+    pub fn lift_ban(&self, ban_id: &str) -> LiftServerBanResponse {
+        if !bans::ban_list().lift(ban_id) {
+            return LiftServerBanResponse {
+                error: true,
+                error_code: Some(ServerBanLiftingError::BanNotFound),
+            };
+        }
+        LiftServerBanResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    /// Posts the announcement to the chat of every room as a bot message.
+    pub async fn announce(&self, content: String) -> AnnouncementResponse {
+        let content = content.trim().to_string();
+        let error_code = if content.is_empty() {
+            Some(AnnouncementError::EmptyContent)
+        } else if content.graphemes(true).count() > MAX_MESSAGE_LENGTH {
+            Some(AnnouncementError::ContentTooLong)
+        } else {
+            None
+        };
+        if error_code.is_some() {
+            return AnnouncementResponse {
+                error: true,
+                error_code,
+                rooms_count: None,
+            };
+        }
+        let mut rooms_count = 0;
+        for room_id in self.app_context.rooms.ids().await {
+            let bot_message_payload = BotMessagePayload::Announcement {
+                r#type: AnnouncementBotMsg,
+                payload: AnnouncementBotMessagePayload {
+                    content: content.clone(),
+                },
+            };
+            let bot_message = ChatMessage::from_bot(bot_message_payload.clone());
+            let ws_message = ServerSentSocketMessage::BotMessage {
+                r#type: message_types::BotMessage,
+                id: bot_message.id(),
+                payload: bot_message_payload,
+            };
+            if !self
+                .app_context
+                .rooms
+                .add_message(&room_id, bot_message)
+                .await
+            {
+                // Closed since the IDs were taken.
+                continue;
+            }
+            self.app_context
+                .broadcast_event(&room_id, &ws_message, Audience::Everyone)
+                .await;
+            rooms_count += 1;
+        }
+        AnnouncementResponse {
+            error: false,
+            error_code: None,
+            rooms_count: Some(rooms_count),
+        }
+    }
+
+    pub async fn room_snapshot(&self, room_id: &str) -> RoomSnapshotResponse {
+        match self.app_context.rooms.snapshot(room_id).await {
+            Some(snapshot) => RoomSnapshotResponse {
+                error: false,
+                error_code: None,
+                snapshot: Some(snapshot),
+            },
+            None => RoomSnapshotResponse {
+                error: true,
+                error_code: Some(RoomSnapshotError::RoomNotFound),
+                snapshot: None,
+            },
+        }
+    }
+
+    pub async fn restore_room(&self, snapshot: RoomSnapshot) -> RestoreRoomResponse {
+        let room = match Room::try_from(snapshot) {
+            Ok(room) => room,
+            Err(error) => {
+                let error_code = match error {
+                    SnapshotRestoreError::UnsupportedVersion => {
+                        RoomRestoreError::UnsupportedSnapshotVersion
+                    }
+                    SnapshotRestoreError::DuplicateUser => RoomRestoreError::DuplicateUser,
+                    SnapshotRestoreError::InvalidHost => RoomRestoreError::InvalidHost,
+                    SnapshotRestoreError::BanDurationTooLong => {
+                        RoomRestoreError::BanDurationTooLong
+                    }
+                    SnapshotRestoreError::InvalidRoundsLeft => RoomRestoreError::InvalidRoundsLeft,
+                    SnapshotRestoreError::InvalidMaxPlayers => RoomRestoreError::InvalidMaxPlayers,
+                    SnapshotRestoreError::UnorderedChatHistory => {
+                        RoomRestoreError::UnorderedChatHistory
+                    }
+                    SnapshotRestoreError::ChatMessageIdTooLarge => {
+                        RoomRestoreError::ChatMessageIdTooLarge
+                    }
+                };
+                return RestoreRoomResponse {
+                    error: true,
+                    error_code: Some(error_code),
+                    room_id: None,
+                };
+            }
+        };
+        let private_ids: Vec<String> = room
+            .users
+            .iter()
+            .map(|user| user.private_id.clone())
+            .collect();
+        let room_id = self.app_context.rooms.restore(room).await;
+        self.app_context.cluster.claim(&room_id).await;
+        // The restored users are away, so they get the same grace period as after a disconnect.
+        for private_id in private_ids {
+            self.app_context
+                .rooms
+                .on_user_disconnected(&room_id, &private_id, self.app_context.sockets.clone())
+                .await;
+        }
+        RestoreRoomResponse {
+            error: false,
+            error_code: None,
+            room_id: Some(room_id),
+        }
+    }
+}
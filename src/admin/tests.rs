@@ -0,0 +1,309 @@
+use crate::admin::responses::{
+    AdminRoomsResponse, AnnouncementError, AnnouncementResponse, CloseRoomResponse,
+    CreateServerBanResponse, LiftServerBanResponse, RestoreRoomResponse, RoomRestoreError,
+    RoomSnapshotResponse, ServerBanError,
+};
+use crate::admin::services::AdminHttpHandler;
+use crate::app_context::tests::{test_app_context, test_room, TestSocket};
+use crate::auth::responses::{
+    AdminTokenExtractionError, AdminTokenExtractionReason, PasscodeExtractionError,
+    PasscodeExtractionReason,
+};
+use crate::auth::tests::PASSCODE;
+use crate::http::tests::test_server;
+use crate::rooms::consts::{MAX_PLAYERS_LIMIT, ROUNDS_PER_GAME};
+use crate::rooms::models::{BannedUserInfo, ChatMessage};
+use crate::rooms::presence_grace_period;
+use crate::rooms::services::responses::CreateRoomResponse;
+use crate::storage::interface::{RoomInfoRepo, RoomRepo, RoomSnapshotRepo};
+use crate::users::models::UserRole;
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+pub static ADMIN_TOKEN: &str = "testAdminToken";
+
+#[tokio::test]
+async fn test_admin_routes_require_admin_token() {
+    let server = test_server();
+
+    let response = server
+        .get("/admin/rooms/someRoom/snapshot")
+        .add_header("Admin-Token", "wrongToken")
+        .await;
+
+    response.assert_status(StatusCode::UNAUTHORIZED);
+    response.assert_json(&AdminTokenExtractionError {
+        error: true,
+        reason: AdminTokenExtractionReason::InvalidAdminToken,
+    });
+}
+
+#[tokio::test]
+async fn test_restored_room_matches_snapshot() {
+    let server = test_server();
+    let room_id = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .json(&json!({ "public": true, "displayName": "Europe only" }))
+        .await
+        .json::<CreateRoomResponse>()
+        .room_id
+        .expect("Room wasn't created.");
+    let snapshot = server
+        .get(&format!("/admin/rooms/{room_id}/snapshot"))
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .await
+        .json::<RoomSnapshotResponse>()
+        .snapshot
+        .expect("Snapshot wasn't made.");
+
+    let restored_room_id = server
+        .post("/admin/rooms/restore")
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .json(&snapshot)
+        .await
+        .json::<RestoreRoomResponse>()
+        .room_id
+        .expect("Room wasn't restored.");
+
+    assert_ne!(restored_room_id, room_id);
+    let restored_snapshot = server
+        .get(&format!("/admin/rooms/{restored_room_id}/snapshot"))
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .await
+        .json::<Value>();
+    assert_eq!(
+        restored_snapshot["snapshot"],
+        serde_json::to_value(&snapshot).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_closed_room_is_gone() {
+    let server = test_server();
+    let room_id = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .await
+        .json::<CreateRoomResponse>()
+        .room_id
+        .expect("Room wasn't created.");
+
+    let response = server
+        .post(&format!("/admin/rooms/{room_id}/close"))
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .await;
+
+    response.assert_json(&CloseRoomResponse {
+        error: false,
+        error_code: None,
+    });
+    let rooms = server
+        .get("/admin/rooms")
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .await
+        .json::<AdminRoomsResponse>()
+        .rooms;
+    assert!(rooms.iter().all(|room| room.room_id != room_id));
+}
+
+#[tokio::test]
+async fn test_blank_announcement_is_rejected() {
+    let server = test_server();
+
+    let response = server
+        .post("/admin/announcements")
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .json(&json!({ "content": "   " }))
+        .await;
+
+    response.assert_json(&AnnouncementResponse {
+        error: true,
+        error_code: Some(AnnouncementError::EmptyContent),
+        rooms_count: None,
+    });
+}
+
+#[tokio::test]
+async fn test_ip_range_ban_keeps_users_out() {
+    let server = test_server();
+    let ban = server
+        .post("/admin/bans")
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .json(&json!({ "target": { "kind": "ipRange", "range": "203.0.113.0/24" } }))
+        .await
+        .json::<CreateServerBanResponse>()
+        .ban
+        .unwrap();
+
+    let response = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .add_header("X-Forwarded-For", "203.0.113.7")
+        .await;
+
+    response.assert_status(StatusCode::FORBIDDEN);
+    response.assert_json(&PasscodeExtractionError {
+        error: true,
+        reason: PasscodeExtractionReason::BannedFromServer,
+    });
+    server
+        .post(&format!("/admin/bans/{}/lift", ban.id))
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .await
+        .assert_json(&LiftServerBanResponse {
+            error: false,
+            error_code: None,
+        });
+}
+
+#[tokio::test]
+async fn test_ban_with_huge_duration_is_rejected() {
+    let server = test_server();
+    let response = server
+        .post("/admin/bans")
+        .add_header("Admin-Token", ADMIN_TOKEN)
+        .json(&json!({
+            "target": { "kind": "publicId", "publicId": "troll" },
+            "durationSecs": u64::MAX,
+        }))
+        .await;
+
+    response.assert_json(&CreateServerBanResponse {
+        error: true,
+        error_code: Some(ServerBanError::BanDurationTooLong),
+        ban: None,
+    });
+}
+
+#[tokio::test]
+async fn test_inconsistent_snapshots_are_not_restored() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    TestSocket::join(&app_context, &room_id, "alice").await;
+    TestSocket::join(&app_context, &room_id, "bob").await;
+    let admin = AdminHttpHandler::new(app_context.clone());
+    let restore_error = |error_code| RestoreRoomResponse {
+        error: true,
+        error_code: Some(error_code),
+        room_id: None,
+    };
+
+    let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+    snapshot.users[1].public_id = snapshot.users[0].public_id.clone();
+    assert_eq!(
+        admin.restore_room(snapshot).await,
+        restore_error(RoomRestoreError::DuplicateUser)
+    );
+
+    let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+    snapshot.users[1].role = UserRole::Host;
+    assert_eq!(
+        admin.restore_room(snapshot).await,
+        restore_error(RoomRestoreError::InvalidHost)
+    );
+
+    let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+    snapshot.users.retain(|user| user.role != UserRole::Host);
+    assert_eq!(
+        admin.restore_room(snapshot).await,
+        restore_error(RoomRestoreError::InvalidHost)
+    );
+
+    let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+    snapshot.bans.push(BannedUserInfo {
+        public_id: String::from("troll"),
+        username: None,
+        expires_in_secs: Some(u64::MAX),
+    });
+    assert_eq!(
+        admin.restore_room(snapshot).await,
+        restore_error(RoomRestoreError::BanDurationTooLong)
+    );
+
+    let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+    snapshot.rounds_left = ROUNDS_PER_GAME + 1;
+    assert_eq!(
+        admin.restore_room(snapshot).await,
+        restore_error(RoomRestoreError::InvalidRoundsLeft)
+    );
+
+    for max_players in [0, MAX_PLAYERS_LIMIT + 1] {
+        let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+        snapshot.max_players = max_players;
+        assert_eq!(
+            admin.restore_room(snapshot).await,
+            restore_error(RoomRestoreError::InvalidMaxPlayers)
+        );
+    }
+
+    for content in ["first", "second"] {
+        app_context
+            .rooms
+            .add_message(
+                &room_id,
+                ChatMessage::from_player(
+                    String::from("alicePublicId"),
+                    String::from("alice"),
+                    content.to_string(),
+                    vec![],
+                    None,
+                ),
+            )
+            .await;
+    }
+    let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+    snapshot.chat_history.reverse();
+    assert_eq!(
+        admin.restore_room(snapshot).await,
+        restore_error(RoomRestoreError::UnorderedChatHistory)
+    );
+
+    let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+    snapshot.chat_history[1] = snapshot.chat_history[0].clone();
+    assert_eq!(
+        admin.restore_room(snapshot).await,
+        restore_error(RoomRestoreError::UnorderedChatHistory)
+    );
+
+    let mut snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+    if let ChatMessage::FromPlayer { id, .. } = &mut snapshot.chat_history[1] {
+        *id = usize::MAX;
+    }
+    assert_eq!(
+        admin.restore_room(snapshot).await,
+        restore_error(RoomRestoreError::ChatMessageIdTooLarge)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_restored_users_that_never_come_back_are_removed() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    TestSocket::join(&app_context, &room_id, "alice").await;
+    let snapshot = app_context.rooms.snapshot(&room_id).await.unwrap();
+
+    let restored_room_id = AdminHttpHandler::new(app_context.clone())
+        .restore_room(snapshot)
+        .await
+        .room_id
+        .expect("Room wasn't restored.");
+    assert_eq!(
+        app_context
+            .rooms
+            .users(&restored_room_id)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    tokio::time::sleep(presence_grace_period() + Duration::from_secs(1)).await;
+    assert!(app_context
+        .rooms
+        .users(&restored_room_id)
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -1,15 +1,122 @@
-use crate::storage::interface::IRoomStorage;
-use crate::storage::rooms::HashMapRoomsStorage;
-use crate::storage::sockets::HashMapClientSocketsStorage;
+use crate::cli::Args;
+use crate::cluster::{self, Cluster};
+use crate::rate_limit::{RateLimits, RateLimitsConfig};
+use crate::rooms::message_types::{
+    self, RemovalReason, RemovedFromRoomPayload, RoomClosedPayload, RoomClosingReason,
+    ServerSentSocketMessage, UserPubIdInfoPayload,
+};
+use crate::storage::interface::{IRoomStorage, RoomRepo};
+use crate::storage::rooms::{HashMapRoomsStorage, RemovedRoom, RemovedUser};
+use crate::storage::socket_queue::{self, CoalescingKey};
+use crate::storage::sockets::{Audience, HashMapClientSocketsStorage};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::task;
 
+#[cfg(test)]
+pub mod tests;
+
 #[derive(Clone, Default)]
 pub struct AppContext<RS: IRoomStorage> {
     pub rooms: RS,
     // TODO: make the struct generic over sockets storage as well?
     pub sockets: HashMapClientSocketsStorage,
+    pub shutdown: ShutdownState,
+    pub rate_limits: RateLimits,
+    pub cluster: Cluster,
+}
+
+/// Set once the server starts shutting down, after which no new rooms, sockets or rounds are
+/// started.
+#[derive(Clone, Default)]
+pub struct ShutdownState {
+    is_draining: Arc<AtomicBool>,
+}
+
+impl ShutdownState {
+    pub fn start_draining(&self) {
+        self.is_draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.is_draining.load(Ordering::Relaxed)
+    }
+}
+
+impl<RS: IRoomStorage> AppContext<RS> {
+    /// Sends the event to the room's sockets, numbered and logged for replaying.
+    pub async fn broadcast_event(
+        &self,
+        room_id: &str,
+        ws_event: &ServerSentSocketMessage,
+        audience: Audience,
+    ) {
+        let raw_event = serde_json::to_string(ws_event).unwrap();
+        self.rooms
+            .publish_event(
+                room_id,
+                &raw_event,
+                audience,
+                CoalescingKey::of(ws_event),
+                &self.sockets,
+            )
+            .await;
+    }
+
+    /// Tells the room that the user has been kicked from it, along with who the new host is if
+    /// it was them, and closes their socket.
+    pub async fn announce_kicked_user(&self, removed_user: RemovedUser) {
+        let ws_event_msg = ServerSentSocketMessage::UserKicked {
+            r#type: message_types::UserKicked,
+            payload: UserPubIdInfoPayload {
+                public_id: removed_user.user.public_id,
+            },
+        };
+        self.broadcast_event(&removed_user.room_id, &ws_event_msg, Audience::Everyone)
+            .await;
+        if let Some(new_host_public_id) = removed_user.new_host_public_id {
+            let ws_event_msg = ServerSentSocketMessage::HostChanged {
+                r#type: message_types::HostChanged,
+                payload: UserPubIdInfoPayload {
+                    public_id: new_host_public_id,
+                },
+            };
+            self.broadcast_event(&removed_user.room_id, &ws_event_msg, Audience::Everyone)
+                .await;
+        }
+        if let Some(socket_id) = removed_user.user.socket_id {
+            let payload = RemovedFromRoomPayload {
+                reason: RemovalReason::Kicked,
+                ban_duration_secs: None,
+            };
+            self.sockets.remove_from_room(socket_id, payload).await;
+        }
+    }
+
+    /// Tells the users of a room that has just been removed why it was closed and closes their
+    /// sockets.
+    pub async fn close_removed_room(&self, removed_room: RemovedRoom, reason: RoomClosingReason) {
+        let ws_event_msg = ServerSentSocketMessage::RoomClosed {
+            r#type: message_types::RoomClosed,
+            payload: RoomClosedPayload { reason },
+        };
+        let raw_ws_event_msg = serde_json::to_string(&ws_event_msg).unwrap();
+        self.sockets
+            .publish(
+                &removed_room.room_id,
+                raw_ws_event_msg,
+                Audience::Everyone,
+                None,
+            )
+            .await;
+        self.sockets.close_room(&removed_room.room_id).await;
+        self.cluster.release(&removed_room.room_id).await;
+        for socket_id in removed_room.socket_ids.into_iter().flatten() {
+            self.sockets.close(socket_id).await;
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -19,8 +126,15 @@ pub struct RequestContext {
     pub room_id: String,
 }
 
-pub fn init() -> AppContext<HashMapRoomsStorage> {
-    let app_context = AppContext::<HashMapRoomsStorage>::default();
+pub fn init(args: &Args) -> AppContext<HashMapRoomsStorage> {
+    socket_queue::init(args);
+    let (cluster, sockets) = cluster::init(args);
+    let app_context = AppContext::<HashMapRoomsStorage> {
+        rate_limits: RateLimits::new(&RateLimitsConfig::load(args)),
+        cluster,
+        sockets,
+        ..Default::default()
+    };
     let app_context_in_sockets_logger = app_context.clone();
     task::spawn(async move {
         loop {
@@ -31,6 +145,36 @@ pub fn init() -> AppContext<HashMapRoomsStorage> {
                 .unwrap()
                 .as_secs();
             tracing::info!(task = "sockets_count", count, timestamp);
+            for (socket_id, depth) in app_context_in_sockets_logger.sockets.queue_depths().await {
+                tracing::info!(task = "socket_queue_depth", socket_id, depth, timestamp);
+            }
+        }
+    });
+    let app_context_in_rooms_sweeper = app_context.clone();
+    let idle_ttl = Duration::from_secs(args.room_idle_ttl_secs);
+    let empty_room_ttl = Duration::from_secs(args.empty_room_ttl_secs);
+    task::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            let expired_rooms = app_context_in_rooms_sweeper
+                .rooms
+                .delete_expired(idle_ttl, empty_room_ttl)
+                .await;
+            for expired_room in expired_rooms {
+                tracing::info!("Closing expired room {}.", expired_room.room_id);
+                app_context_in_rooms_sweeper
+                    .close_removed_room(expired_room, RoomClosingReason::Idle)
+                    .await;
+            }
+            for room_id in app_context_in_rooms_sweeper.rooms.ids().await {
+                app_context_in_rooms_sweeper.cluster.claim(&room_id).await;
+            }
+            let count = app_context_in_rooms_sweeper.rooms.count().await;
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            tracing::info!(task = "rooms_count", count, timestamp);
         }
     });
     app_context
@@ -0,0 +1,111 @@
+use crate::app_context::{self, AppContext, RequestContext};
+use crate::cli::tests::fake_args;
+use crate::map::locations::LOCATIONS;
+use crate::map::models::LatLng;
+use crate::rooms::message_types::BriefUserInfoPayload;
+use crate::storage::interface::{RoomConnectionHandler, RoomRepo};
+use crate::storage::rooms::HashMapRoomsStorage;
+use crate::storage::socket_queue::SocketQueue;
+use crate::{auth, bans, moderation, rooms};
+use axum::extract::ws::Message;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub fn test_app_context() -> AppContext<HashMapRoomsStorage> {
+    let args = fake_args();
+    auth::init(&args);
+    bans::init(&args);
+    rooms::init(&args);
+    moderation::init(&args);
+    // The locations file isn't read in tests; rounds can still be played with these.
+    LOCATIONS.get_or_init(|| {
+        vec![
+            LatLng { lat: 0.0, lng: 0.0 },
+            LatLng {
+                lat: 10.0,
+                lng: 10.0,
+            },
+        ]
+    });
+    app_context::init(&args)
+}
+
+pub struct TestSocket {
+    pub request_context: RequestContext,
+    pub socket_id: usize,
+    pub queue: Arc<SocketQueue>,
+}
+
+impl TestSocket {
+    /// Opens a socket to the room without joining it.
+    pub async fn open(
+        app_context: &AppContext<HashMapRoomsStorage>,
+        room_id: &str,
+        name: &str,
+    ) -> Self {
+        let (socket_id, queue) = app_context.sockets.add().await;
+        Self {
+            request_context: RequestContext {
+                public_id: format!("{name}PublicId"),
+                private_id: format!("{name}PrivateId"),
+                room_id: room_id.to_string(),
+            },
+            socket_id,
+            queue,
+        }
+    }
+
+    /// Opens a socket to the room and joins it, as the `UserConnected` message would.
+    pub async fn join(
+        app_context: &AppContext<HashMapRoomsStorage>,
+        room_id: &str,
+        name: &str,
+    ) -> Self {
+        let socket = Self::open(app_context, room_id, name).await;
+        app_context
+            .rooms
+            .on_user_connected(
+                room_id,
+                BriefUserInfoPayload {
+                    username: name.to_string(),
+                    avatar_emoji: String::from("🦊"),
+                },
+                socket.socket_id,
+                &socket.request_context.public_id,
+                &socket.request_context.private_id,
+            )
+            .await
+            .expect("User wasn't let into the room.");
+        app_context
+            .sockets
+            .subscribe(room_id, socket.socket_id)
+            .await;
+        socket
+    }
+
+    /// The next message sent to the socket, if any arrives shortly.
+    pub async fn next_message(&self) -> Option<Value> {
+        let message = tokio::time::timeout(Duration::from_millis(100), self.queue.pop())
+            .await
+            .ok()??;
+        match message {
+            Message::Text(text) => Some(serde_json::from_str(&text).unwrap()),
+            _ => None,
+        }
+    }
+
+    /// Skips messages until one of the type arrives.
+    pub async fn expect_message(&self, r#type: &str) -> Value {
+        while let Some(message) = self.next_message().await {
+            if message["type"] == r#type {
+                return message;
+            }
+        }
+        panic!("No `{type}` message was sent to the socket.");
+    }
+}
+
+pub async fn test_room(app_context: &AppContext<HashMapRoomsStorage>) -> String {
+    app_context.rooms.create(None).await
+}
@@ -1,5 +1,10 @@
-use crate::auth::passcode;
-use crate::auth::responses::{PasscodeExtractionError, PasscodeExtractionReason};
+use crate::auth::responses::{
+    AdminTokenExtractionError, AdminTokenExtractionReason, PasscodeExtractionError,
+    PasscodeExtractionReason,
+};
+use crate::auth::{self, passcode};
+use crate::bans;
+use crate::http::client_ip::ClientIp;
 use async_trait::async_trait;
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
@@ -22,10 +27,25 @@ where
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         if let Some(passcode) = parts.headers.get("Passcode") {
             match passcode::decode(passcode.to_str().unwrap()) {
-                Ok(jwt_payload) => Ok(User {
-                    public_id: jwt_payload.public_id,
-                    private_id: jwt_payload.private_id,
-                }),
+                Ok(jwt_payload) => {
+                    let ClientIp(client_ip) = ClientIp::from_parts(parts);
+                    if bans::ban_list()
+                        .find(Some(&jwt_payload.public_id), Some(client_ip))
+                        .is_some()
+                    {
+                        return Err((
+                            StatusCode::FORBIDDEN,
+                            Json(PasscodeExtractionError {
+                                error: true,
+                                reason: PasscodeExtractionReason::BannedFromServer,
+                            }),
+                        ));
+                    }
+                    Ok(User {
+                        public_id: jwt_payload.public_id,
+                        private_id: jwt_payload.private_id,
+                    })
+                }
                 Err(_) => Err((
                     StatusCode::UNAUTHORIZED,
                     Json(PasscodeExtractionError {
@@ -79,3 +99,38 @@ where
         }
     }
 }
+
+/// An operator authenticated with the admin token.
+pub struct Admin;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Admin
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<AdminTokenExtractionError>);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let rejection = |reason| {
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(AdminTokenExtractionError {
+                    error: true,
+                    reason,
+                }),
+            )
+        };
+        if !auth::admin_api_enabled() {
+            return Err(rejection(AdminTokenExtractionReason::AdminApiDisabled));
+        }
+        let Some(admin_token) = parts.headers.get("Admin-Token") else {
+            return Err(rejection(
+                AdminTokenExtractionReason::NoAdminTokenHeaderProvided,
+            ));
+        };
+        match admin_token.to_str() {
+            Ok(admin_token) if auth::is_admin_token(admin_token) => Ok(Admin),
+            _ => Err(rejection(AdminTokenExtractionReason::InvalidAdminToken)),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::auth::JWT_SIGNING_KEY;
+use jwt::{SignWithKey, VerifyWithKey};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteJwtPayload {
+    pub room_id: String,
+    /// Unix timestamp (in seconds) after which the invite is no longer valid.
+    pub expires_at: u64,
+}
+
+pub fn issue(room_id: &str, valid_for: Duration) -> String {
+    let expires_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .saturating_add(valid_for)
+        .as_secs();
+    InviteJwtPayload {
+        room_id: room_id.to_string(),
+        expires_at,
+    }
+    .sign_with_key(
+        JWT_SIGNING_KEY
+            .get()
+            .expect("`JWT_SIGNING_KEY` was not initialized."),
+    )
+    .expect("Failed to sign an invite token.")
+}
+
+/// Checks that the invite token was issued by this server for the given room and hasn't expired.
+pub fn verify(invite_token: &str, room_id: &str) -> Result<(), ()> {
+    let jwt_payload: InviteJwtPayload = invite_token
+        .verify_with_key(
+            JWT_SIGNING_KEY
+                .get()
+                .expect("`JWT_SIGNING_KEY` was not initialized."),
+        )
+        .map_err(|_err| ())?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    if jwt_payload.room_id != room_id || jwt_payload.expires_at < now {
+        return Err(());
+    }
+    Ok(())
+}
@@ -1,19 +1,44 @@
 use crate::cli::Args;
 use hmac::{Hmac, Mac};
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
 use std::sync::OnceLock;
 
 pub mod extractors;
 pub mod handlers;
+pub mod invite;
 pub mod passcode;
 pub mod responses;
 #[cfg(test)]
 pub mod tests;
 
 static JWT_SIGNING_KEY: OnceLock<Hmac<Sha256>> = OnceLock::new();
+/// Digest of the admin token, so that comparing against it takes the same time for any guess.
+static ADMIN_TOKEN_DIGEST: OnceLock<Option<Vec<u8>>> = OnceLock::new();
 
 pub fn init(args: &Args) {
     JWT_SIGNING_KEY.get_or_init(|| {
         Hmac::new_from_slice(args.jwt_signing_key.as_bytes()).expect("Failed to create HMAC code.")
     });
+    ADMIN_TOKEN_DIGEST.get_or_init(|| {
+        args.admin_token
+            .as_ref()
+            .map(|admin_token| Sha256::digest(admin_token.as_bytes()).to_vec())
+    });
+}
+
+/// Whether the admin API is on, i.e. an admin token is configured.
+pub fn admin_api_enabled() -> bool {
+    admin_token_digest().is_some()
+}
+
+pub fn is_admin_token(token: &str) -> bool {
+    admin_token_digest()
+        .is_some_and(|digest| Sha256::digest(token.as_bytes()).as_slice() == digest.as_slice())
+}
+
+fn admin_token_digest() -> Option<&'static Vec<u8>> {
+    ADMIN_TOKEN_DIGEST
+        .get()
+        .expect("Somehow the admin token is used before `init`.")
+        .as_ref()
 }
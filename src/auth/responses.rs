@@ -19,4 +19,22 @@ pub struct PasscodeExtractionError {
 pub enum PasscodeExtractionReason {
     NoPasscodeHeaderProvided,
     InvalidPasscode,
+    /// The user's public ID or IP is banned from the whole server.
+    BannedFromServer,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminTokenExtractionError {
+    pub error: bool,
+    pub reason: AdminTokenExtractionReason,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AdminTokenExtractionReason {
+    /// The server runs without an admin token.
+    AdminApiDisabled,
+    NoAdminTokenHeaderProvided,
+    InvalidAdminToken,
 }
@@ -0,0 +1,165 @@
+use crate::cli::Args;
+use crate::http::ip_range::IpRange;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+#[cfg(test)]
+pub mod tests;
+
+static BAN_LIST: OnceLock<BanList> = OnceLock::new();
+
+pub fn init(args: &Args) {
+    BAN_LIST.get_or_init(|| BanList::load(args.bans_file.clone()));
+}
+
+pub fn ban_list() -> &'static BanList {
+    BAN_LIST
+        .get()
+        .expect("Somehow the ban list is used before `init`.")
+}
+
+/// Keeps someone out of the whole server, as opposed to the bans that hosts issue in their rooms.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerBan {
+    pub id: String,
+    pub target: BanTarget,
+    pub reason: Option<String>,
+    /// Unix timestamp in seconds; `None` for permanent bans.
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum BanTarget {
+    #[serde(rename_all = "camelCase")]
+    PublicId { public_id: String },
+    /// A single address is a range too.
+    IpRange { range: IpRange },
+}
+
+impl ServerBan {
+    fn has_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    fn applies_to(&self, public_id: Option<&str>, ip: Option<IpAddr>) -> bool {
+        match &self.target {
+            BanTarget::PublicId { public_id: banned } => public_id == Some(banned.as_str()),
+            BanTarget::IpRange { range } => ip.is_some_and(|ip| range.contains(ip)),
+        }
+    }
+}
+
+/// Server-wide bans, written to the bans file (if there is one) on every change so that they
+/// survive restarts.
+pub struct BanList {
+    path: Option<PathBuf>,
+    bans: Mutex<Vec<ServerBan>>,
+}
+
+impl BanList {
+    /// Starts with an empty list if the file doesn't exist yet.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let bans = match &path {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(contents) => {
+                    serde_json::from_str(&contents).expect("Failed to parse the bans file.")
+                }
+                Err(error) if error.kind() == ErrorKind::NotFound => vec![],
+                Err(error) => panic!("Failed to read the bans file: {error}"),
+            },
+            None => vec![],
+        };
+        Self {
+            path,
+            bans: Mutex::new(bans),
+        }
+    }
+
+    /// Returns `None` if the ban would expire too far in the future to be stored.
+    pub fn add(
+        &self,
+        target: BanTarget,
+        duration: Option<Duration>,
+        reason: Option<String>,
+    ) -> Option<ServerBan> {
+        let expires_at = match duration {
+            Some(duration) => Some(now().checked_add(duration.as_secs())?),
+            None => None,
+        };
+        let ban = ServerBan {
+            id: Uuid::new_v4().to_string(),
+            target,
+            reason,
+            expires_at,
+        };
+        let mut bans = self.bans.lock().unwrap();
+        let now = now();
+        bans.retain(|ban| !ban.has_expired(now));
+        bans.push(ban.clone());
+        self.save(&bans);
+        Some(ban)
+    }
+
+    /// Returns `false` if there was no such ban.
+    pub fn lift(&self, ban_id: &str) -> bool {
+        let mut bans = self.bans.lock().unwrap();
+        let bans_count = bans.len();
+        bans.retain(|ban| ban.id != ban_id);
+        if bans.len() == bans_count {
+            return false;
+        }
+        self.save(&bans);
+        true
+    }
+
+    pub fn active(&self) -> Vec<ServerBan> {
+        let now = now();
+        self.bans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|ban| !ban.has_expired(now))
+            .cloned()
+            .collect()
+    }
+
+    /// The ban that keeps the user with this public ID or IP out, if any.
+    pub fn find(&self, public_id: Option<&str>, ip: Option<IpAddr>) -> Option<ServerBan> {
+        let now = now();
+        self.bans
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|ban| !ban.has_expired(now) && ban.applies_to(public_id, ip))
+            .cloned()
+    }
+
+    /// Writes to a temporary file first, so that a crash can't leave a half-written list behind.
+    fn save(&self, bans: &[ServerBan]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let temporary_path = path.with_extension("tmp");
+        let contents = serde_json::to_string_pretty(bans).unwrap();
+        if let Err(error) =
+            fs::write(&temporary_path, contents).and_then(|()| fs::rename(&temporary_path, path))
+        {
+            eprintln!("[bans]: failed to save the bans file: {error}");
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,55 @@
+use crate::bans::{BanList, BanTarget};
+use crate::http::ip_range::IpRange;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+fn ip(ip: &str) -> Option<IpAddr> {
+    Some(IpAddr::from_str(ip).unwrap())
+}
+
+#[test]
+fn test_ip_range_ban_covers_the_whole_range() {
+    let ban_list = BanList::load(None);
+    ban_list.add(
+        BanTarget::IpRange {
+            range: IpRange::from_str("198.51.100.0/24").unwrap(),
+        },
+        None,
+        None,
+    );
+
+    assert!(ban_list.find(None, ip("198.51.100.42")).is_some());
+    assert!(ban_list.find(None, ip("::ffff:198.51.100.42")).is_some());
+    assert!(ban_list.find(None, ip("198.51.101.42")).is_none());
+}
+
+#[test]
+fn test_expired_ban_is_ignored() {
+    let ban_list = BanList::load(None);
+    ban_list.add(
+        BanTarget::PublicId {
+            public_id: String::from("troll"),
+        },
+        Some(Duration::ZERO),
+        None,
+    );
+
+    assert!(ban_list.find(Some("troll"), None).is_none());
+    assert!(ban_list.active().is_empty());
+}
+
+#[test]
+fn test_ban_expiring_past_the_end_of_time_is_not_added() {
+    let ban_list = BanList::load(None);
+    let ban = ban_list.add(
+        BanTarget::PublicId {
+            public_id: String::from("troll"),
+        },
+        Some(Duration::from_secs(u64::MAX)),
+        None,
+    );
+
+    assert!(ban.is_none());
+    assert!(ban_list.find(Some("troll"), None).is_none());
+}
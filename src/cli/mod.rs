@@ -1,3 +1,7 @@
+use crate::http::client_ip::ClientIpHeader;
+use crate::http::ip_range::IpRange;
+use crate::moderation::word_filter::WordFilterAction;
+use crate::storage::socket_queue::SlowClientPolicy;
 use clap::Parser;
 use std::net::SocketAddr;
 use std::path::PathBuf;
@@ -13,9 +17,111 @@ pub struct Args {
     #[arg(long)]
     #[arg(default_value = "0.0.0.0:3030")]
     pub listen_address: SocketAddr,
+    /// Proxies (addresses or CIDR blocks, comma-separated) whose forwarding headers are believed.
+    /// Without any, the client IP is always the address the request came from.
+    #[arg(long, value_delimiter = ',')]
+    pub trusted_proxies: Vec<IpRange>,
+    /// The header the trusted proxies pass the client IP in.
+    #[arg(long)]
+    #[arg(value_enum, default_value = "x-forwarded-for")]
+    pub client_ip_header: ClientIpHeader,
     #[arg(long)]
     pub jwt_signing_key: String,
+    /// Grants access to the admin API via the `Admin-Token` header; the API is off if not set.
+    #[arg(long)]
+    pub admin_token: Option<String>,
+    /// JSON file the server-wide bans are kept in; without it, they are lost on restart.
+    #[arg(long)]
+    pub bans_file: Option<PathBuf>,
+    /// JSON file with request quotas per group of routes; the defaults apply to missing groups.
+    #[arg(long)]
+    pub rate_limits: Option<PathBuf>,
     #[arg(long)]
     #[arg(default_value = "locations.example.ndjson")]
     pub locations: PathBuf,
+    /// Rooms with no activity for this long get closed once none of their users are online.
+    #[arg(long)]
+    #[arg(default_value = "3600")]
+    pub room_idle_ttl_secs: u64,
+    /// Rooms with no users in them for this long get closed.
+    #[arg(long)]
+    #[arg(default_value = "300")]
+    pub empty_room_ttl_secs: u64,
+    /// Uploaded images that aren't referenced by any room and are older than this get deleted.
+    /// Attachments garbage collection is disabled if not set.
+    #[arg(long)]
+    pub attachments_retention_hours: Option<u64>,
+    #[arg(long)]
+    #[arg(default_value = "3600")]
+    pub attachments_gc_interval_secs: u64,
+    /// Only report which attachments would be deleted by the garbage collection.
+    #[arg(long)]
+    pub attachments_gc_dry_run: bool,
+    /// A vote started by players passes once more than this percentage of the room agrees.
+    #[arg(long)]
+    #[arg(default_value = "50")]
+    pub vote_majority_percent: u64,
+    #[arg(long)]
+    #[arg(default_value = "30")]
+    pub vote_timeout_secs: u64,
+    /// File with words that aren't allowed in chat, one per line.
+    #[arg(long)]
+    pub chat_banned_words: Option<PathBuf>,
+    #[arg(long)]
+    #[arg(value_enum, default_value = "mask")]
+    pub chat_banned_words_action: WordFilterAction,
+    #[arg(long)]
+    #[arg(default_value = "20")]
+    pub chat_messages_per_minute: u32,
+    /// How many messages a user can send in a quick succession before being rate limited.
+    #[arg(long)]
+    #[arg(default_value = "5")]
+    pub chat_messages_burst: u32,
+    /// Sending the same message again within this time is rejected.
+    #[arg(long)]
+    #[arg(default_value = "30")]
+    pub chat_duplicate_window_secs: u64,
+    /// Users that get this many messages rejected within a minute are muted. Zero disables it.
+    #[arg(long)]
+    #[arg(default_value = "5")]
+    pub chat_auto_mute_violations: usize,
+    /// How many chat messages each room keeps, dropping the oldest ones. Zero keeps all of them.
+    #[arg(long)]
+    #[arg(default_value = "10000")]
+    pub chat_history_limit: usize,
+    /// Users that lose the connection keep their place in the room for this long.
+    #[arg(long)]
+    #[arg(default_value = "30")]
+    pub presence_grace_period_secs: u64,
+    /// How many messages can wait to be sent to a client before `slow_client_policy` kicks in.
+    #[arg(long)]
+    #[arg(default_value = "256")]
+    pub socket_queue_capacity: usize,
+    #[arg(long)]
+    #[arg(value_enum, default_value = "coalesce")]
+    pub slow_client_policy: SlowClientPolicy,
+    /// How often the server pings clients to find out if they are still there; zero turns it off.
+    #[arg(long)]
+    #[arg(default_value = "20")]
+    pub ping_interval_secs: u64,
+    /// Sockets that don't answer this many pings in a row are considered dead and closed.
+    #[arg(long)]
+    #[arg(default_value = "2")]
+    pub max_missed_pongs: u32,
+    /// On shutdown, rounds in progress are given this long to finish.
+    #[arg(long)]
+    #[arg(default_value = "120")]
+    pub shutdown_deadline_secs: u64,
+    /// How long clients are told to wait before reconnecting when the server shuts down.
+    #[arg(long)]
+    #[arg(default_value = "10")]
+    pub shutdown_reconnect_after_secs: u64,
+    /// Redis server through which several instances share room broadcasts and keep track of
+    /// which of them holds each room. Without it, the server runs as a single instance.
+    #[arg(long, requires = "instance_url")]
+    pub redis_url: Option<Url>,
+    /// The address the other instances reach this one at, e.g. `http://10.0.0.5:3030`; requests
+    /// for the rooms held here are forwarded to it.
+    #[arg(long)]
+    pub instance_url: Option<Url>,
 }
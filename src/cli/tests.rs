@@ -1,4 +1,8 @@
 use crate::cli::Args;
+use crate::http::client_ip::ClientIpHeader;
+use crate::http::ip_range::IpRange;
+use crate::moderation::word_filter::WordFilterAction;
+use crate::storage::socket_queue::SlowClientPolicy;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -10,7 +14,36 @@ pub fn fake_args() -> Args {
             .expect("Failed co construct fake Quickwit URL."),
         listen_address: SocketAddr::from_str("0.0.0.0:3030")
             .expect("Failed co construct fake listen address."),
+        // Tests aren't served with connection info, so requests seem to come from localhost.
+        trusted_proxies: vec![IpRange::from_str("127.0.0.1").unwrap()],
+        client_ip_header: ClientIpHeader::XForwardedFor,
         jwt_signing_key: String::from("testKey"),
+        admin_token: Some(String::from("testAdminToken")),
+        bans_file: None,
+        rate_limits: None,
         locations: PathBuf::new(),
+        room_idle_ttl_secs: 3600,
+        empty_room_ttl_secs: 300,
+        attachments_retention_hours: None,
+        attachments_gc_interval_secs: 3600,
+        attachments_gc_dry_run: false,
+        vote_majority_percent: 50,
+        vote_timeout_secs: 30,
+        chat_banned_words: None,
+        chat_banned_words_action: WordFilterAction::Mask,
+        chat_messages_per_minute: 20,
+        chat_messages_burst: 5,
+        chat_duplicate_window_secs: 30,
+        chat_auto_mute_violations: 5,
+        chat_history_limit: 10000,
+        presence_grace_period_secs: 30,
+        socket_queue_capacity: 256,
+        slow_client_policy: SlowClientPolicy::Coalesce,
+        ping_interval_secs: 20,
+        max_missed_pongs: 2,
+        shutdown_deadline_secs: 120,
+        shutdown_reconnect_after_secs: 10,
+        redis_url: None,
+        instance_url: None,
     }
 }
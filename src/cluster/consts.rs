@@ -0,0 +1,5 @@
+/// How long a room stays claimed without being renewed; the rooms sweeper renews the claims of
+/// the instance's rooms every 10 seconds.
+pub const ROOM_CLAIM_TTL_SECS: u64 = 60;
+/// Same as the default body limit of the routes that requests are forwarded to.
+pub const MAX_FORWARDED_BODY_BYTES: usize = 2_000_000;
@@ -0,0 +1,87 @@
+use crate::cli::Args;
+use crate::cluster::consts::ROOM_CLAIM_TTL_SECS;
+use crate::storage::directory::RoomDirectory;
+use crate::storage::redis::{RedisRoomBroker, RedisRoomDirectory};
+use crate::storage::sockets::HashMapClientSocketsStorage;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+pub mod consts;
+pub mod proxy;
+#[cfg(test)]
+pub mod tests;
+
+/// The instance's place among the ones serving the same rooms. A room's state lives on the
+/// instance that created it, which claims it in the directory; the other instances forward the
+/// requests for the room to it.
+#[derive(Clone, Default)]
+pub struct Cluster {
+    /// Not set when the server runs as a single instance.
+    membership: Option<Arc<Membership>>,
+}
+
+struct Membership {
+    instance_url: Url,
+    directory: Arc<dyn RoomDirectory>,
+    http_client: reqwest::Client,
+}
+
+pub struct RoomOwner {
+    pub url: Url,
+    pub http_client: reqwest::Client,
+}
+
+impl Cluster {
+    pub fn new(instance_url: Url, directory: Arc<dyn RoomDirectory>) -> Self {
+        Self {
+            membership: Some(Arc::new(Membership {
+                instance_url,
+                directory,
+                http_client: reqwest::Client::new(),
+            })),
+        }
+    }
+
+    /// Tells the other instances that the room is held here.
+    pub async fn claim(&self, room_id: &str) {
+        if let Some(membership) = &self.membership {
+            let ttl = Duration::from_secs(ROOM_CLAIM_TTL_SECS);
+            membership
+                .directory
+                .claim(room_id, &membership.instance_url, ttl)
+                .await;
+        }
+    }
+
+    pub async fn release(&self, room_id: &str) {
+        if let Some(membership) = &self.membership {
+            membership.directory.release(room_id).await;
+        }
+    }
+
+    /// The instance that holds the room, if it's another one.
+    pub async fn owner_elsewhere(&self, room_id: &str) -> Option<RoomOwner> {
+        let membership = self.membership.as_ref()?;
+        let url = membership.directory.owner(room_id).await?;
+        (url != membership.instance_url).then(|| RoomOwner {
+            url,
+            http_client: membership.http_client.clone(),
+        })
+    }
+}
+
+/// Joins the other instances if a Redis server is given, in which case the room broadcasts go
+/// through it too.
+pub fn init(args: &Args) -> (Cluster, HashMapClientSocketsStorage) {
+    let (Some(redis_url), Some(instance_url)) = (&args.redis_url, &args.instance_url) else {
+        return Default::default();
+    };
+    let client = redis::Client::open(redis_url.as_str()).expect("Failed to parse Redis URL.");
+    let cluster = Cluster::new(
+        instance_url.clone(),
+        Arc::new(RedisRoomDirectory::new(client.clone())),
+    );
+    let sockets = HashMapClientSocketsStorage::with_broker(Arc::new(RedisRoomBroker::new(client)));
+    (cluster, sockets)
+}
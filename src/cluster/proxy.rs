@@ -0,0 +1,234 @@
+use crate::app_context::AppContext;
+use crate::cluster::consts::MAX_FORWARDED_BODY_BYTES;
+use crate::http::client_ip::{self, ClientIpHeader};
+use crate::storage::interface::IRoomStorage;
+use axum::body::{self, Body};
+use axum::extract::ws::{self, WebSocket};
+use axum::extract::{FromRequestParts, OriginalUri, Path, Request, State, WebSocketUpgrade};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{self, protocol::CloseFrame};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use url::Url;
+
+/// Headers that only concern a single connection, so they aren't passed on.
+const HOP_BY_HOP_HEADERS: [HeaderName; 5] = [
+    header::CONNECTION,
+    header::CONTENT_LENGTH,
+    header::HOST,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// Passes requests for rooms held by another instance on to it, sockets included.
+pub async fn forward_to_room_owner<RS>(
+    State(app_context): State<AppContext<RS>>,
+    path_params: Option<Path<HashMap<String, String>>>,
+    request: Request,
+    next: Next,
+) -> Response
+where
+    RS: IRoomStorage,
+{
+    let Some(room_id) = path_params.and_then(|Path(mut params)| params.remove("room-id")) else {
+        return next.run(request).await;
+    };
+    if app_context.rooms.exists(&room_id).await {
+        return next.run(request).await;
+    }
+    let Some(owner) = app_context.cluster.owner_elsewhere(&room_id).await else {
+        return next.run(request).await;
+    };
+    let (mut parts, body) = request.into_parts();
+    let owner_url = owner_url(&owner.url, &parts);
+    let headers = forwarded_headers(&parts.headers, client_ip::peer_ip(&parts));
+    if !parts.headers.contains_key(header::UPGRADE) {
+        return forward_http(&owner.http_client, owner_url, parts, headers, body).await;
+    }
+    match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+        Ok(ws) => forward_ws(ws, owner_url, headers).await,
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// The same request, as addressed to the owner.
+fn owner_url(owner: &Url, parts: &Parts) -> Url {
+    // Nested routers only see what's left of the path.
+    let uri = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or(&parts.uri, |OriginalUri(uri)| uri);
+    let mut url = owner.clone();
+    url.set_path(uri.path());
+    url.set_query(uri.query());
+    url
+}
+
+/// The request's headers, with the peer added to the forwarding header the owner reads the
+/// client IP from.
+pub fn forwarded_headers(headers: &HeaderMap, peer_ip: IpAddr) -> HeaderMap {
+    let mut headers = headers.clone();
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(name);
+    }
+    let (name, hop) = match client_ip::settings().header {
+        ClientIpHeader::XForwardedFor => ("X-Forwarded-For", peer_ip.to_string()),
+        ClientIpHeader::Forwarded => (
+            "Forwarded",
+            match peer_ip {
+                IpAddr::V4(ip) => format!("for={ip}"),
+                IpAddr::V6(ip) => format!("for=\"[{ip}]\""),
+            },
+        ),
+        // Set once, by Cloudflare itself.
+        ClientIpHeader::CfConnectingIp => return headers,
+    };
+    let chain = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .chain([hop.as_str()])
+        .collect::<Vec<_>>()
+        .join(", ");
+    headers.remove(name);
+    if let Ok(chain) = HeaderValue::from_str(&chain) {
+        headers.insert(name, chain);
+    }
+    headers
+}
+
+async fn forward_http(
+    http_client: &reqwest::Client,
+    owner_url: Url,
+    parts: Parts,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let Ok(body) = body::to_bytes(body, MAX_FORWARDED_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let owner_response = http_client
+        .request(parts.method, owner_url)
+        .headers(headers)
+        .body(body)
+        .send()
+        .await;
+    let owner_response = match owner_response {
+        Ok(owner_response) => owner_response,
+        Err(error) => {
+            eprintln!("[room_forwarding]: failed to reach the room's instance: {error}");
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+    let status = owner_response.status();
+    let mut headers = owner_response.headers().clone();
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(name);
+    }
+    match owner_response.bytes().await {
+        Ok(body) => (status, headers, body).into_response(),
+        Err(error) => {
+            eprintln!("[room_forwarding]: failed to read the room's instance response: {error}");
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+/// Connects to the owner first, so that a refusal reaches the client as is.
+async fn forward_ws(ws: WebSocketUpgrade, mut owner_url: Url, headers: HeaderMap) -> Response {
+    let scheme = if owner_url.scheme() == "https" {
+        "wss"
+    } else {
+        "ws"
+    };
+    let _ = owner_url.set_scheme(scheme);
+    let mut owner_request = match owner_url.as_str().into_client_request() {
+        Ok(owner_request) => owner_request,
+        Err(error) => {
+            eprintln!("[room_forwarding]: bad URL of the room's instance: {error}");
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+    for (name, value) in &headers {
+        if !name.as_str().starts_with("sec-websocket-") {
+            owner_request.headers_mut().append(name, value.clone());
+        }
+    }
+    match tokio_tungstenite::connect_async(owner_request).await {
+        Ok((owner_socket, _)) => ws.on_upgrade(move |socket| pipe(socket, owner_socket)),
+        Err(tungstenite::Error::Http(refusal)) => {
+            let (parts, body) = refusal.into_parts();
+            (parts.status, parts.headers, body.unwrap_or_default()).into_response()
+        }
+        Err(error) => {
+            eprintln!("[room_forwarding]: failed to reach the room's instance: {error}");
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+/// Relays messages both ways until either side goes away.
+async fn pipe(socket: WebSocket, owner_socket: WebSocketStream<MaybeTlsStream<TcpStream>>) {
+    let (mut client_tx, mut client_rx) = socket.split();
+    let (mut owner_tx, mut owner_rx) = owner_socket.split();
+    let to_owner = async {
+        while let Some(Ok(msg)) = client_rx.next().await {
+            if owner_tx.send(into_owner_message(msg)).await.is_err() {
+                break;
+            }
+        }
+        let _ = owner_tx.close().await;
+    };
+    let to_client = async {
+        while let Some(Ok(msg)) = owner_rx.next().await {
+            let Some(msg) = into_client_message(msg) else {
+                continue;
+            };
+            if client_tx.send(msg).await.is_err() {
+                break;
+            }
+        }
+        let _ = client_tx.close().await;
+    };
+    tokio::select! {
+        _ = to_owner => {},
+        _ = to_client => {},
+    }
+}
+
+fn into_owner_message(msg: ws::Message) -> tungstenite::Message {
+    match msg {
+        ws::Message::Text(text) => tungstenite::Message::Text(text),
+        ws::Message::Binary(data) => tungstenite::Message::Binary(data),
+        ws::Message::Ping(data) => tungstenite::Message::Ping(data),
+        ws::Message::Pong(data) => tungstenite::Message::Pong(data),
+        ws::Message::Close(frame) => tungstenite::Message::Close(frame.map(|frame| CloseFrame {
+            code: frame.code.into(),
+            reason: frame.reason,
+        })),
+    }
+}
+
+fn into_client_message(msg: tungstenite::Message) -> Option<ws::Message> {
+    match msg {
+        tungstenite::Message::Text(text) => Some(ws::Message::Text(text)),
+        tungstenite::Message::Binary(data) => Some(ws::Message::Binary(data)),
+        tungstenite::Message::Ping(data) => Some(ws::Message::Ping(data)),
+        tungstenite::Message::Pong(data) => Some(ws::Message::Pong(data)),
+        tungstenite::Message::Close(frame) => {
+            Some(ws::Message::Close(frame.map(|frame| ws::CloseFrame {
+                code: frame.code.into(),
+                reason: frame.reason,
+            })))
+        }
+        // Only seen when reading raw frames.
+        tungstenite::Message::Frame(_) => None,
+    }
+}
@@ -0,0 +1,196 @@
+use crate::app_context::tests::test_app_context;
+use crate::app_context::AppContext;
+use crate::auth::tests::PASSCODE;
+use crate::cli::tests::fake_args;
+use crate::cluster::proxy::forwarded_headers;
+use crate::cluster::Cluster;
+use crate::http::{client_ip, router};
+use crate::rooms::services::responses::CreateRoomResponse;
+use crate::storage::broker::{Audience, InstanceId, RoomBroadcast};
+use crate::storage::directory::RoomDirectory;
+use crate::storage::interface::{RoomInfoRepo, RoomRepo};
+use crate::storage::redis::ChannelMessage;
+use crate::storage::rooms::HashMapRoomsStorage;
+use crate::storage::socket_queue::CoalescingKey;
+use async_trait::async_trait;
+use axum::http::{header, HeaderMap, HeaderValue};
+use futures_util::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+use url::Url;
+
+/// Keeps the claims in memory; shared between the instances of a test, it stands in for Redis.
+#[derive(Default)]
+pub struct LocalRoomDirectory {
+    owners: Mutex<HashMap<String, Url>>,
+}
+
+#[async_trait]
+impl RoomDirectory for LocalRoomDirectory {
+    async fn claim(&self, room_id: &str, instance_url: &Url, _ttl: Duration) {
+        self.owners
+            .lock()
+            .unwrap()
+            .insert(room_id.to_string(), instance_url.clone());
+    }
+
+    async fn release(&self, room_id: &str) {
+        self.owners.lock().unwrap().remove(room_id);
+    }
+
+    async fn owner(&self, room_id: &str) -> Option<Url> {
+        self.owners.lock().unwrap().get(room_id).cloned()
+    }
+}
+
+/// Serves an instance on a free port, sharing the directory with the other test instances.
+async fn serve_instance(
+    directory: Arc<LocalRoomDirectory>,
+) -> (AppContext<HashMapRoomsStorage>, Url) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let instance_url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    let mut app_context = test_app_context();
+    app_context.cluster = Cluster::new(instance_url.clone(), directory);
+    let routes = router::new(&fake_args(), app_context.clone());
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            routes.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+    (app_context, instance_url)
+}
+
+async fn json<T: DeserializeOwned>(response: reqwest::Response) -> T {
+    serde_json::from_str(&response.text().await.unwrap()).unwrap()
+}
+
+async fn create_room(instance_url: &Url) -> String {
+    let response = reqwest::Client::new()
+        .post(instance_url.join("/rooms").unwrap())
+        .header("Passcode", PASSCODE)
+        .send()
+        .await
+        .unwrap();
+    json::<CreateRoomResponse>(response)
+        .await
+        .room_id
+        .expect("Room wasn't created.")
+}
+
+#[test]
+fn test_broadcasts_survive_the_trip_through_redis() {
+    let messages = [
+        ChannelMessage::Broadcast(RoomBroadcast {
+            raw_msg: Arc::from(r#"{"type":"PresenceChanged"}"#),
+            origin: InstanceId::generate(),
+            audience: Audience::EveryoneExcept(7),
+            coalescing_key: Some(CoalescingKey::Presence {
+                public_id: String::from("alicePublicId"),
+            }),
+        }),
+        ChannelMessage::RoomClosed,
+    ];
+
+    for message in messages {
+        assert_eq!(
+            ChannelMessage::decode(message.encode().as_bytes()).unwrap(),
+            message
+        );
+    }
+}
+
+#[test]
+fn test_forwarded_requests_keep_the_client_ip() {
+    client_ip::init(&fake_args());
+    let mut headers = HeaderMap::new();
+    headers.insert(header::HOST, HeaderValue::from_static("b.example.com"));
+    headers.insert("X-Forwarded-For", HeaderValue::from_static("203.0.113.7"));
+
+    let headers = forwarded_headers(&headers, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+
+    assert!(!headers.contains_key(header::HOST));
+    assert_eq!(headers["X-Forwarded-For"], "203.0.113.7, 10.0.0.2");
+}
+
+#[tokio::test]
+async fn test_requests_for_rooms_held_elsewhere_are_forwarded() {
+    let directory = Arc::new(LocalRoomDirectory::default());
+    let (owner, owner_url) = serve_instance(directory.clone()).await;
+    let (_, other_url) = serve_instance(directory).await;
+    let room_id = create_room(&owner_url).await;
+    assert!(owner.rooms.exists(&room_id).await);
+
+    let response = reqwest::Client::new()
+        .get(
+            other_url
+                .join(&format!("/rooms/{room_id}/messages"))
+                .unwrap(),
+        )
+        .query(&[("after", usize::MAX), ("limit", 10)])
+        .header("Passcode", PASSCODE)
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        json::<Value>(response).await,
+        json!({ "error": false, "messages": [], "hasMore": false }),
+    );
+}
+
+#[tokio::test]
+async fn test_sockets_to_rooms_held_elsewhere_are_forwarded() {
+    let directory = Arc::new(LocalRoomDirectory::default());
+    let (owner, owner_url) = serve_instance(directory.clone()).await;
+    let (_, mut other_url) = serve_instance(directory).await;
+    let room_id = create_room(&owner_url).await;
+    other_url.set_scheme("ws").unwrap();
+    other_url.set_path(&format!("/rooms/{room_id}/ws"));
+    other_url.set_query(Some(&format!("passcode={PASSCODE}")));
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(other_url.as_str())
+        .await
+        .unwrap();
+    let user_connected = json!({
+        "type": "UserConnected",
+        "payload": { "username": "alice", "avatarEmoji": "🦊" },
+    });
+    socket
+        .send(Message::Text(user_connected.to_string()))
+        .await
+        .unwrap();
+
+    let reply = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .expect("The room's instance didn't reply.")
+        .unwrap()
+        .unwrap();
+    assert!(matches!(reply, Message::Text(_)));
+    assert_eq!(owner.rooms.users(&room_id).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_rooms_nobody_holds_are_not_forwarded() {
+    let directory = Arc::new(LocalRoomDirectory::default());
+    let (_, other_url) = serve_instance(directory).await;
+
+    let response = reqwest::Client::new()
+        .get(other_url.join("/rooms/unknownRoom/messages").unwrap())
+        .header("Passcode", PASSCODE)
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status().is_success());
+    assert_eq!(json::<Value>(response).await["errorCode"], "roomNotFound");
+}
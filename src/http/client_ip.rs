@@ -0,0 +1,140 @@
+use crate::cli::Args;
+use crate::http::ip_range::IpRange;
+use async_trait::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, StatusCode};
+use clap::ValueEnum;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::OnceLock;
+
+static CLIENT_IP_SETTINGS: OnceLock<ClientIpSettings> = OnceLock::new();
+
+pub fn init(args: &Args) {
+    CLIENT_IP_SETTINGS.get_or_init(|| ClientIpSettings {
+        trusted_proxies: args.trusted_proxies.clone(),
+        header: args.client_ip_header,
+    });
+}
+
+pub fn settings() -> &'static ClientIpSettings {
+    CLIENT_IP_SETTINGS
+        .get()
+        .expect("Somehow client IP settings are used before `init`.")
+}
+
+#[derive(Clone, Debug)]
+pub struct ClientIpSettings {
+    /// Only the proxies in these ranges are believed about who they forward requests for.
+    pub trusted_proxies: Vec<IpRange>,
+    pub header: ClientIpHeader,
+}
+
+/// The header the trusted proxies put the client's IP in.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum ClientIpHeader {
+    XForwardedFor,
+    /// The standard `Forwarded` header (RFC 7239).
+    Forwarded,
+    /// Set by Cloudflare.
+    CfConnectingIp,
+}
+
+/// The IP of the client that made the request.
+///
+/// Forwarding headers are easy to forge, so they are only looked at if the request came through
+/// a trusted proxy, and only as far back as the chain of trusted proxies goes: the client is the
+/// first address, counting from the right, that isn't a trusted proxy.
+#[derive(Copy, Clone, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Infallible);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(parts))
+    }
+}
+
+impl ClientIp {
+    pub fn from_parts(parts: &Parts) -> Self {
+        Self(resolve(settings(), peer_ip(parts), &parts.headers))
+    }
+}
+
+/// The address the request came from, which is a proxy's if it came through one.
+pub fn peer_ip(parts: &Parts) -> IpAddr {
+    // The connection info is missing only if the app isn't served with it, e.g. in tests.
+    parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::LOCALHOST), |ConnectInfo(peer)| {
+            peer.ip()
+        })
+}
+
+pub fn resolve(settings: &ClientIpSettings, peer_ip: IpAddr, headers: &HeaderMap) -> IpAddr {
+    let is_trusted = |ip: IpAddr| {
+        settings
+            .trusted_proxies
+            .iter()
+            .any(|range| range.contains(ip))
+    };
+    if !is_trusted(peer_ip) {
+        return peer_ip;
+    }
+    let hops = match settings.header {
+        ClientIpHeader::XForwardedFor => header_values(headers, "X-Forwarded-For")
+            .flat_map(|value| value.split(','))
+            .map(|hop| hop.trim().parse().ok())
+            .collect::<Vec<_>>(),
+        ClientIpHeader::Forwarded => header_values(headers, "Forwarded")
+            .flat_map(|value| value.split(','))
+            .map(forwarded_for)
+            .collect(),
+        ClientIpHeader::CfConnectingIp => header_values(headers, "CF-Connecting-IP")
+            .last()
+            .map(|value| value.trim().parse().ok())
+            .into_iter()
+            .collect(),
+    };
+    let mut client_ip = peer_ip;
+    for hop in hops.into_iter().rev() {
+        // An address that can't be read can't be trusted either, so the search stops there.
+        let Some(hop) = hop else {
+            break;
+        };
+        client_ip = hop;
+        if !is_trusted(hop) {
+            break;
+        }
+    }
+    client_ip
+}
+
+fn header_values<'a>(headers: &'a HeaderMap, name: &str) -> impl Iterator<Item = &'a str> {
+    headers
+        .get_all(name)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+}
+
+/// Reads the `for` parameter of a `Forwarded` header element, e.g. `for=192.0.2.60;proto=http`
+/// or `for="[2001:db8:cafe::17]:4711"`.
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    let node = element.split(';').find_map(|pair| {
+        let (name, value) = pair.trim().split_once('=')?;
+        name.eq_ignore_ascii_case("for").then_some(value)
+    })?;
+    let node = node.trim_matches('"');
+    if let Some(bracketed) = node.strip_prefix('[') {
+        return bracketed.split_once(']')?.0.parse().ok();
+    }
+    // IPv4 addresses may come with a port.
+    node.split(':').next()?.parse().ok()
+}
@@ -1,14 +1,25 @@
 use axum::{
-    extract::{MatchedPath, Request},
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
-use axum_client_ip::InsecureClientIp;
+use std::sync::Arc;
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+use crate::app_context::AppContext;
 use crate::auth::extractors::MaybeUser;
+use crate::http::client_ip::ClientIp;
+use crate::rate_limit::{KeyedRateLimiter, RateLimitKey};
+use crate::shutdown;
+use crate::storage::interface::IRoomStorage;
 
-pub async fn tracing(user: MaybeUser, request: Request, next: Next) -> Response {
+pub async fn tracing(
+    user: MaybeUser,
+    ClientIp(client_ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
     let method = request.method().clone();
     let path = request
         .extensions()
@@ -16,9 +27,6 @@ pub async fn tracing(user: MaybeUser, request: Request, next: Next) -> Response
         .map(|matched_path| matched_path.as_str())
         .unwrap_or(request.uri().path())
         .to_string();
-    let client_ip = InsecureClientIp::from(request.headers(), request.extensions())
-        .map(|InsecureClientIp(ip)| ip.to_string())
-        .unwrap_or_else(|_| "127.0.0.1".to_string());
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
@@ -40,3 +48,49 @@ pub async fn tracing(user: MaybeUser, request: Request, next: Next) -> Response
 
     response
 }
+
+/// Turns away requests that would start new rooms or sockets once the server is shutting down.
+pub async fn refuse_while_draining<RS>(
+    State(app_context): State<AppContext<RS>>,
+    request: Request,
+    next: Next,
+) -> Response
+where
+    RS: IRoomStorage,
+{
+    if !app_context.shutdown.is_draining() {
+        return next.run(request).await;
+    }
+    let retry_after_secs = shutdown::settings().reconnect_after.as_secs();
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, retry_after_secs.to_string())],
+    )
+        .into_response()
+}
+
+/// Counts requests against the limiter of the route group, keyed by the passcode's private ID or,
+/// without a passcode, by the client IP.
+pub async fn rate_limit(
+    State(limiter): State<Arc<KeyedRateLimiter>>,
+    user: MaybeUser,
+    ClientIp(client_ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = match user.private_id {
+        Some(private_id) => RateLimitKey::User(private_id),
+        None => RateLimitKey::ClientIp(client_ip.to_string()),
+    };
+    match limiter.check(key) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            let retry_after_secs = retry_after.as_secs_f64().ceil() as u64;
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_secs.max(1).to_string())],
+            )
+                .into_response()
+        }
+    }
+}
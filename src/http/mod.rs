@@ -1,4 +1,6 @@
+pub mod client_ip;
 pub mod cors;
+pub mod ip_range;
 pub mod middleware;
 pub mod requests;
 pub mod router;
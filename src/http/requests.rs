@@ -3,9 +3,20 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize)]
 pub struct PasscodeQueryParam {
     pub passcode: String,
+    #[serde(flatten)]
+    pub credentials: RoomCredentialsQueryParams,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct UsernameQueryParam {
     pub username: String,
+    #[serde(flatten)]
+    pub credentials: RoomCredentialsQueryParams,
+}
+
+/// Proof that the user is allowed into a private room.
+#[derive(Default, Serialize, Deserialize)]
+pub struct RoomCredentialsQueryParams {
+    pub password: Option<String>,
+    pub invite: Option<String>,
 }
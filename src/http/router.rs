@@ -1,8 +1,13 @@
 use crate::app_context::AppContext;
 use crate::cli::Args;
 use crate::storage::rooms::HashMapRoomsStorage;
-use crate::{auth, health, http::cors, rooms, uploads};
+use crate::{
+    admin, auth, cluster, health,
+    http::{client_ip, cors},
+    rooms, uploads,
+};
 use axum::extract::DefaultBodyLimit;
+use axum::middleware::from_fn_with_state;
 use axum::{
     routing::{any, get, post},
     Router,
@@ -10,10 +15,16 @@ use axum::{
 
 pub fn new(args: &Args, app_context: AppContext<HashMapRoomsStorage>) -> Router {
     let cors_policy = cors::layer(args);
+    client_ip::init(args);
     tracing::info!("Initialized HTTP configuration.");
 
     let health_routes = Router::new().route("/check", get(health::handlers::healthcheck));
-    let auth_routes = Router::new().route("/passcode/decode", get(auth::handlers::decode_passcode));
+    let auth_routes = Router::new()
+        .route("/passcode/decode", get(auth::handlers::decode_passcode))
+        .route_layer(from_fn_with_state(
+            app_context.rate_limits.auth.clone(),
+            crate::middleware::rate_limit,
+        ));
     let users_routes = Router::new()
         .route("/", get(rooms::handlers::room::users))
         .route(
@@ -24,25 +35,84 @@ pub fn new(args: &Args, app_context: AppContext<HashMapRoomsStorage>) -> Router
             "/:user-id/unmute",
             get(rooms::handlers::host_actions::unmute_user),
         )
+        .route(
+            "/:user-id/kick",
+            post(rooms::handlers::host_actions::kick_user),
+        )
         .route(
             "/:user-id/ban",
             post(rooms::handlers::host_actions::ban_user),
         )
+        .route(
+            "/:user-id/unban",
+            post(rooms::handlers::host_actions::unban_user),
+        )
         .route(
             "/:user-id/change-score",
             post(rooms::handlers::host_actions::change_user_score),
+        )
+        .route(
+            "/:user-id/role",
+            post(rooms::handlers::host_actions::change_user_role),
         );
     let messages_routes = Router::new().route("/", get(rooms::handlers::room::messages));
-    let rooms_routes = Router::new()
+    let room_creation_routes = Router::new()
         .route("/", post(rooms::handlers::room::create))
+        .route("/quick-play", post(rooms::handlers::room::quick_play))
+        .route_layer(from_fn_with_state(
+            app_context.rate_limits.room_creation.clone(),
+            crate::middleware::rate_limit,
+        ));
+    // Routes that lead to new rooms or sockets, closed once the server starts shutting down.
+    let joining_routes = Router::new()
+        .merge(room_creation_routes)
         .route(
             "/:room-id/can-connect",
             get(rooms::handlers::permissions::can_connect_to_room),
         )
+        .route("/:room-id/ws", any(rooms::handlers::ws::ws))
+        .route_layer(from_fn_with_state(
+            app_context.clone(),
+            crate::middleware::refuse_while_draining,
+        ));
+    let rooms_routes = Router::new()
+        .route("/", get(rooms::handlers::room::list_public))
         .route(
             "/:room-id/am-i-host",
             get(rooms::handlers::permissions::is_host),
         )
+        .route(
+            "/:room-id/access",
+            post(rooms::handlers::host_actions::change_room_access),
+        )
+        .route(
+            "/:room-id/listing",
+            post(rooms::handlers::host_actions::change_room_listing),
+        )
+        .route(
+            "/:room-id/max-players",
+            post(rooms::handlers::host_actions::change_max_players),
+        )
+        .route(
+            "/:room-id/lock",
+            post(rooms::handlers::host_actions::lock_room),
+        )
+        .route(
+            "/:room-id/unlock",
+            post(rooms::handlers::host_actions::unlock_room),
+        )
+        .route(
+            "/:room-id/invites",
+            post(rooms::handlers::host_actions::create_invite),
+        )
+        .route(
+            "/:room-id/bans",
+            get(rooms::handlers::host_actions::list_bans),
+        )
+        .route(
+            "/:room-id/co-host-permissions",
+            post(rooms::handlers::host_actions::change_co_host_permissions),
+        )
         .route(
             "/:room-id/save-guess",
             post(rooms::handlers::player_actions::save_guess),
@@ -57,13 +127,44 @@ pub fn new(args: &Args, app_context: AppContext<HashMapRoomsStorage>) -> Router
         )
         .nest("/:room-id/users", users_routes)
         .nest("/:room-id/messages", messages_routes)
-        .route("/:room-id/ws", any(rooms::handlers::ws::ws));
+        .merge(joining_routes)
+        .route_layer(from_fn_with_state(
+            app_context.clone(),
+            cluster::proxy::forward_to_room_owner,
+        ))
+        .route_layer(from_fn_with_state(
+            app_context.rate_limits.rooms.clone(),
+            crate::middleware::rate_limit,
+        ));
+    let admin_routes = Router::new()
+        .route("/rooms", get(admin::handlers::rooms))
+        .route("/rooms/:room-id", get(admin::handlers::room_details))
+        .route("/rooms/:room-id/close", post(admin::handlers::close_room))
+        .route(
+            "/users/:user-id/kick",
+            post(admin::handlers::kick_user_everywhere),
+        )
+        .route("/announcements", post(admin::handlers::announce))
+        .route(
+            "/bans",
+            get(admin::handlers::server_bans).post(admin::handlers::ban),
+        )
+        .route("/bans/:ban-id/lift", post(admin::handlers::lift_ban))
+        .route(
+            "/rooms/:room-id/snapshot",
+            get(admin::handlers::room_snapshot),
+        )
+        .route("/rooms/restore", post(admin::handlers::restore_room));
     let uploads_routes = Router::new()
         .route("/images", post(uploads::handlers::upload_images))
         .route(
             "/attachment-links",
             post(uploads::handlers::attachment_links),
         )
+        .route_layer(from_fn_with_state(
+            app_context.rate_limits.uploads.clone(),
+            crate::middleware::rate_limit,
+        ))
         // TODO: make this configurable
         .layer(DefaultBodyLimit::max(10_000_000));
 
@@ -72,6 +173,7 @@ pub fn new(args: &Args, app_context: AppContext<HashMapRoomsStorage>) -> Router
         .nest("/auth", auth_routes)
         .nest("/rooms", rooms_routes)
         .nest("/uploads", uploads_routes)
+        .nest("/admin", admin_routes)
         .with_state(app_context)
         .layer(cors_policy)
         .layer(axum::middleware::from_fn(crate::middleware::tracing))
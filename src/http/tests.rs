@@ -1,12 +1,110 @@
+use crate::auth::tests::PASSCODE;
 use crate::cli::tests::fake_args;
+use crate::http::client_ip::{self, ClientIpHeader, ClientIpSettings};
+use crate::http::ip_range::IpRange;
 use crate::http::router;
-use crate::{app_context, auth};
+use crate::rate_limit::RateLimitsConfig;
+use crate::{app_context, auth, bans, rooms};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use axum_test::TestServer;
+use serde_json::json;
+use std::net::IpAddr;
+use std::str::FromStr;
 
 pub fn test_server() -> TestServer {
     let args = fake_args();
     auth::init(&args);
-    let app_context = app_context::init();
+    bans::init(&args);
+    rooms::init(&args);
+    let app_context = app_context::init(&args);
     let router = router::new(&args, app_context);
     TestServer::new(router).expect("Failed to run test server.")
 }
+
+fn client_ip_settings(header: ClientIpHeader) -> ClientIpSettings {
+    ClientIpSettings {
+        trusted_proxies: vec![IpRange::from_str("10.0.0.0/8").unwrap()],
+        header,
+    }
+}
+
+fn ip(ip: &str) -> IpAddr {
+    IpAddr::from_str(ip).unwrap()
+}
+
+#[test]
+fn test_forwarding_headers_from_untrusted_peers_are_ignored() {
+    let mut headers = HeaderMap::new();
+    headers.insert("X-Forwarded-For", HeaderValue::from_static("192.0.2.1"));
+
+    let client_ip = client_ip::resolve(
+        &client_ip_settings(ClientIpHeader::XForwardedFor),
+        ip("198.51.100.7"),
+        &headers,
+    );
+
+    assert_eq!(client_ip, ip("198.51.100.7"));
+}
+
+#[test]
+fn test_client_ip_is_the_last_untrusted_hop() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "X-Forwarded-For",
+        HeaderValue::from_static("192.0.2.1, 198.51.100.7, 10.0.0.2"),
+    );
+
+    let client_ip = client_ip::resolve(
+        &client_ip_settings(ClientIpHeader::XForwardedFor),
+        ip("10.0.0.1"),
+        &headers,
+    );
+
+    assert_eq!(client_ip, ip("198.51.100.7"));
+}
+
+#[test]
+fn test_client_ip_is_read_from_forwarded_header() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Forwarded",
+        HeaderValue::from_static("for=\"[2001:db8:cafe::17]:4711\";proto=https, for=10.0.0.2"),
+    );
+
+    let client_ip = client_ip::resolve(
+        &client_ip_settings(ClientIpHeader::Forwarded),
+        ip("10.0.0.1"),
+        &headers,
+    );
+
+    assert_eq!(client_ip, ip("2001:db8:cafe::17"));
+}
+
+#[tokio::test]
+async fn test_room_creation_over_quota_is_refused() {
+    let server = test_server();
+    let burst = RateLimitsConfig::default().room_creation.burst;
+    for _ in 0..burst {
+        server
+            .post("/rooms")
+            .add_header("Passcode", PASSCODE)
+            .json(&json!({ "public": false }))
+            .await
+            .assert_status_ok();
+    }
+
+    let response = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .json(&json!({ "public": false }))
+        .await;
+
+    response.assert_status(StatusCode::TOO_MANY_REQUESTS);
+    let retry_after_secs: u64 = response
+        .header(header::RETRY_AFTER)
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after_secs >= 1);
+}
@@ -12,6 +12,8 @@ pub fn init(args: &Args) {
             .map_marker_to_index("http_request", "http_requests")
             .map_marker_to_index("client_sent_ws_message", "client_sent_ws_messages")
             .map_marker_to_index("sockets_count", "sockets_counts")
+            .map_marker_to_index("rooms_count", "rooms_counts")
+            .map_marker_to_index("socket_queue_depth", "socket_queue_depths")
             .with_batch_size(100)
             .build();
     tokio::spawn(quickwit_background_client_task);
@@ -1,15 +1,22 @@
 use crate::cli::Args;
 use crate::http::middleware;
 use clap::Parser;
+use std::net::SocketAddr;
 
+mod admin;
 mod app_context;
 mod auth;
+mod bans;
 mod cli;
+mod cluster;
 mod health;
 mod http;
 mod logging;
 mod map;
+mod moderation;
+mod rate_limit;
 mod rooms;
+mod shutdown;
 mod storage;
 mod uploads;
 mod users;
@@ -24,16 +31,28 @@ async fn main() {
     auth::init(&args);
     tracing::info!("Initialized HMAC code.");
 
-    let app_context = app_context::init();
+    bans::init(&args);
+    tracing::info!("Initialized server-wide bans.");
+
+    let app_context = app_context::init(&args);
     tracing::info!("Initialized app context.");
 
     map::init(&args);
     tracing::info!("Initialized map data.");
 
-    uploads::init(&args);
-    tracing::info!("Initialized map data.");
+    rooms::init(&args);
+    tracing::info!("Initialized rooms settings.");
+
+    moderation::init(&args);
+    tracing::info!("Initialized chat moderation.");
 
-    let routes = http::router::new(&args, app_context);
+    uploads::init(&args, app_context.clone());
+    tracing::info!("Initialized uploads.");
+
+    shutdown::init(&args);
+    tracing::info!("Initialized shutdown settings.");
+
+    let routes = http::router::new(&args, app_context.clone());
 
     let listener = tokio::net::TcpListener::bind(args.listen_address)
         .await
@@ -41,7 +60,14 @@ async fn main() {
 
     tracing::info!("Initialization completed, starting serving...");
 
-    axum::serve(listener, routes)
-        .await
-        .expect("Failed to run the app.");
+    axum::serve(
+        listener,
+        routes.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown::signal().await;
+        shutdown::drain(app_context).await;
+    })
+    .await
+    .expect("Failed to run the app.");
 }
@@ -0,0 +1,6 @@
+use std::time::Duration;
+
+/// Rejected messages older than this don't count towards the auto-mute threshold.
+pub const VIOLATIONS_WINDOW: Duration = Duration::from_secs(60);
+
+pub const MASK_CHARACTER: char = '*';
@@ -0,0 +1,43 @@
+use super::{IncomingChatMessage, ModerationStage, RejectionReason};
+use crate::rate_limit::MAX_TRACKED_KEYS;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Rejects a message if the same user has sent the same text to the same room within `window`.
+pub struct DuplicateSuppressor {
+    window: Duration,
+    last_messages: Mutex<HashMap<(String, String), (String, Instant)>>,
+}
+
+impl DuplicateSuppressor {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_messages: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ModerationStage for DuplicateSuppressor {
+    fn check(&self, message: &mut IncomingChatMessage) -> Result<(), RejectionReason> {
+        let now = Instant::now();
+        let mut last_messages = self.last_messages.lock().unwrap();
+        if last_messages.len() >= MAX_TRACKED_KEYS {
+            last_messages
+                .retain(|_author, (_content, sent_at)| now.duration_since(*sent_at) < self.window);
+        }
+        let key = (
+            message.room_id.to_string(),
+            message.author_public_id.to_string(),
+        );
+        let normalized_content = message.content.trim().to_lowercase();
+        if let Some((last_content, sent_at)) = last_messages.get(&key) {
+            if *last_content == normalized_content && now.duration_since(*sent_at) < self.window {
+                return Err(RejectionReason::Duplicate);
+            }
+        }
+        last_messages.insert(key, (normalized_content, now));
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use crate::cli::Args;
+use crate::rate_limit::MAX_TRACKED_KEYS;
+use consts::VIOLATIONS_WINDOW;
+use duplicates::DuplicateSuppressor;
+use rate_limit::RateLimiter;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use word_filter::WordListFilter;
+
+pub mod consts;
+pub mod duplicates;
+pub mod rate_limit;
+#[cfg(test)]
+pub mod tests;
+pub mod word_filter;
+
+static PIPELINE: OnceLock<ModerationPipeline> = OnceLock::new();
+
+pub fn init(args: &Args) {
+    PIPELINE.get_or_init(|| {
+        let mut pipeline = ModerationPipeline::new(args.chat_auto_mute_violations);
+        if let Some(path) = &args.chat_banned_words {
+            pipeline = pipeline.with_stage(WordListFilter::from_file(
+                path,
+                args.chat_banned_words_action,
+            ));
+        }
+        pipeline
+            .with_stage(RateLimiter::new(
+                args.chat_messages_per_minute,
+                args.chat_messages_burst,
+            ))
+            .with_stage(DuplicateSuppressor::new(Duration::from_secs(
+                args.chat_duplicate_window_secs,
+            )))
+    });
+}
+
+pub fn pipeline() -> &'static ModerationPipeline {
+    PIPELINE
+        .get()
+        .expect("Somehow the moderation pipeline is used before `init`.")
+}
+
+/// A chat message on its way to the room.
+pub struct IncomingChatMessage<'a> {
+    pub room_id: &'a str,
+    pub author_public_id: &'a str,
+    /// Stages may rewrite it, e.g. to mask some words.
+    pub content: String,
+}
+
+/// A single check that chat messages go through. Stages run in the order they were added to the
+/// pipeline and the first rejection stops the message.
+pub trait ModerationStage: Send + Sync {
+    fn check(&self, message: &mut IncomingChatMessage) -> Result<(), RejectionReason>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RejectionReason {
+    BannedWords,
+    RateLimited,
+    Duplicate,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ModerationOutcome {
+    Accepted {
+        content: String,
+    },
+    Rejected {
+        reason: RejectionReason,
+        /// Set once the author has had too many messages rejected lately.
+        mute_author: bool,
+    },
+}
+
+pub struct ModerationPipeline {
+    stages: Vec<Box<dyn ModerationStage>>,
+    /// How many rejections within `VIOLATIONS_WINDOW` get the author muted; never if zero.
+    auto_mute_violations: usize,
+    violations: Mutex<HashMap<(String, String), VecDeque<Instant>>>,
+}
+
+impl ModerationPipeline {
+    pub fn new(auto_mute_violations: usize) -> Self {
+        Self {
+            stages: vec![],
+            auto_mute_violations,
+            violations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_stage(mut self, stage: impl ModerationStage + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    pub fn moderate(
+        &self,
+        room_id: &str,
+        author_public_id: &str,
+        content: String,
+    ) -> ModerationOutcome {
+        let mut message = IncomingChatMessage {
+            room_id,
+            author_public_id,
+            content,
+        };
+        for stage in self.stages.iter() {
+            if let Err(reason) = stage.check(&mut message) {
+                return ModerationOutcome::Rejected {
+                    reason,
+                    mute_author: self.record_violation(room_id, author_public_id),
+                };
+            }
+        }
+        ModerationOutcome::Accepted {
+            content: message.content,
+        }
+    }
+
+    /// Returns whether the author has reached the auto-mute threshold.
+    fn record_violation(&self, room_id: &str, author_public_id: &str) -> bool {
+        if self.auto_mute_violations == 0 {
+            return false;
+        }
+        let now = Instant::now();
+        let mut violations = self.violations.lock().unwrap();
+        if violations.len() >= MAX_TRACKED_KEYS {
+            violations.retain(|_author, timestamps| {
+                timestamps
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) < VIOLATIONS_WINDOW)
+            });
+        }
+        let timestamps = violations
+            .entry((room_id.to_string(), author_public_id.to_string()))
+            .or_default();
+        timestamps.retain(|timestamp| now.duration_since(*timestamp) < VIOLATIONS_WINDOW);
+        timestamps.push_back(now);
+        if timestamps.len() >= self.auto_mute_violations {
+            timestamps.clear();
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,28 @@
+use super::{IncomingChatMessage, ModerationStage, RejectionReason};
+use crate::rate_limit::{KeyedRateLimiter, Quota};
+
+/// Token bucket per user per room: `burst` messages can be sent at once, after which the
+/// allowance refills at `messages_per_minute`.
+pub struct RateLimiter {
+    limiter: KeyedRateLimiter<(String, String)>,
+}
+
+impl RateLimiter {
+    pub fn new(messages_per_minute: u32, burst: u32) -> Self {
+        Self {
+            limiter: KeyedRateLimiter::new(Quota::new(messages_per_minute, burst)),
+        }
+    }
+}
+
+impl ModerationStage for RateLimiter {
+    fn check(&self, message: &mut IncomingChatMessage) -> Result<(), RejectionReason> {
+        let key = (
+            message.room_id.to_string(),
+            message.author_public_id.to_string(),
+        );
+        self.limiter
+            .check(key)
+            .map_err(|_retry_after| RejectionReason::RateLimited)
+    }
+}
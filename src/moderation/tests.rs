@@ -0,0 +1,68 @@
+use crate::moderation::duplicates::DuplicateSuppressor;
+use crate::moderation::rate_limit::RateLimiter;
+use crate::moderation::word_filter::{WordFilterAction, WordListFilter};
+use crate::moderation::{ModerationOutcome, ModerationPipeline, RejectionReason};
+use std::time::Duration;
+
+#[test]
+fn test_banned_words_are_masked() {
+    let pipeline = ModerationPipeline::new(0).with_stage(WordListFilter::new(
+        [String::from("Heck")],
+        WordFilterAction::Mask,
+    ));
+
+    let outcome = pipeline.moderate("room", "user", String::from("What the heck, HECK!"));
+
+    assert_eq!(
+        outcome,
+        ModerationOutcome::Accepted {
+            content: String::from("What the ****, ****!")
+        }
+    );
+}
+
+#[test]
+fn test_rate_limit_allows_burst_then_rejects() {
+    let pipeline = ModerationPipeline::new(0).with_stage(RateLimiter::new(1, 2));
+
+    let first = pipeline.moderate("room", "user", String::from("one"));
+    let second = pipeline.moderate("room", "user", String::from("two"));
+    let third = pipeline.moderate("room", "user", String::from("three"));
+    let other_user = pipeline.moderate("room", "another user", String::from("one"));
+
+    assert!(matches!(first, ModerationOutcome::Accepted { .. }));
+    assert!(matches!(second, ModerationOutcome::Accepted { .. }));
+    assert_eq!(
+        third,
+        ModerationOutcome::Rejected {
+            reason: RejectionReason::RateLimited,
+            mute_author: false,
+        }
+    );
+    assert!(matches!(other_user, ModerationOutcome::Accepted { .. }));
+}
+
+#[test]
+fn test_repeated_duplicates_get_author_muted() {
+    let pipeline =
+        ModerationPipeline::new(2).with_stage(DuplicateSuppressor::new(Duration::from_secs(60)));
+
+    pipeline.moderate("room", "user", String::from("spam"));
+    let first_duplicate = pipeline.moderate("room", "user", String::from("spam"));
+    let second_duplicate = pipeline.moderate("room", "user", String::from(" SPAM "));
+
+    assert_eq!(
+        first_duplicate,
+        ModerationOutcome::Rejected {
+            reason: RejectionReason::Duplicate,
+            mute_author: false,
+        }
+    );
+    assert_eq!(
+        second_duplicate,
+        ModerationOutcome::Rejected {
+            reason: RejectionReason::Duplicate,
+            mute_author: true,
+        }
+    );
+}
@@ -0,0 +1,76 @@
+use super::consts::MASK_CHARACTER;
+use super::{IncomingChatMessage, ModerationStage, RejectionReason};
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum WordFilterAction {
+    /// Replace the banned words with asterisks.
+    Mask,
+    /// Drop the whole message.
+    Reject,
+}
+
+pub struct WordListFilter {
+    /// Lowercase.
+    banned_words: HashSet<String>,
+    action: WordFilterAction,
+}
+
+impl WordListFilter {
+    pub fn new(banned_words: impl IntoIterator<Item = String>, action: WordFilterAction) -> Self {
+        Self {
+            banned_words: banned_words
+                .into_iter()
+                .map(|word| word.to_lowercase())
+                .collect(),
+            action,
+        }
+    }
+
+    /// Reads a file with one word per line. Empty lines and lines starting with `#` are skipped.
+    pub fn from_file(path: &Path, action: WordFilterAction) -> Self {
+        let contents = fs::read_to_string(path).expect("Failed to read the banned words file.");
+        let banned_words = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from);
+        Self::new(banned_words, action)
+    }
+
+    fn is_banned(&self, word: &str) -> bool {
+        self.banned_words.contains(&word.to_lowercase())
+    }
+}
+
+impl ModerationStage for WordListFilter {
+    fn check(&self, message: &mut IncomingChatMessage) -> Result<(), RejectionReason> {
+        if !message
+            .content
+            .split_word_bounds()
+            .any(|word| self.is_banned(word))
+        {
+            return Ok(());
+        }
+        match self.action {
+            WordFilterAction::Reject => Err(RejectionReason::BannedWords),
+            WordFilterAction::Mask => {
+                message.content = message
+                    .content
+                    .split_word_bounds()
+                    .map(|word| match self.is_banned(word) {
+                        true => MASK_CHARACTER
+                            .to_string()
+                            .repeat(word.graphemes(true).count()),
+                        false => word.to_string(),
+                    })
+                    .collect();
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::cli::Args;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+pub mod tests;
+
+/// How many keys a limiter (or any other per-user state) tracks before forgetting the ones that
+/// are no longer relevant.
+pub const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Request quotas per group of routes, read from the `--rate-limits` JSON file. Groups missing
+/// from the file keep their defaults.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct RateLimitsConfig {
+    pub auth: Quota,
+    /// Every route under `/rooms`, room creation and WebSocket upgrades included.
+    pub rooms: Quota,
+    /// Creating rooms and quick play, on top of the `rooms` quota.
+    pub room_creation: Quota,
+    pub uploads: Quota,
+    /// Messages sent over a room's WebSocket.
+    pub ws_messages: Quota,
+}
+
+impl Default for RateLimitsConfig {
+    fn default() -> Self {
+        Self {
+            auth: Quota::new(60, 20),
+            rooms: Quota::new(600, 120),
+            room_creation: Quota::new(20, 5),
+            uploads: Quota::new(30, 10),
+            ws_messages: Quota::new(300, 60),
+        }
+    }
+}
+
+impl RateLimitsConfig {
+    pub fn load(args: &Args) -> Self {
+        match &args.rate_limits {
+            Some(path) => {
+                let contents =
+                    fs::read_to_string(path).expect("Failed to read the rate limits file.");
+                serde_json::from_str(&contents).expect("Failed to parse the rate limits file.")
+            }
+            None => Self::default(),
+        }
+    }
+}
+
+/// `burst` requests can be made at once, after which the allowance refills at
+/// `requests_per_minute`. No limit if `requests_per_minute` is zero.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quota {
+    pub requests_per_minute: u32,
+    pub burst: u32,
+}
+
+impl Quota {
+    pub fn new(requests_per_minute: u32, burst: u32) -> Self {
+        Self {
+            requests_per_minute,
+            burst,
+        }
+    }
+}
+
+/// Identifies whose requests count towards the same bucket.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// The passcode's private ID, for requests that carry one.
+    User(String),
+    ClientIp(String),
+}
+
+/// One limiter per group of routes, shared by all the copies of the app context.
+#[derive(Clone)]
+pub struct RateLimits {
+    pub auth: Arc<KeyedRateLimiter>,
+    pub rooms: Arc<KeyedRateLimiter>,
+    pub room_creation: Arc<KeyedRateLimiter>,
+    pub uploads: Arc<KeyedRateLimiter>,
+    pub ws_messages: Arc<KeyedRateLimiter>,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitsConfig) -> Self {
+        Self {
+            auth: Arc::new(KeyedRateLimiter::new(config.auth)),
+            rooms: Arc::new(KeyedRateLimiter::new(config.rooms)),
+            room_creation: Arc::new(KeyedRateLimiter::new(config.room_creation)),
+            uploads: Arc::new(KeyedRateLimiter::new(config.uploads)),
+            ws_messages: Arc::new(KeyedRateLimiter::new(config.ws_messages)),
+        }
+    }
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self::new(&RateLimitsConfig::default())
+    }
+}
+
+/// Token bucket per key.
+pub struct KeyedRateLimiter<K = RateLimitKey> {
+    quota: Quota,
+    buckets: Mutex<Buckets<K>>,
+}
+
+struct Buckets<K> {
+    by_key: HashMap<K, TokenBucket>,
+    /// The size at which the full buckets get dropped next.
+    evict_at: usize,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl<K: Eq + Hash> KeyedRateLimiter<K> {
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            quota: Quota {
+                burst: quota.burst.max(1),
+                ..quota
+            },
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                evict_at: MAX_TRACKED_KEYS,
+            }),
+        }
+    }
+
+    /// Takes a token from the key's bucket, or tells how long to wait until there is one.
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        if self.quota.requests_per_minute == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.by_key.len() >= buckets.evict_at {
+            // Full buckets are no different from the ones that haven't been created yet.
+            buckets.by_key.retain(|_key, bucket| {
+                self.refill(bucket, now);
+                bucket.tokens < f64::from(self.quota.burst)
+            });
+            // Waiting for the map to double before scanning it again keeps the cost per call
+            // constant on average, even when none of the buckets can be dropped.
+            buckets.evict_at = (buckets.by_key.len() * 2).max(MAX_TRACKED_KEYS);
+        }
+        let bucket = buckets.by_key.entry(key).or_insert_with(|| TokenBucket {
+            tokens: f64::from(self.quota.burst),
+            refilled_at: now,
+        });
+        self.refill(bucket, now);
+        if bucket.tokens < 1.0 {
+            let missing_secs = (1.0 - bucket.tokens) * 60.0 / self.tokens_per_minute();
+            return Err(Duration::from_secs_f64(missing_secs));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    fn tokens_per_minute(&self) -> f64 {
+        f64::from(self.quota.requests_per_minute)
+    }
+
+    fn refill(&self, bucket: &mut TokenBucket, now: Instant) {
+        let elapsed_secs = now.duration_since(bucket.refilled_at).as_secs_f64();
+        let refill = elapsed_secs * self.tokens_per_minute() / 60.0;
+        bucket.tokens = (bucket.tokens + refill).min(f64::from(self.quota.burst));
+        bucket.refilled_at = now;
+    }
+}
@@ -0,0 +1,48 @@
+use crate::rate_limit::{
+    KeyedRateLimiter, Quota, RateLimitKey, RateLimitsConfig, MAX_TRACKED_KEYS,
+};
+use std::time::Duration;
+
+#[test]
+fn test_limiter_refuses_requests_over_the_burst() {
+    let limiter = KeyedRateLimiter::new(Quota::new(60, 2));
+    let key = RateLimitKey::ClientIp(String::from("203.0.113.7"));
+    assert_eq!(limiter.check(key.clone()), Ok(()));
+    assert_eq!(limiter.check(key.clone()), Ok(()));
+    let retry_after = limiter.check(key).unwrap_err();
+    assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(1));
+    let other_key = RateLimitKey::User(String::from("somePrivateId"));
+    assert_eq!(limiter.check(other_key), Ok(()));
+}
+
+#[test]
+fn test_zero_requests_per_minute_means_no_limit() {
+    let limiter = KeyedRateLimiter::new(Quota::new(0, 1));
+    for _ in 0..10 {
+        assert_eq!(
+            limiter.check(RateLimitKey::ClientIp(String::from("203.0.113.7"))),
+            Ok(())
+        );
+    }
+}
+
+#[test]
+fn test_config_groups_missing_from_file_keep_defaults() {
+    let config: RateLimitsConfig =
+        serde_json::from_str(r#"{"uploads": {"requestsPerMinute": 5, "burst": 1}}"#).unwrap();
+    assert_eq!(config.uploads, Quota::new(5, 1));
+    assert_eq!(config.auth, RateLimitsConfig::default().auth);
+}
+
+#[test]
+fn test_limiter_waits_for_the_map_to_double_before_evicting_again() {
+    let limiter = KeyedRateLimiter::new(Quota::new(1, 2));
+    for key in 0..=MAX_TRACKED_KEYS {
+        assert_eq!(limiter.check(key), Ok(()));
+    }
+
+    let buckets = limiter.buckets.lock().unwrap();
+    // None of the buckets are full again yet, so they all have to be kept.
+    assert_eq!(buckets.by_key.len(), MAX_TRACKED_KEYS + 1);
+    assert_eq!(buckets.evict_at, 2 * MAX_TRACKED_KEYS);
+}
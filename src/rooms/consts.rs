@@ -1,3 +1,19 @@
+use crate::users::models::Permission;
+
 pub const MAX_USERNAME_LENGTH: usize = 20;
 pub const MAX_MESSAGE_LENGTH: usize = 500;
+pub const MAX_REACTION_BYTES: usize = 32;
+pub const MAX_DISTINCT_REACTIONS_PER_MESSAGE: usize = 20;
+/// How many of the latest events each room keeps for replaying to reconnecting users.
+pub const EVENT_LOG_CAPACITY: usize = 1000;
 pub const ROUNDS_PER_GAME: u64 = 5;
+/// The round timer counts down from this, a tick per second.
+pub const ROUND_DURATION_TICKS: i32 = 100;
+pub const DEFAULT_INVITE_VALIDITY_SECS: u64 = 24 * 60 * 60;
+pub const MAX_ROOM_DISPLAY_NAME_LENGTH: usize = 40;
+pub const QUICK_PLAY_ROOM_DISPLAY_NAME: &str = "Quick play";
+pub const DEFAULT_MAX_PLAYERS: usize = 10;
+pub const MAX_PLAYERS_LIMIT: usize = 50;
+pub const MAX_BAN_DURATION_SECS: u64 = 365 * 24 * 60 * 60;
+pub const DEFAULT_CO_HOST_PERMISSIONS: [Permission; 3] =
+    [Permission::Mute, Permission::Kick, Permission::StartRound];
@@ -0,0 +1,80 @@
+use crate::rooms::consts::EVENT_LOG_CAPACITY;
+use std::collections::VecDeque;
+
+/// The latest events broadcast in a room, numbered in the order they were sent, so that users
+/// that come back after losing their connection can catch up.
+#[derive(Clone, Debug)]
+pub struct EventLog {
+    last_seq: u64,
+    entries: VecDeque<LoggedEvent>,
+}
+
+#[derive(Clone, Debug)]
+struct LoggedEvent {
+    seq: u64,
+    /// Public IDs of the users that were connected but weren't meant to get the event, e.g. the
+    /// author of a chat message. It isn't replayed to them either.
+    left_out: Vec<String>,
+    raw_event: String,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            last_seq: 0,
+            entries: VecDeque::with_capacity(EVENT_LOG_CAPACITY),
+        }
+    }
+}
+
+impl EventLog {
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Returns the event with its sequence number added.
+    pub fn record(&mut self, raw_event: &str, left_out: Vec<String>) -> String {
+        self.last_seq += 1;
+        let raw_event = with_seq(raw_event, self.last_seq);
+        if self.entries.len() >= EVENT_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LoggedEvent {
+            seq: self.last_seq,
+            left_out,
+            raw_event: raw_event.clone(),
+        });
+        raw_event
+    }
+
+    /// Returns the events the user missed after `last_seen_seq`, or `None` if some of them are
+    /// gone from the log and the user has to fetch the whole room state again.
+    pub fn events_since(&self, last_seen_seq: u64, public_id: &str) -> Option<Vec<String>> {
+        if last_seen_seq > self.last_seq {
+            // Numbering from a room that no longer exists, e.g. before a restart.
+            return None;
+        }
+        let oldest_seq = self
+            .entries
+            .front()
+            .map_or(self.last_seq + 1, |event| event.seq);
+        if last_seen_seq + 1 < oldest_seq {
+            return None;
+        }
+        let missed_events = self
+            .entries
+            .iter()
+            .filter(|event| event.seq > last_seen_seq)
+            .filter(|event| !event.left_out.iter().any(|id| id == public_id))
+            .map(|event| event.raw_event.clone())
+            .collect();
+        Some(missed_events)
+    }
+}
+
+/// Adds the `seq` field to a serialized event. All server-sent events are JSON objects with at
+/// least the `type` field, so the number can be spliced in without parsing the event.
+fn with_seq(raw_event: &str, seq: u64) -> String {
+    debug_assert!(raw_event.starts_with('{') && raw_event.len() > 2);
+    format!("{{\"seq\":{seq},{}", &raw_event[1..])
+}
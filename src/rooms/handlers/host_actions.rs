@@ -1,14 +1,26 @@
 use crate::app_context::{AppContext, RequestContext};
 use crate::auth::extractors::User;
+use crate::rooms::consts::DEFAULT_INVITE_VALIDITY_SECS;
+use crate::rooms::services::http::RoomHttpHandler;
+use crate::rooms::services::responses::{
+    ChangeCoHostPermissionsResponse, ChangeMaxPlayersResponse, ChangeRoomAccessResponse,
+    ChangeRoomListingResponse, CreateInviteResponse, LockRoomResponse, RoomBansResponse,
+    UnlockRoomResponse,
+};
 use crate::storage::interface::IRoomStorage;
 use crate::users::handlers::UsersHttpHandler;
 use crate::users::responses::{
-    BanUserResponse, ChangeScoreResponse, MuteUserResponse, UnmuteUserResponse,
+    BanUserResponse, ChangeRoleResponse, ChangeScoreResponse, KickUserResponse, MuteUserResponse,
+    UnbanUserResponse, UnmuteUserResponse,
 };
 use axum::extract::{Path, State};
 use axum::response::Json;
+use std::time::Duration;
 
-use super::requests::ScoreChangeRequestBody;
+use super::requests::{
+    BanRequestBody, CoHostPermissionsRequestBody, CreateInviteRequestBody, MaxPlayersRequestBody,
+    RoomAccessRequestBody, RoomListingRequestBody, ScoreChangeRequestBody, UserRoleRequestBody,
+};
 
 pub async fn mute_user<RS>(
     user: User,
@@ -48,10 +60,30 @@ where
     Json(response)
 }
 
+pub async fn kick_user<RS>(
+    user: User,
+    Path((room_id, user_id)): Path<(String, String)>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<KickUserResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = UsersHttpHandler::new(app_context, &request_context)
+        .kick(user_id)
+        .await;
+    Json(response)
+}
+
 pub async fn ban_user<RS>(
     user: User,
     Path((room_id, user_id)): Path<(String, String)>,
     State(app_context): State<AppContext<RS>>,
+    body: Option<Json<BanRequestBody>>,
 ) -> Json<BanUserResponse>
 where
     RS: IRoomStorage,
@@ -61,8 +93,50 @@ where
         private_id: user.private_id,
         room_id,
     };
+    // The body is optional so that permanent bans can still be issued without one.
+    let duration = body
+        .and_then(|Json(body)| body.duration_secs)
+        .map(Duration::from_secs);
     let response = UsersHttpHandler::new(app_context, &request_context)
-        .ban(user_id)
+        .ban(user_id, duration)
+        .await;
+    Json(response)
+}
+
+pub async fn unban_user<RS>(
+    user: User,
+    Path((room_id, user_id)): Path<(String, String)>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<UnbanUserResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = UsersHttpHandler::new(app_context, &request_context)
+        .unban(user_id)
+        .await;
+    Json(response)
+}
+
+pub async fn list_bans<RS>(
+    user: User,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<RoomBansResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = RoomHttpHandler::new(app_context, &request_context)
+        .bans()
         .await;
     Json(response)
 }
@@ -86,3 +160,163 @@ where
         .await;
     Json(response)
 }
+
+pub async fn change_user_role<RS>(
+    user: User,
+    Path((room_id, user_id)): Path<(String, String)>,
+    State(app_context): State<AppContext<RS>>,
+    Json(body): Json<UserRoleRequestBody>,
+) -> Json<ChangeRoleResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = UsersHttpHandler::new(app_context, &request_context)
+        .change_role(user_id, body.role)
+        .await;
+    Json(response)
+}
+
+pub async fn change_room_access<RS>(
+    user: User,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+    Json(body): Json<RoomAccessRequestBody>,
+) -> Json<ChangeRoomAccessResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = RoomHttpHandler::new(app_context, &request_context)
+        .change_access(body.private, body.password)
+        .await;
+    Json(response)
+}
+
+pub async fn create_invite<RS>(
+    user: User,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+    Json(body): Json<CreateInviteRequestBody>,
+) -> Json<CreateInviteResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let valid_for =
+        Duration::from_secs(body.valid_for_secs.unwrap_or(DEFAULT_INVITE_VALIDITY_SECS));
+    let response = RoomHttpHandler::new(app_context, &request_context)
+        .create_invite(valid_for)
+        .await;
+    Json(response)
+}
+
+pub async fn change_room_listing<RS>(
+    user: User,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+    Json(body): Json<RoomListingRequestBody>,
+) -> Json<ChangeRoomListingResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = RoomHttpHandler::new(app_context, &request_context)
+        .change_listing(body.public, body.display_name)
+        .await;
+    Json(response)
+}
+
+pub async fn change_max_players<RS>(
+    user: User,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+    Json(body): Json<MaxPlayersRequestBody>,
+) -> Json<ChangeMaxPlayersResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = RoomHttpHandler::new(app_context, &request_context)
+        .change_max_players(body.max_players)
+        .await;
+    Json(response)
+}
+
+pub async fn lock_room<RS>(
+    user: User,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<LockRoomResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = RoomHttpHandler::new(app_context, &request_context)
+        .lock()
+        .await;
+    Json(response)
+}
+
+pub async fn unlock_room<RS>(
+    user: User,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<UnlockRoomResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = RoomHttpHandler::new(app_context, &request_context)
+        .unlock()
+        .await;
+    Json(response)
+}
+
+pub async fn change_co_host_permissions<RS>(
+    user: User,
+    Path(room_id): Path<String>,
+    State(app_context): State<AppContext<RS>>,
+    Json(body): Json<CoHostPermissionsRequestBody>,
+) -> Json<ChangeCoHostPermissionsResponse>
+where
+    RS: IRoomStorage,
+{
+    let request_context = RequestContext {
+        public_id: user.public_id,
+        private_id: user.private_id,
+        room_id,
+    };
+    let response = RoomHttpHandler::new(app_context, &request_context)
+        .change_co_host_permissions(body.permissions)
+        .await;
+    Json(response)
+}
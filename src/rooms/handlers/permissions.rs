@@ -24,7 +24,7 @@ where
         room_id,
     };
     let response = RoomHttpHandler::new(app_context, &request_context)
-        .can_connect(query_params.username)
+        .can_connect(query_params.username, query_params.credentials)
         .await;
     Json(response)
 }
@@ -1,6 +1,65 @@
+use crate::users::models::{Permission, UserRole};
 use serde::Deserialize;
 
 #[derive(Deserialize)]
 pub struct ScoreChangeRequestBody {
     pub amount: i64,
 }
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BanRequestBody {
+    /// The ban is permanent if not set.
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomAccessRequestBody {
+    pub private: bool,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateInviteRequestBody {
+    pub valid_for_secs: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct CreateRoomRequestBody {
+    pub public: bool,
+    pub display_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomListingRequestBody {
+    pub public: bool,
+    pub display_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaxPlayersRequestBody {
+    pub max_players: usize,
+}
+
+#[derive(Deserialize)]
+pub struct UserRoleRequestBody {
+    pub role: UserRole,
+}
+
+#[derive(Deserialize)]
+pub struct CoHostPermissionsRequestBody {
+    pub permissions: Vec<Permission>,
+}
+
+/// Cursors are message IDs; neither of them has to exist anymore.
+#[derive(Deserialize)]
+pub struct MessagesPageQueryParams {
+    pub after: Option<usize>,
+    pub before: Option<usize>,
+    pub limit: Option<usize>,
+}
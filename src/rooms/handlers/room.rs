@@ -1,27 +1,65 @@
 use crate::app_context::{AppContext, RequestContext};
 use crate::auth::extractors::User;
-use crate::rooms::services::http::{CreateRoomHttpHandler, RoomHttpHandler};
+use crate::http::requests::RoomCredentialsQueryParams;
+use crate::rooms::handlers::requests::{CreateRoomRequestBody, MessagesPageQueryParams};
+use crate::rooms::services::http::{
+    CreateRoomHttpHandler, PublicRoomsHttpHandler, RoomHttpHandler,
+};
 use crate::rooms::services::responses::{
-    CreateRoomResponse, RoomMessagesResponse, RoomUsersResponse,
+    CreateRoomResponse, PublicRoomsResponse, QuickPlayResponse, RoomMessagesResponse,
+    RoomUsersResponse,
 };
 use crate::storage::interface::IRoomStorage;
-use axum::extract::{Path, State};
+use axum::body::Bytes;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, Query, State};
 use axum::response::Json;
 
 pub async fn create<RS>(
     _user: User,
     State(app_context): State<AppContext<RS>>,
-) -> Json<CreateRoomResponse>
+    body: Bytes,
+) -> Result<Json<CreateRoomResponse>, JsonRejection>
+where
+    RS: IRoomStorage,
+{
+    // Private rooms can still be created without a body, but a malformed one is refused.
+    let body = match body.is_empty() {
+        true => CreateRoomRequestBody::default(),
+        false => Json::<CreateRoomRequestBody>::from_bytes(&body)?.0,
+    };
+    let response = CreateRoomHttpHandler::new(app_context)
+        .create(body.public, body.display_name)
+        .await;
+    Ok(Json(response))
+}
+
+pub async fn list_public<RS>(
+    _user: User,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<PublicRoomsResponse>
+where
+    RS: IRoomStorage,
+{
+    let response = PublicRoomsHttpHandler::new(app_context).list().await;
+    Json(response)
+}
+
+pub async fn quick_play<RS>(
+    _user: User,
+    State(app_context): State<AppContext<RS>>,
+) -> Json<QuickPlayResponse>
 where
     RS: IRoomStorage,
 {
-    let response = CreateRoomHttpHandler::new(app_context).create().await;
+    let response = PublicRoomsHttpHandler::new(app_context).quick_play().await;
     Json(response)
 }
 
 pub async fn users<RS>(
     user: User,
     Path(room_id): Path<String>,
+    Query(credentials): Query<RoomCredentialsQueryParams>,
     State(app_context): State<AppContext<RS>>,
 ) -> Json<RoomUsersResponse>
 where
@@ -33,7 +71,7 @@ where
         room_id,
     };
     let response = RoomHttpHandler::new(app_context, &request_context)
-        .users()
+        .users(&credentials)
         .await;
     Json(response)
 }
@@ -41,6 +79,8 @@ where
 pub async fn messages<RS>(
     user: User,
     Path(room_id): Path<String>,
+    Query(page): Query<MessagesPageQueryParams>,
+    Query(credentials): Query<RoomCredentialsQueryParams>,
     State(app_context): State<AppContext<RS>>,
 ) -> Json<RoomMessagesResponse>
 where
@@ -52,7 +92,7 @@ where
         room_id,
     };
     let response = RoomHttpHandler::new(app_context, &request_context)
-        .messages()
+        .messages(&credentials, page.after, page.before, page.limit)
         .await;
     Json(response)
 }
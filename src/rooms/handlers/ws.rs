@@ -1,30 +1,42 @@
 use crate::app_context::{AppContext, RequestContext};
-use crate::auth::passcode;
+use crate::auth::passcode::{self, JwtPayload};
+use crate::bans;
+use crate::http::client_ip::ClientIp;
 use crate::http::requests::PasscodeQueryParam;
-use crate::rooms::consts::MAX_MESSAGE_LENGTH;
+use crate::rate_limit::RateLimitKey;
+use crate::rooms;
 use crate::rooms::consts::ROUNDS_PER_GAME;
 use crate::rooms::message_types::{
-    self, BotMessagePayload, BriefUserInfoPayload, ClientSentSocketMessage,
-    RoundStartedBotMessagePayload, RoundStartedBotMsg, ServerSentChatMessagePayload,
-    ServerSentSocketMessage, UserConnectedBotMessagePayload, UserConnectedBotMsg,
+    self, BotMessagePayload, BriefUserInfoPayload, ClientSentSocketMessage, PresenceChangedPayload,
+    RateLimitedPayload, RemovalReason, RemovedFromRoomPayload, ResyncRequiredPayload,
+    RoundStartedBotMessagePayload, RoundStartedBotMsg, ServerSentSocketMessage,
+    UserConnectedBotMessagePayload, UserConnectedBotMsg,
 };
 use crate::rooms::models::ChatMessage;
+use crate::rooms::services::chat::ChatWsHandler;
+use crate::rooms::services::http::RoomHttpHandler;
+use crate::rooms::services::responses::{CanConnectToRoomResponse, ConnectionRefusalError};
+use crate::rooms::services::votes::VotesWsHandler;
 use crate::storage::interface::{
-    RoomConnectionHandler, RoomGameFlowHandler, RoomRepo, RoomSocketsRepo, UserPermissionsRepo,
+    RoomConnectionHandler, RoomEventsRepo, RoomGameFlowHandler, RoomRepo, UserPermissionsRepo,
 };
 use crate::storage::rooms::HashMapRoomsStorage;
 use crate::storage::rooms::UserConnectedResult;
+use crate::storage::socket_queue::SocketQueue;
+use crate::storage::sockets::Audience;
+use crate::users::models::{Permission, Presence};
 use axum::extract::ws::Message;
 use axum::extract::ws::WebSocket;
-use axum::extract::{Path, Query, Request, State, WebSocketUpgrade};
-use axum::response::Response;
-use axum_client_ip::InsecureClientIp;
-use futures_util::{SinkExt, StreamExt, TryFutureExt};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::mpsc;
-use tokio::time::Instant;
-use tokio_stream::wrappers::UnboundedReceiverStream;
-use unicode_segmentation::UnicodeSegmentation;
+use axum::extract::{Path, Query, State, WebSocketUpgrade};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use futures_util::{Sink, SinkExt, StreamExt};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
 
 // TODO: make the handler generic over rooms storage
 
@@ -33,7 +45,7 @@ pub async fn ws(
     Path(room_id): Path<String>,
     Query(query_params): Query<PasscodeQueryParam>,
     State(app_context): State<AppContext<HashMapRoomsStorage>>,
-    request: Request,
+    ClientIp(client_ip): ClientIp,
 ) -> Response {
     // TODO: If the handler is made generic over rooms storage, `ws.on_upgrade` requires the future
     // returned by the closure to be `Send`, so all futures that it awaits must also be `Send`.
@@ -45,49 +57,74 @@ pub async fn ws(
     //
     // - async fn disconnect_user(...);
     // + fn disconnect_user(...) -> impl std::future::Future<Output = ()> + std::marker::Send;
-    let client_ip = InsecureClientIp::from(request.headers(), request.extensions())
-        .map(|InsecureClientIp(ip)| ip.to_string())
-        .unwrap_or_else(|_| "127.0.0.1".to_string());
-    ws.on_upgrade(|socket| {
-        handle_socket(
-            socket,
-            room_id,
-            query_params.passcode,
-            app_context,
-            client_ip,
-        )
-    })
+    let Ok(jwt_payload) = passcode::decode(&query_params.passcode) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if bans::ban_list()
+        .find(Some(&jwt_payload.public_id), Some(client_ip))
+        .is_some()
+    {
+        let response = CanConnectToRoomResponse {
+            can_connect: false,
+            error_code: Some(ConnectionRefusalError::BannedFromServer),
+        };
+        return (StatusCode::FORBIDDEN, Json(response)).into_response();
+    }
+    let client_ip = client_ip.to_string();
+    let request_context = RequestContext {
+        public_id: jwt_payload.public_id.clone(),
+        private_id: jwt_payload.private_id.clone(),
+        room_id: room_id.clone(),
+    };
+    let access_check = if app_context.rooms.exists(&room_id).await {
+        RoomHttpHandler::new(app_context.clone(), &request_context)
+            .check_access(&query_params.credentials)
+            .await
+    } else {
+        Err(ConnectionRefusalError::RoomNotFound)
+    };
+    if let Err(refusal_reason) = access_check {
+        let response = CanConnectToRoomResponse {
+            can_connect: false,
+            error_code: Some(refusal_reason),
+        };
+        return (StatusCode::FORBIDDEN, Json(response)).into_response();
+    }
+    ws.on_upgrade(|socket| handle_socket(socket, room_id, jwt_payload, app_context, client_ip))
 }
 
 async fn handle_socket(
     socket: WebSocket,
     room_id: String,
-    passcode: String,
+    jwt_payload: JwtPayload,
     app_context: AppContext<HashMapRoomsStorage>,
     client_ip: String,
 ) {
-    // TODO: reject if incorrect
-    let jwt_payload = passcode::decode(&passcode).unwrap();
     let request_context = RequestContext {
         public_id: jwt_payload.public_id,
         private_id: jwt_payload.private_id,
         room_id,
     };
-    let (mut user_ws_tx, mut user_ws_rx) = socket.split();
-    let (tx, rx) = mpsc::unbounded_channel();
-    let mut rx = UnboundedReceiverStream::new(rx);
-    let socket_id = app_context.sockets.add(tx).await;
+    let (user_ws_tx, mut user_ws_rx) = socket.split();
+    let (socket_id, queue) = app_context.sockets.add().await;
 
-    tokio::task::spawn(async move {
-        while let Some(message) = rx.next().await {
-            user_ws_tx
-                .send(message)
-                .unwrap_or_else(|e| eprintln!("[user_connected]: websocket send error: {e}"))
-                .await;
-        }
-    });
+    tokio::task::spawn(write_queued_messages(queue.clone(), user_ws_tx));
+
+    let missed_pongs = Arc::new(AtomicU32::new(0));
+    let heartbeat = rooms::ping_interval()
+        .map(|interval| spawn_heartbeat(interval, queue.clone(), missed_pongs.clone(), socket_id));
 
-    while let Some(result) = user_ws_rx.next().await {
+    loop {
+        let result = tokio::select! {
+            result = user_ws_rx.next() => match result {
+                Some(result) => result,
+                None => break,
+            },
+            _ = queue.disconnect_requested() => {
+                eprintln!("[user_connected]: dropping socket {socket_id} on the server's behalf");
+                break;
+            }
+        };
         let message = match result {
             Ok(message) => message,
             Err(e) => {
@@ -95,6 +132,11 @@ async fn handle_socket(
                 break;
             }
         };
+        // Anything coming from the client shows that the connection is alive.
+        missed_pongs.store(0, Ordering::Relaxed);
+        if let Message::Pong(_) = message {
+            continue;
+        }
         // TODO: is `clone()` needed?
         on_new_message(
             app_context.clone(),
@@ -105,10 +147,56 @@ async fn handle_socket(
         )
         .await;
     }
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.abort();
+    }
     on_user_disconnected(app_context, request_context, socket_id).await;
 }
 
-async fn on_new_message(
+/// Drops the socket as soon as something can't be written to it, as the connection is gone, or
+/// once a close frame has been written, so that removed users can't keep the socket open.
+pub async fn write_queued_messages<S>(queue: Arc<SocketQueue>, mut sink: S)
+where
+    S: Sink<Message> + Unpin,
+    S::Error: Display,
+{
+    while let Some(message) = queue.pop().await {
+        let is_close = matches!(message, Message::Close(_));
+        if let Err(e) = sink.send(message).await {
+            eprintln!("[user_connected]: websocket send error: {e}");
+            queue.disconnect();
+            break;
+        }
+        if is_close {
+            queue.disconnect();
+            break;
+        }
+    }
+}
+
+/// Pings the client every `interval` and drops the socket once it stops answering.
+pub fn spawn_heartbeat(
+    interval: Duration,
+    queue: Arc<SocketQueue>,
+    missed_pongs: Arc<AtomicU32>,
+    socket_id: usize,
+) -> JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval_at(Instant::now() + interval, interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if missed_pongs.fetch_add(1, Ordering::Relaxed) >= rooms::max_missed_pongs() {
+                eprintln!("[heartbeat]: socket {socket_id} stopped answering pings");
+                queue.disconnect();
+                break;
+            }
+            queue.push_control(Message::Ping(Vec::new()));
+        }
+    })
+}
+
+pub async fn on_new_message(
     app_context: AppContext<HashMapRoomsStorage>,
     request_context: RequestContext,
     msg: Message,
@@ -135,100 +223,54 @@ async fn on_new_message(
         );
         return;
     }
-    let relevant_socket_ids = app_context
-        .rooms
-        .socket_ids_except_sender(&request_context.room_id, socket_id)
-        .await;
+    if !app_context.rooms.exists(&request_context.room_id).await {
+        // The room might have been closed, in which case the socket is about to be closed too.
+        return;
+    }
     let socket_message = socket_message.unwrap();
+    let rate_limit_key = RateLimitKey::User(request_context.private_id.clone());
+    let rate_limit = match socket_message.is_rate_limited() {
+        true => app_context.rate_limits.ws_messages.check(rate_limit_key),
+        false => Ok(()),
+    };
+    if let Err(retry_after) = rate_limit {
+        let ws_message = ServerSentSocketMessage::RateLimited {
+            r#type: message_types::RateLimited,
+            payload: RateLimitedPayload {
+                retry_after_secs: retry_after.as_secs_f64().ceil() as u64,
+            },
+        };
+        let msg = serde_json::to_string(&ws_message).unwrap();
+        app_context.sockets.send_msg(&msg, socket_id).await;
+        return;
+    }
     let message_type = socket_message.message_type_as_string();
     match socket_message {
         ClientSentSocketMessage::ChatMessage { payload, .. } => {
-            if app_context
-                .rooms
-                .is_muted(&request_context.room_id, &request_context.public_id)
-                .await
-            {
-                return;
-            }
-            if payload.content.graphemes(true).count() > MAX_MESSAGE_LENGTH {
-                eprintln!(
-                    "Rejecting a message because the it is too long: \
-                        {} symbols when at most {} is allowed.",
-                    payload.content.len(),
-                    MAX_MESSAGE_LENGTH,
-                );
-                return;
-            }
-            let chat_message = ChatMessage::from_player(
-                payload.from.clone(),
-                payload.content.clone(),
-                payload.attachment_ids.clone(),
-            );
-            let ws_chat_message = ServerSentSocketMessage::ChatMessage {
-                r#type: message_types::ChatMessage,
-                payload: ServerSentChatMessagePayload {
-                    id: chat_message.id(),
-                    from: payload.from,
-                    content: payload.content,
-                    attachment_ids: payload.attachment_ids,
-                },
-            };
-            let raw_chat_message = serde_json::to_string(&ws_chat_message).unwrap();
-            app_context
-                .rooms
-                .add_message(&request_context.room_id, chat_message)
+            ChatWsHandler::new(app_context.clone(), &request_context, socket_id)
+                .post(payload)
                 .await;
-            app_context
-                .sockets
-                .broadcast_msg(&raw_chat_message, &relevant_socket_ids)
+        }
+        ClientSentSocketMessage::EditChatMessage { payload, .. } => {
+            ChatWsHandler::new(app_context.clone(), &request_context, socket_id)
+                .edit(payload.id, payload.content)
+                .await;
+        }
+        ClientSentSocketMessage::DeleteChatMessage { payload, .. } => {
+            ChatWsHandler::new(app_context.clone(), &request_context, socket_id)
+                .delete(payload.id)
+                .await;
+        }
+        ClientSentSocketMessage::ToggleReaction { payload, .. } => {
+            ChatWsHandler::new(app_context.clone(), &request_context, socket_id)
+                .toggle_reaction(payload.message_id, payload.emoji)
                 .await;
         }
         ClientSentSocketMessage::UserConnected { payload, .. } => {
-            if !app_context
-                .rooms
-                .has_user_with_such_private_id(
-                    &request_context.room_id,
-                    &request_context.private_id,
-                )
-                .await
-            {
-                let bot_message_payload = BotMessagePayload::UserConnected {
-                    r#type: UserConnectedBotMsg,
-                    payload: UserConnectedBotMessagePayload {
-                        username: payload.username.clone(),
-                    },
-                };
-                let bot_message = ChatMessage::from_bot(bot_message_payload.clone());
-                let ws_message = ServerSentSocketMessage::BotMessage {
-                    r#type: message_types::BotMessage,
-                    id: bot_message.id(),
-                    payload: bot_message_payload,
-                };
-                let msg = serde_json::to_string(&ws_message).unwrap();
-                let mut all_sockets_ids = relevant_socket_ids.clone();
-                all_sockets_ids.push(Some(socket_id));
-                let ws_event = ServerSentSocketMessage::UserConnected {
-                    r#type: message_types::UserConnected,
-                    payload: BriefUserInfoPayload {
-                        username: payload.username.clone(),
-                        avatar_emoji: payload.avatar_emoji.clone(),
-                    },
-                };
-                let raw_ws_event = serde_json::to_string(&ws_event).unwrap();
-                // TODO: `UserConnected` gets sent twice?
-                app_context
-                    .rooms
-                    .add_message(&request_context.room_id, bot_message)
-                    .await;
-                app_context
-                    .sockets
-                    .broadcast_msg(&msg, &all_sockets_ids)
-                    .await;
-                app_context
-                    .sockets
-                    .broadcast_msg(&raw_ws_event, &relevant_socket_ids)
-                    .await;
-            }
+            let user_info = BriefUserInfoPayload {
+                username: payload.username.clone(),
+                avatar_emoji: payload.avatar_emoji.clone(),
+            };
             match app_context
                 .rooms
                 .on_user_connected(
@@ -241,57 +283,141 @@ async fn on_new_message(
                 .await
             {
                 Ok(UserConnectedResult::NewUser) => {
-                    // TODO
                     app_context
                         .sockets
-                        .broadcast_msg(raw_incoming_msg, &relevant_socket_ids)
+                        .subscribe(&request_context.room_id, socket_id)
                         .await;
+                    let bot_message_payload = BotMessagePayload::UserConnected {
+                        r#type: UserConnectedBotMsg,
+                        payload: UserConnectedBotMessagePayload {
+                            username: user_info.username.clone(),
+                        },
+                    };
+                    let bot_message = ChatMessage::from_bot(bot_message_payload.clone());
+                    let ws_message = ServerSentSocketMessage::BotMessage {
+                        r#type: message_types::BotMessage,
+                        id: bot_message.id(),
+                        payload: bot_message_payload,
+                    };
+                    let ws_event = ServerSentSocketMessage::UserConnected {
+                        r#type: message_types::UserConnected,
+                        payload: user_info,
+                    };
+                    app_context
+                        .rooms
+                        .add_message(&request_context.room_id, bot_message)
+                        .await;
+                    app_context
+                        .broadcast_event(&request_context.room_id, &ws_message, Audience::Everyone)
+                        .await;
+                    app_context
+                        .broadcast_event(
+                            &request_context.room_id,
+                            &ws_event,
+                            Audience::EveryoneExcept(socket_id),
+                        )
+                        .await;
+                }
+                Ok(UserConnectedResult::AlreadyInTheRoom { came_back }) => {
+                    app_context
+                        .sockets
+                        .subscribe(&request_context.room_id, socket_id)
+                        .await;
+                    if came_back {
+                        broadcast_presence(
+                            &app_context,
+                            &request_context.room_id,
+                            &request_context.public_id,
+                            Presence::Online,
+                        )
+                        .await;
+                    }
                 }
-                Ok(UserConnectedResult::AlreadyInTheRoom) => {}
                 Err(_) => {
                     eprintln!(
-                            "[user_message]: user with such name already connected : {raw_incoming_msg:?}."
-                        );
+                        "[user_message]: the room is locked or full, refusing: {raw_incoming_msg:?}."
+                    );
+                    app_context.sockets.close(socket_id).await;
                 }
             }
         }
         ClientSentSocketMessage::UserReConnected { payload, .. } => {
-            app_context
+            let Ok(came_back) = app_context
                 .rooms
                 .on_user_reconnected(
                     &request_context.room_id,
-                    payload,
+                    payload.user_info,
                     socket_id,
                     &request_context.private_id,
                 )
+                .await
+            else {
+                let ws_message = ServerSentSocketMessage::RemovedFromRoom {
+                    r#type: message_types::RemovedFromRoom,
+                    payload: RemovedFromRoomPayload {
+                        reason: RemovalReason::GracePeriodOver,
+                        ban_duration_secs: None,
+                    },
+                };
+                let msg = serde_json::to_string(&ws_message).unwrap();
+                app_context.sockets.send_msg(&msg, socket_id).await;
+                app_context.sockets.close(socket_id).await;
+                return;
+            };
+            app_context
+                .sockets
+                .subscribe(&request_context.room_id, socket_id)
                 .await;
+            if came_back {
+                broadcast_presence(
+                    &app_context,
+                    &request_context.room_id,
+                    &request_context.public_id,
+                    Presence::Online,
+                )
+                .await;
+            }
+            if let Some(last_seen_seq) = payload.last_seen_seq {
+                replay_missed_events(&app_context, &request_context, socket_id, last_seen_seq)
+                    .await;
+            }
         }
-        ClientSentSocketMessage::UserDisconnected { payload, .. } => {
-            let ws_event = ServerSentSocketMessage::UserDisconnected {
-                r#type: message_types::UserDisconnected,
-                payload: BriefUserInfoPayload {
-                    username: payload.username.clone(),
-                    avatar_emoji: payload.avatar_emoji.clone(),
-                },
-            };
-            let raw_ws_event = serde_json::to_string(&ws_event).unwrap();
+        ClientSentSocketMessage::UserDisconnected { .. } => {
+            // The grace period starts once the socket is closed, which the client is about to do.
             app_context
                 .rooms
                 .on_user_disconnected(
                     &request_context.room_id,
-                    raw_ws_event,
                     &request_context.private_id,
-                    socket_id,
                     app_context.sockets.clone(),
                 )
                 .await;
         }
         ClientSentSocketMessage::RoundStarted { .. } => {
-            // TODO: Check if the user is host
-            let rounds_left = app_context
+            if app_context.shutdown.is_draining() {
+                eprintln!("Ignoring a round start because the server is shutting down.");
+                return;
+            }
+            if !app_context
+                .rooms
+                .has_permission(
+                    &request_context.room_id,
+                    &request_context.public_id,
+                    Permission::StartRound,
+                )
+                .await
+            {
+                eprintln!("Ignoring a round start from a user that isn't allowed to start rounds.");
+                return;
+            }
+            let Some(rounds_left) = app_context
                 .rooms
                 .current_round_number(&request_context.room_id)
-                .await;
+                .await
+            else {
+                eprintln!("Ignoring a round start in a room that has been closed.");
+                return;
+            };
             let round_number = match rounds_left {
                 0 => ROUNDS_PER_GAME,
                 _ => ROUNDS_PER_GAME + 1 - rounds_left,
@@ -309,16 +435,12 @@ async fn on_new_message(
                 id: bot_message.id(),
                 payload: bot_message_payload,
             };
-            let msg = serde_json::to_string(&ws_message).unwrap();
-            let mut all_sockets_ids = relevant_socket_ids.clone();
-            all_sockets_ids.push(Some(socket_id));
             app_context
                 .rooms
                 .add_message(&request_context.room_id, bot_message)
                 .await;
             app_context
-                .sockets
-                .broadcast_msg(&msg, &all_sockets_ids)
+                .broadcast_event(&request_context.room_id, &ws_message, Audience::Everyone)
                 .await;
             app_context
                 .rooms
@@ -327,10 +449,12 @@ async fn on_new_message(
             let ws_event = ServerSentSocketMessage::RoundStarted {
                 r#type: message_types::RoundStarted,
             };
-            let raw_ws_event = serde_json::to_string(&ws_event).unwrap();
             app_context
-                .sockets
-                .broadcast_msg(&raw_ws_event, &relevant_socket_ids)
+                .broadcast_event(
+                    &request_context.room_id,
+                    &ws_event,
+                    Audience::EveryoneExcept(socket_id),
+                )
                 .await;
         }
         ClientSentSocketMessage::Ping { .. } => {
@@ -340,6 +464,16 @@ async fn on_new_message(
             let msg = serde_json::to_string(&ws_message).unwrap();
             app_context.sockets.send_msg(&msg, socket_id).await;
         }
+        ClientSentSocketMessage::StartVote { payload, .. } => {
+            VotesWsHandler::new(app_context.clone(), &request_context)
+                .start(payload)
+                .await;
+        }
+        ClientSentSocketMessage::CastVote { payload, .. } => {
+            VotesWsHandler::new(app_context.clone(), &request_context)
+                .cast(payload.in_favor)
+                .await;
+        }
     }
     let processing_time_ms = start_time.elapsed().as_millis();
     tracing::info!(
@@ -352,14 +486,82 @@ async fn on_new_message(
     );
 }
 
+/// Events broadcast while the socket was being reconnected can arrive twice, so clients are
+/// expected to skip the ones with `seq` they've already seen.
+async fn replay_missed_events(
+    app_context: &AppContext<HashMapRoomsStorage>,
+    request_context: &RequestContext,
+    socket_id: usize,
+    last_seen_seq: u64,
+) {
+    let missed_events = app_context
+        .rooms
+        .events_since(
+            &request_context.room_id,
+            &request_context.public_id,
+            last_seen_seq,
+        )
+        .await;
+    let Some(missed_events) = missed_events else {
+        let ws_message = ServerSentSocketMessage::ResyncRequired {
+            r#type: message_types::ResyncRequired,
+            payload: ResyncRequiredPayload {
+                seq: app_context.rooms.last_seq(&request_context.room_id).await,
+            },
+        };
+        let msg = serde_json::to_string(&ws_message).unwrap();
+        app_context.sockets.send_msg(&msg, socket_id).await;
+        return;
+    };
+    for raw_event in missed_events {
+        app_context.sockets.send_msg(&raw_event, socket_id).await;
+    }
+}
+
 async fn on_user_disconnected(
     app_context: AppContext<HashMapRoomsStorage>,
     request_context: RequestContext,
     socket_id: usize,
 ) {
     app_context.sockets.remove(socket_id).await;
-    app_context
+    let Some(public_id) = app_context
         .rooms
         .disconnect_user(&request_context.room_id, socket_id)
+        .await
+    else {
+        return;
+    };
+    broadcast_presence(
+        &app_context,
+        &request_context.room_id,
+        &public_id,
+        Presence::Away,
+    )
+    .await;
+    app_context
+        .rooms
+        .on_user_disconnected(
+            &request_context.room_id,
+            &request_context.private_id,
+            app_context.sockets.clone(),
+        )
+        .await;
+}
+
+async fn broadcast_presence(
+    app_context: &AppContext<HashMapRoomsStorage>,
+    room_id: &str,
+    public_id: &str,
+    presence: Presence,
+) {
+    let ws_event = ServerSentSocketMessage::PresenceChanged {
+        r#type: message_types::PresenceChanged,
+        payload: PresenceChangedPayload {
+            public_id: public_id.to_string(),
+            presence,
+        },
+    };
+    app_context
+        .broadcast_event(room_id, &ws_event, Audience::Everyone)
         .await;
 }
@@ -1,3 +1,6 @@
+use crate::moderation::RejectionReason;
+use crate::rooms::votes::VoteSubject;
+use crate::users::models::{Presence, UserRole};
 use serde::{Deserialize, Serialize};
 use serde_unit_struct::{Deserialize_unit_struct, Serialize_unit_struct};
 
@@ -18,11 +21,13 @@ pub enum ClientSentSocketMessage {
     UserReConnected {
         #[allow(dead_code)]
         r#type: UserReConnected,
-        payload: BriefUserInfoPayload,
+        payload: UserReConnectedPayload,
     },
     UserDisconnected {
         #[allow(dead_code)]
         r#type: UserDisconnected,
+        /// The server knows who is leaving, but clients still send it.
+        #[allow(dead_code)]
         payload: BriefUserInfoPayload,
     },
     RoundStarted {
@@ -33,6 +38,31 @@ pub enum ClientSentSocketMessage {
         #[allow(dead_code)]
         r#type: Ping,
     },
+    StartVote {
+        #[allow(dead_code)]
+        r#type: StartVote,
+        payload: VoteSubject,
+    },
+    CastVote {
+        #[allow(dead_code)]
+        r#type: CastVote,
+        payload: CastVotePayload,
+    },
+    EditChatMessage {
+        #[allow(dead_code)]
+        r#type: EditChatMessage,
+        payload: EditChatMessagePayload,
+    },
+    DeleteChatMessage {
+        #[allow(dead_code)]
+        r#type: DeleteChatMessage,
+        payload: DeleteChatMessagePayload,
+    },
+    ToggleReaction {
+        #[allow(dead_code)]
+        r#type: ToggleReaction,
+        payload: ToggleReactionPayload,
+    },
 }
 
 #[macro_export]
@@ -52,9 +82,27 @@ impl ClientSentSocketMessage {
             ClientSentSocketMessage::UserDisconnected { .. } => name_of!(UserDisconnected),
             ClientSentSocketMessage::RoundStarted { .. } => name_of!(RoundStarted),
             ClientSentSocketMessage::Ping { .. } => name_of!(Ping),
+            ClientSentSocketMessage::StartVote { .. } => name_of!(StartVote),
+            ClientSentSocketMessage::CastVote { .. } => name_of!(CastVote),
+            ClientSentSocketMessage::EditChatMessage { .. } => name_of!(EditChatMessage),
+            ClientSentSocketMessage::DeleteChatMessage { .. } => name_of!(DeleteChatMessage),
+            ClientSentSocketMessage::ToggleReaction { .. } => name_of!(ToggleReaction),
         }
         .to_string()
     }
+
+    /// Connection upkeep is never rate limited, so that throttled clients can still reconnect and
+    /// keep their sockets alive. Chat messages have a rate limit of their own.
+    pub fn is_rate_limited(&self) -> bool {
+        !matches!(
+            self,
+            ClientSentSocketMessage::UserConnected { .. }
+                | ClientSentSocketMessage::UserReConnected { .. }
+                | ClientSentSocketMessage::UserDisconnected { .. }
+                | ClientSentSocketMessage::Ping { .. }
+                | ClientSentSocketMessage::ChatMessage { .. }
+        )
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -103,6 +151,15 @@ pub enum ServerSentSocketMessage {
         r#type: UserBanned,
         payload: UserPubIdInfoPayload,
     },
+    UserKicked {
+        r#type: UserKicked,
+        payload: UserPubIdInfoPayload,
+    },
+    /// Sent only to the removed user right before their socket is closed.
+    RemovedFromRoom {
+        r#type: RemovedFromRoom,
+        payload: RemovedFromRoomPayload,
+    },
     UserScoreChanged {
         r#type: UserScoreChanged,
     },
@@ -113,6 +170,63 @@ pub enum ServerSentSocketMessage {
         r#type: Tick,
         payload: i32,
     },
+    RoomClosed {
+        r#type: RoomClosed,
+        payload: RoomClosedPayload,
+    },
+    /// The server is about to go away; the client should reconnect once it's back.
+    ServerShuttingDown {
+        r#type: ServerShuttingDown,
+        payload: ServerShuttingDownPayload,
+    },
+    /// The missed events can't be replayed, so the client has to fetch the room state again.
+    ResyncRequired {
+        r#type: ResyncRequired,
+        payload: ResyncRequiredPayload,
+    },
+    RoomLocked {
+        r#type: RoomLocked,
+    },
+    RoomUnlocked {
+        r#type: RoomUnlocked,
+    },
+    HostChanged {
+        r#type: HostChanged,
+        payload: UserPubIdInfoPayload,
+    },
+    PresenceChanged {
+        r#type: PresenceChanged,
+        payload: PresenceChangedPayload,
+    },
+    LocationSkipped {
+        r#type: LocationSkipped,
+    },
+    /// Sent only to the author of the rejected message.
+    ChatMessageRejected {
+        r#type: ChatMessageRejected,
+        payload: ChatMessageRejectedPayload,
+    },
+    MessageEdited {
+        r#type: MessageEdited,
+        payload: MessageEditedPayload,
+    },
+    MessageDeleted {
+        r#type: MessageDeleted,
+        payload: MessageDeletedPayload,
+    },
+    ReactionToggled {
+        r#type: ReactionToggled,
+        payload: ReactionToggledPayload,
+    },
+    UserRoleChanged {
+        r#type: UserRoleChanged,
+        payload: UserRoleChangedPayload,
+    },
+    /// Sent only to the socket whose message was dropped for going over the quota.
+    RateLimited {
+        r#type: RateLimited,
+        payload: RateLimitedPayload,
+    },
 }
 
 #[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
@@ -154,6 +268,12 @@ pub struct UserUnmuted;
 #[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
 pub struct UserBanned;
 
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct UserKicked;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct RemovedFromRoom;
+
 #[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
 pub struct UserScoreChanged;
 
@@ -166,12 +286,136 @@ pub struct Pong;
 #[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
 pub struct Tick;
 
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct RoomClosed;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct ServerShuttingDown;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct ResyncRequired;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct RoomLocked;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct RoomUnlocked;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct HostChanged;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct PresenceChanged;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct UserRoleChanged;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct StartVote;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct CastVote;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct LocationSkipped;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct ChatMessageRejected;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct RateLimited;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct EditChatMessage;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct DeleteChatMessage;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct MessageEdited;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct MessageDeleted;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct ToggleReaction;
+
+#[derive(Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct ReactionToggled;
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClientSentChatMessagePayload {
     pub from: String,
     pub content: String,
     pub attachment_ids: Vec<String>,
+    pub reply_to: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CastVotePayload {
+    pub in_favor: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditChatMessagePayload {
+    pub id: usize,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteChatMessagePayload {
+    pub id: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToggleReactionPayload {
+    pub message_id: usize,
+    pub emoji: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReactionToggledPayload {
+    pub message_id: usize,
+    pub emoji: String,
+    pub user_public_id: String,
+    /// `false` if the user took their reaction back.
+    pub added: bool,
+    /// How many users have reacted with this emoji now.
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageEditedPayload {
+    pub id: usize,
+    pub content: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageDeletedPayload {
+    pub id: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatMessageRejectedPayload {
+    pub reason: RejectionReason,
+    /// Set if the author got muted for sending too many rejected messages.
+    pub muted: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitedPayload {
+    /// How long until the next message would be accepted.
+    pub retry_after_secs: u64,
 }
 
 #[derive(Debug, Serialize)]
@@ -181,9 +425,10 @@ pub struct ServerSentChatMessagePayload {
     pub from: String,
     pub content: String,
     pub attachment_ids: Vec<String>,
+    pub reply_to: Option<usize>,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum BotMessagePayload {
     RoundStarted {
@@ -202,42 +447,100 @@ pub enum BotMessagePayload {
         r#type: UserDisconnectedBotMsg,
         payload: UserDisconnectedBotMessagePayload,
     },
+    VoteStarted {
+        r#type: VoteStartedBotMsg,
+        payload: VoteStartedBotMessagePayload,
+    },
+    VoteProgress {
+        r#type: VoteProgressBotMsg,
+        payload: VoteProgressBotMessagePayload,
+    },
+    VoteFinished {
+        r#type: VoteFinishedBotMsg,
+        payload: VoteFinishedBotMessagePayload,
+    },
+    /// Sent by the operators to all rooms.
+    Announcement {
+        r#type: AnnouncementBotMsg,
+        payload: AnnouncementBotMessagePayload,
+    },
 }
 
-#[derive(Clone, Debug, Serialize_unit_struct)]
+#[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
 pub struct RoundStartedBotMsg;
 
-#[derive(Clone, Debug, Serialize_unit_struct)]
+#[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
 pub struct RoundEndedBotMsg;
 
-#[derive(Clone, Debug, Serialize_unit_struct)]
+#[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
 pub struct UserConnectedBotMsg;
 
-#[derive(Clone, Debug, Serialize_unit_struct)]
+#[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
 pub struct UserDisconnectedBotMsg;
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct VoteStartedBotMsg;
+
+#[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct VoteProgressBotMsg;
+
+#[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct VoteFinishedBotMsg;
+
+#[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
+pub struct AnnouncementBotMsg;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RoundStartedBotMessagePayload {
     pub round_number: u64,
     pub rounds_per_game: u64,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RoundEndedBotMessagePayload {
     pub round_number: u64,
     pub rounds_per_game: u64,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserConnectedBotMessagePayload {
     pub username: String,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserDisconnectedBotMessagePayload {
     pub username: String,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VoteStartedBotMessagePayload {
+    pub vote_id: usize,
+    pub initiator_name: String,
+    pub subject: VoteSubject,
+    pub votes_needed: usize,
+    pub timeout_secs: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VoteProgressBotMessagePayload {
+    pub vote_id: usize,
+    pub votes_for: usize,
+    pub votes_against: usize,
+    pub votes_needed: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VoteFinishedBotMessagePayload {
+    pub vote_id: usize,
+    pub subject: VoteSubject,
+    pub passed: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnnouncementBotMessagePayload {
+    pub content: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BriefUserInfoPayload {
@@ -250,3 +553,74 @@ pub struct BriefUserInfoPayload {
 pub struct UserPubIdInfoPayload {
     pub public_id: String,
 }
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserReConnectedPayload {
+    #[serde(flatten)]
+    pub user_info: BriefUserInfoPayload,
+    /// `seq` of the last event the client got; the events after it are sent again.
+    pub last_seen_seq: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerShuttingDownPayload {
+    /// Rounds in progress are given this long to finish before the sockets are closed.
+    pub deadline_secs: u64,
+    /// How long the client should wait before reconnecting.
+    pub reconnect_after_secs: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResyncRequiredPayload {
+    /// `seq` of the latest event in the room; the client can go on from here once it has
+    /// fetched the room state.
+    pub seq: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomClosedPayload {
+    pub reason: RoomClosingReason,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomClosingReason {
+    Idle,
+    ClosedByAdmin,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedFromRoomPayload {
+    pub reason: RemovalReason,
+    /// Only set for time-limited bans.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban_duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RemovalReason {
+    Kicked,
+    Banned,
+    /// The user reconnected after the presence grace period was over.
+    GracePeriodOver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceChangedPayload {
+    pub public_id: String,
+    pub presence: Presence,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserRoleChangedPayload {
+    pub public_id: String,
+    pub role: UserRole,
+}
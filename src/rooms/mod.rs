@@ -1,5 +1,57 @@
+use crate::cli::Args;
+use std::sync::OnceLock;
+use std::time::Duration;
+
 pub mod consts;
+pub mod event_log;
 pub mod handlers;
 pub mod message_types;
 pub mod models;
 pub mod services;
+pub mod snapshot;
+#[cfg(test)]
+pub mod tests;
+pub mod votes;
+
+static CHAT_HISTORY_LIMIT: OnceLock<usize> = OnceLock::new();
+static PRESENCE_GRACE_PERIOD: OnceLock<Duration> = OnceLock::new();
+static PING_INTERVAL: OnceLock<Option<Duration>> = OnceLock::new();
+static MAX_MISSED_PONGS: OnceLock<u32> = OnceLock::new();
+
+pub fn init(args: &Args) {
+    votes::init(args);
+    CHAT_HISTORY_LIMIT.get_or_init(|| args.chat_history_limit);
+    PRESENCE_GRACE_PERIOD.get_or_init(|| Duration::from_secs(args.presence_grace_period_secs));
+    PING_INTERVAL.get_or_init(|| {
+        (args.ping_interval_secs != 0).then(|| Duration::from_secs(args.ping_interval_secs))
+    });
+    MAX_MISSED_PONGS.get_or_init(|| args.max_missed_pongs.max(1));
+}
+
+/// How many messages each room keeps; zero means no limit.
+pub fn chat_history_limit() -> usize {
+    *CHAT_HISTORY_LIMIT
+        .get()
+        .expect("Somehow the chat history limit is used before `init`.")
+}
+
+/// How long users that lost the connection stay in the room waiting for them to come back.
+pub fn presence_grace_period() -> Duration {
+    *PRESENCE_GRACE_PERIOD
+        .get()
+        .expect("Somehow the presence grace period is used before `init`.")
+}
+
+/// How often sockets are pinged; `None` if the server doesn't ping them.
+pub fn ping_interval() -> Option<Duration> {
+    *PING_INTERVAL
+        .get()
+        .expect("Somehow the ping interval is used before `init`.")
+}
+
+/// How many pings in a row a socket can leave unanswered before it's closed.
+pub fn max_missed_pongs() -> u32 {
+    *MAX_MISSED_PONGS
+        .get()
+        .expect("Somehow the missed pongs limit is used before `init`.")
+}
@@ -1,34 +1,262 @@
 use crate::map::{self, models::LatLng};
-use crate::rooms::consts::ROUNDS_PER_GAME;
-use crate::storage::consts::HOW_MUCH_LAST_MESSAGES_TO_STORE;
-use crate::users::models::User;
-use serde::Serialize;
+use crate::rooms;
+use crate::rooms::consts::{
+    DEFAULT_CO_HOST_PERMISSIONS, DEFAULT_MAX_PLAYERS, MAX_DISTINCT_REACTIONS_PER_MESSAGE,
+    ROUNDS_PER_GAME,
+};
+use crate::storage::sockets::Audience;
+use crate::users::models::{Permission, Presence, User, UserRole};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_unit_struct::{Deserialize_unit_struct, Serialize_unit_struct};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use super::event_log::EventLog;
 use super::message_types::BotMessagePayload;
+use super::votes::{Vote, VoteProgress, VoteStartError, VoteStatus, VoteSubject};
 
 pub static NEXT_CHAT_MESSAGE_ID: AtomicUsize = AtomicUsize::new(1);
 
 #[derive(Clone, Debug)]
 pub struct Room {
     pub users: Vec<User>,
-    pub last_messages: VecDeque<ChatMessage>,
+    /// Sorted by message ID.
+    pub chat_history: VecDeque<ChatMessage>,
     pub status: RoomStatus,
-    pub banned_public_users_ids: Vec<String>,
+    pub bans: Vec<Ban>,
     pub rounds_left: u64,
+    pub last_activity: Instant,
+    pub access: RoomAccess,
+    /// Set if the room is shown in the public rooms list.
+    pub public_listing: Option<PublicListing>,
+    pub max_players: usize,
+    /// Locked rooms can't be joined by new users.
+    pub is_locked: bool,
+    /// What the co-hosts are allowed to do; the host can do anything.
+    pub co_host_permissions: HashSet<Permission>,
+    /// At most one vote can run in a room at a time.
+    pub active_vote: Option<Vote>,
+    /// Bumped whenever the location of the current round is skipped, so the round timer knows
+    /// to count down again.
+    pub skipped_locations: usize,
+    pub events: EventLog,
 }
 
 impl Room {
-    pub fn reassign_host(&mut self) {
-        if self.users.is_empty() {
-            return;
+    pub fn new(public_listing: Option<PublicListing>) -> Self {
+        Self {
+            users: vec![],
+            chat_history: VecDeque::new(),
+            status: RoomStatus::Waiting {
+                previous_location: None,
+            },
+            bans: vec![],
+            rounds_left: ROUNDS_PER_GAME,
+            last_activity: Instant::now(),
+            access: RoomAccess::Open,
+            public_listing,
+            max_players: DEFAULT_MAX_PLAYERS,
+            is_locked: false,
+            co_host_permissions: HashSet::from(DEFAULT_CO_HOST_PERMISSIONS),
+            active_vote: None,
+            skipped_locations: 0,
+            events: EventLog::default(),
         }
-        self.users[0].is_host = true;
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.users.len() >= self.max_players
+    }
+
+    /// Whether a game is running, including the pauses between its rounds.
+    pub fn game_in_progress(&self) -> bool {
+        matches!(self.status, RoomStatus::Playing { .. }) || self.rounds_left != ROUNDS_PER_GAME
+    }
+
+    /// Whether every user that takes part in the current round has submitted their guess.
+    pub fn all_players_submitted_guesses(&self) -> bool {
+        self.users
+            .iter()
+            .filter(|user| !user.is_spectating)
+            .all(|user| user.submitted_guess)
+    }
+
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Whether the room should be closed: either nobody has been in it for `empty_room_ttl`,
+    /// or nobody is online and nothing has happened in it for `idle_ttl`.
+    pub fn is_expired(&self, idle_ttl: Duration, empty_room_ttl: Duration) -> bool {
+        let idle_for = self.last_activity.elapsed();
+        let anyone_online = self
+            .users
+            .iter()
+            .any(|user| user.presence == Presence::Online);
+        (idle_for > idle_ttl && !anyone_online)
+            || (self.users.is_empty() && idle_for > empty_room_ttl)
+    }
+
+    pub fn permissions_of(&self, public_user_id: &str) -> HashSet<Permission> {
+        match self
+            .users
+            .iter()
+            .find(|user| user.public_id == public_user_id)
+            .map(|user| user.role)
+        {
+            Some(UserRole::Host) => HashSet::from(Permission::ALL),
+            Some(UserRole::CoHost) => self.co_host_permissions.clone(),
+            Some(UserRole::Player) | None => HashSet::new(),
+        }
+    }
+
+    pub fn has_permission(&self, public_user_id: &str, permission: Permission) -> bool {
+        self.permissions_of(public_user_id).contains(&permission)
+    }
+
+    /// Makes the target user the host, demoting the current host to a regular player. Returns
+    /// `false` if there is no such user in the room.
+    pub fn transfer_host(&mut self, new_host_public_id: &str) -> bool {
+        if !self
+            .users
+            .iter()
+            .any(|user| user.public_id == new_host_public_id)
+        {
+            return false;
+        }
+        for user in self.users.iter_mut() {
+            if user.public_id == new_host_public_id {
+                user.role = UserRole::Host;
+            } else if user.is_host() {
+                user.role = UserRole::Player;
+            }
+        }
+        true
+    }
+
+    /// Picks a new host after the previous one has left: the longest-staying co-host if there
+    /// is one, otherwise the longest-staying player. Returns the new host's public ID.
+    pub fn reassign_host(&mut self) -> Option<String> {
+        let new_host_index = self
+            .users
+            .iter()
+            .position(|user| user.role == UserRole::CoHost)
+            .or_else(|| (!self.users.is_empty()).then_some(0))?;
+        let new_host = &mut self.users[new_host_index];
+        new_host.role = UserRole::Host;
+        Some(new_host.public_id.clone())
+    }
+
+    pub fn start_vote(
+        &mut self,
+        initiator_public_id: &str,
+        subject: VoteSubject,
+        majority_percent: u64,
+    ) -> Result<VoteProgress, VoteStartError> {
+        if self.active_vote.is_some() {
+            return Err(VoteStartError::VoteAlreadyInProgress);
+        }
+        if !self
+            .users
+            .iter()
+            .any(|user| user.public_id == initiator_public_id)
+        {
+            return Err(VoteStartError::NotInTheRoom);
+        }
+        let electorate_size = match &subject {
+            VoteSubject::Kick { target_public_id } => {
+                if target_public_id == initiator_public_id {
+                    return Err(VoteStartError::CannotVoteAgainstYourself);
+                }
+                let target = self
+                    .users
+                    .iter()
+                    .find(|user| user.public_id == *target_public_id)
+                    .ok_or(VoteStartError::TargetNotFound)?;
+                if target.is_host() {
+                    return Err(VoteStartError::TargetIsTheHost);
+                }
+                // The target doesn't get a say.
+                self.users.len() - 1
+            }
+            VoteSubject::SkipLocation => {
+                if !matches!(self.status, RoomStatus::Playing { .. }) {
+                    return Err(VoteStartError::NotPlaying);
+                }
+                self.users.len()
+            }
+        };
+        self.touch();
+        let vote = Vote::new(
+            subject,
+            initiator_public_id,
+            self.users.len(),
+            electorate_size,
+            majority_percent,
+        );
+        let progress = vote.progress();
+        if progress.status == VoteStatus::Pending {
+            self.active_vote = Some(vote);
+        }
+        Ok(progress)
+    }
+
+    /// Records the user's ballot, replacing the previous one. The vote is over once the result
+    /// is known. Returns `None` if there is no vote the user can take part in.
+    pub fn cast_vote(&mut self, voter_public_id: &str, in_favor: bool) -> Option<VoteProgress> {
+        if !self
+            .users
+            .iter()
+            .any(|user| user.public_id == voter_public_id)
+        {
+            return None;
+        }
+        if let VoteSubject::Kick { target_public_id } = &self.active_vote.as_ref()?.subject {
+            if target_public_id == voter_public_id {
+                return None;
+            }
+        }
+        self.touch();
+        let vote = self.active_vote.as_mut()?;
+        vote.ballots.insert(voter_public_id.to_string(), in_favor);
+        let progress = vote.progress();
+        if progress.status != VoteStatus::Pending {
+            self.active_vote = None;
+        }
+        Some(progress)
+    }
+
+    /// Ends the vote as failed if it's still running.
+    pub fn expire_vote(&mut self, vote_id: usize) -> Option<VoteProgress> {
+        if self.active_vote.as_ref()?.id != vote_id {
+            return None;
+        }
+        let mut progress = self.active_vote.take()?.progress();
+        progress.status = VoteStatus::Failed;
+        Some(progress)
+    }
+
+    /// Replaces the location of the current round, which restarts its countdown. Returns `false`
+    /// if no round is being played.
+    pub fn skip_location(&mut self) -> bool {
+        let RoomStatus::Playing { current_location } = &mut self.status else {
+            return false;
+        };
+        *current_location = map::locations::random();
+        self.skipped_locations += 1;
+        for user in self.users.iter_mut() {
+            user.last_guess = None;
+            user.submitted_guess = false;
+        }
+        true
     }
 
     pub fn start_playing(&mut self) {
+        self.touch();
         let new_game = self.rounds_left == ROUNDS_PER_GAME;
         self.status = RoomStatus::Playing {
             current_location: map::locations::random(),
@@ -37,11 +265,13 @@ impl Room {
             user.last_guess = None;
             if new_game {
                 user.score = 0;
+                user.is_spectating = false;
             }
         }
     }
 
     pub fn finish_game(&mut self) -> bool {
+        self.touch();
         let prev_position = match &self.status {
             RoomStatus::Playing { current_location } => *current_location,
             _ => {
@@ -53,7 +283,8 @@ impl Room {
             previous_location: Some(prev_position),
         };
         for user in self.users.iter_mut() {
-            if let Some(guess) = user.last_guess {
+            let guess = user.last_guess.filter(|_guess| !user.is_spectating);
+            if let Some(guess) = guess {
                 let last_round_score = map::estimate_guess(guess, prev_position);
                 user.last_round_score = Some(last_round_score);
                 user.score += last_round_score;
@@ -70,29 +301,357 @@ impl Room {
         game_finished
     }
 
+    /// Numbers the event and logs it for replaying. Returns the event with the number added.
+    pub fn record_event(&mut self, raw_event: &str, audience: Audience) -> String {
+        let left_out = self
+            .users
+            .iter()
+            .filter(|user| {
+                user.socket_id
+                    .is_some_and(|socket_id| !audience.includes(socket_id))
+            })
+            .map(|user| user.public_id.clone())
+            .collect();
+        self.events.record(raw_event, left_out)
+    }
+
     pub fn add_message(&mut self, message: ChatMessage) {
-        if self.last_messages.len() >= HOW_MUCH_LAST_MESSAGES_TO_STORE {
-            self.last_messages.pop_front();
+        self.touch();
+        let limit = rooms::chat_history_limit();
+        if limit != 0 && self.chat_history.len() >= limit {
+            self.chat_history.pop_front();
         }
-        self.last_messages.push_back(message);
+        // IDs are taken before the room is locked, so messages sent at the same time can arrive
+        // out of order. Keeping the history sorted is what makes the ID cursors work.
+        let index = self
+            .chat_history
+            .partition_point(|stored| stored.id() < message.id());
+        self.chat_history.insert(index, message);
     }
 
-    pub fn ban_user(&mut self, target_user_public_id: &str) {
-        self.users
-            .retain(|user| user.public_id != target_user_public_id);
-        self.banned_public_users_ids
-            .push(target_user_public_id.to_string());
+    /// Returns up to `limit` messages, oldest first, and whether there are more of them in the
+    /// direction of paging. With `after` set, pages forward from the oldest message past it;
+    /// otherwise, pages back from the newest message before `before`.
+    pub fn messages_page(
+        &self,
+        after: Option<usize>,
+        before: Option<usize>,
+        limit: usize,
+    ) -> (Vec<ChatMessage>, bool) {
+        let start = after.map_or(0, |after| {
+            self.chat_history
+                .partition_point(|message| message.id() <= after)
+        });
+        let end = before
+            .map_or(self.chat_history.len(), |before| {
+                self.chat_history
+                    .partition_point(|message| message.id() < before)
+            })
+            .max(start);
+        let (start, end, has_more) = if after.is_some() {
+            let page_end = end.min(start + limit);
+            (start, page_end, page_end < end)
+        } else {
+            let page_start = start.max(end.saturating_sub(limit));
+            (page_start, end, page_start > start)
+        };
+        (
+            self.chat_history.range(start..end).cloned().collect(),
+            has_more,
+        )
+    }
+
+    fn message_index(&self, message_id: usize) -> Option<usize> {
+        self.chat_history
+            .binary_search_by_key(&message_id, ChatMessage::id)
+            .ok()
+    }
+
+    /// Removes the user from the room without preventing them from joining again.
+    pub fn kick_user(&mut self, target_user_public_id: &str) -> Option<User> {
+        let index = self
+            .users
+            .iter()
+            .position(|user| user.public_id == target_user_public_id)?;
+        Some(self.users.remove(index))
+    }
+
+    pub fn edit_message(
+        &mut self,
+        message_id: usize,
+        editor_public_id: &str,
+        new_content: String,
+    ) -> Result<(), MessageModificationError> {
+        let index = self
+            .message_index(message_id)
+            .ok_or(MessageModificationError::MessageNotFound)?;
+        let ChatMessage::FromPlayer {
+            author_public_id,
+            content,
+            edited,
+            ..
+        } = &mut self.chat_history[index]
+        else {
+            return Err(MessageModificationError::NotTheAuthor);
+        };
+        if author_public_id != editor_public_id {
+            return Err(MessageModificationError::NotTheAuthor);
+        }
+        *content = new_content;
+        *edited = true;
+        self.touch();
+        Ok(())
+    }
+
+    pub fn has_message(&self, message_id: usize) -> bool {
+        self.message_index(message_id).is_some()
+    }
+
+    pub fn toggle_reaction(
+        &mut self,
+        message_id: usize,
+        user_public_id: &str,
+        emoji: &str,
+    ) -> Result<(bool, usize), MessageModificationError> {
+        if !self
+            .users
+            .iter()
+            .any(|user| user.public_id == user_public_id)
+        {
+            return Err(MessageModificationError::NotInTheRoom);
+        }
+        let index = self
+            .message_index(message_id)
+            .ok_or(MessageModificationError::MessageNotFound)?;
+        let reactions = self.chat_history[index].reactions_mut();
+        if !reactions.contains(emoji)
+            && reactions.distinct_emojis_count() >= MAX_DISTINCT_REACTIONS_PER_MESSAGE
+        {
+            return Err(MessageModificationError::TooManyReactions);
+        }
+        let toggled = reactions.toggle(emoji, user_public_id);
+        self.touch();
+        Ok(toggled)
+    }
+
+    /// `can_delete_any` also allows deleting other users' and bot messages.
+    pub fn delete_message(
+        &mut self,
+        message_id: usize,
+        requester_public_id: &str,
+        can_delete_any: bool,
+    ) -> Result<(), MessageModificationError> {
+        let index = self
+            .message_index(message_id)
+            .ok_or(MessageModificationError::MessageNotFound)?;
+        let is_author = self.chat_history[index].author_public_id() == Some(requester_public_id);
+        if !is_author && !can_delete_any {
+            return Err(MessageModificationError::NotTheAuthor);
+        }
+        self.chat_history.remove(index);
+        self.touch();
+        Ok(())
+    }
+
+    /// Removes the user from the room and keeps them out of it for `duration`, or forever if
+    /// it's `None`. Users that aren't in the room at the moment can be banned too.
+    pub fn ban_user(
+        &mut self,
+        target_user_public_id: &str,
+        duration: Option<Duration>,
+    ) -> Option<User> {
+        let removed_user = self.kick_user(target_user_public_id);
+        self.bans
+            .retain(|ban| ban.public_id != target_user_public_id && !ban.has_expired());
+        self.bans.push(Ban {
+            public_id: target_user_public_id.to_string(),
+            username: removed_user.as_ref().map(|user| user.name.clone()),
+            // Too far in the future to tell apart from a permanent ban anyway.
+            expires_at: duration.and_then(|duration| Instant::now().checked_add(duration)),
+        });
+        removed_user
+    }
+
+    /// Returns `false` if the user wasn't banned.
+    pub fn unban_user(&mut self, target_user_public_id: &str) -> bool {
+        let bans_count = self.bans.len();
+        self.bans
+            .retain(|ban| ban.public_id != target_user_public_id);
+        self.bans.len() != bans_count
+    }
+
+    pub fn is_banned(&self, public_user_id: &str) -> bool {
+        self.bans
+            .iter()
+            .any(|ban| ban.public_id == public_user_id && !ban.has_expired())
+    }
+
+    pub fn active_bans(&self) -> Vec<BannedUserInfo> {
+        self.bans
+            .iter()
+            .filter(|ban| !ban.has_expired())
+            .map(|ban| BannedUserInfo {
+                public_id: ban.public_id.clone(),
+                username: ban.username.clone(),
+                expires_in_secs: ban.expires_at.map(|expires_at| {
+                    expires_at
+                        .saturating_duration_since(Instant::now())
+                        .as_secs()
+                }),
+            })
+            .collect()
     }
 
     pub fn users(&self) -> Vec<User> {
         // TODO: maintain `self.users` sorted on insertion
         let mut users = self.users.clone();
-        users.sort_by(|a, b| b.score.cmp(&a.score));
+        users.sort_by_key(|user| std::cmp::Reverse(user.score));
         users
     }
+
+    pub fn summary(&self, room_id: &str) -> RoomSummary {
+        RoomSummary {
+            room_id: room_id.to_string(),
+            users_count: self.users.len(),
+            online_users_count: self
+                .users
+                .iter()
+                .filter(|user| user.presence == Presence::Online)
+                .count(),
+            status: RoomStatusKind::from(self.status),
+            display_name: self
+                .public_listing
+                .as_ref()
+                .map(|public_listing| public_listing.display_name.clone()),
+            is_locked: self.is_locked,
+            idle_secs: self.last_activity.elapsed().as_secs(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MessageModificationError {
+    RoomNotFound,
+    /// E.g. a reaction that arrived after the user was kicked.
+    NotInTheRoom,
+    MessageNotFound,
+    NotTheAuthor,
+    TooManyReactions,
+}
+
+#[derive(Clone, Debug)]
+pub struct Ban {
+    pub public_id: String,
+    /// Unknown if the user was banned while not being in the room.
+    pub username: Option<String>,
+    /// `None` for permanent bans.
+    pub expires_at: Option<Instant>,
+}
+
+impl Ban {
+    pub fn has_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Instant::now())
+    }
+}
+
+/// How a ban is presented in the ban list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BannedUserInfo {
+    pub public_id: String,
+    pub username: Option<String>,
+    pub expires_in_secs: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicListing {
+    pub display_name: String,
+}
+
+/// How a room is presented to the operators in the admin API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSummary {
+    pub room_id: String,
+    pub users_count: usize,
+    pub online_users_count: usize,
+    pub status: RoomStatusKind,
+    /// Only set for public rooms.
+    pub display_name: Option<String>,
+    pub is_locked: bool,
+    /// Seconds since the last activity in the room.
+    pub idle_secs: u64,
 }
 
-#[derive(Copy, Clone, Debug, Serialize)]
+/// How a public room is presented in the rooms list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicRoomInfo {
+    pub room_id: String,
+    pub display_name: String,
+    pub players_count: usize,
+    pub status: RoomStatusKind,
+    pub password_protected: bool,
+    pub rounds_per_game: u64,
+    pub max_players: usize,
+}
+
+/// Same as `RoomStatus`, but without the locations, which must not be revealed to outsiders.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomStatusKind {
+    Waiting,
+    Playing,
+}
+
+impl From<RoomStatus> for RoomStatusKind {
+    fn from(status: RoomStatus) -> Self {
+        match status {
+            RoomStatus::Waiting { .. } => Self::Waiting,
+            RoomStatus::Playing { .. } => Self::Playing,
+        }
+    }
+}
+
+/// Who is allowed to join the room. Users that are already in the room can always reconnect.
+#[derive(Clone, Debug, Default)]
+pub enum RoomAccess {
+    #[default]
+    Open,
+    InviteOnly,
+    /// Both the password and an invite are accepted.
+    PasswordProtected {
+        /// Salted Argon2 hash in the PHC string format.
+        password_hash: String,
+    },
+}
+
+impl RoomAccess {
+    pub fn password_protected(password: &str) -> Self {
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .expect("Failed to hash the room password.")
+            .to_string();
+        Self::PasswordProtected { password_hash }
+    }
+
+    pub fn password_matches(&self, password: &str) -> bool {
+        let Self::PasswordProtected { password_hash } = self else {
+            return false;
+        };
+        let Ok(password_hash) = PasswordHash::new(password_hash) else {
+            return false;
+        };
+        Argon2::default()
+            .verify_password(password.as_bytes(), &password_hash)
+            .is_ok()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum RoomStatus {
     Waiting {
@@ -105,25 +664,100 @@ pub enum RoomStatus {
     },
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ChatMessage {
     FromPlayer {
         r#type: FromPlayerChatMessage,
         id: usize,
+        #[serde(rename = "authorPublicId")]
+        author_public_id: String,
         #[serde(rename = "authorName")]
         author_name: String,
         content: String,
         #[serde(rename = "attachmentIds")]
         attachment_ids: Vec<String>,
+        edited: bool,
+        /// ID of the message this one replies to.
+        #[serde(rename = "replyTo")]
+        reply_to: Option<usize>,
+        reactions: Reactions,
     },
     FromBot {
         r#type: FromBotChatMessage,
         id: usize,
         content: BotMessagePayload,
+        reactions: Reactions,
     },
 }
 
+/// Emoji mapped to the public IDs of the users that reacted with it.
+#[derive(Clone, Debug, Default)]
+pub struct Reactions(BTreeMap<String, BTreeSet<String>>);
+
+impl Reactions {
+    /// Adds the user's reaction, or takes it back if it's already there.
+    pub fn toggle(&mut self, emoji: &str, user_public_id: &str) -> (bool, usize) {
+        let users = self.0.entry(emoji.to_string()).or_default();
+        let added = users.insert(user_public_id.to_string());
+        if !added {
+            users.remove(user_public_id);
+        }
+        let count = users.len();
+        if count == 0 {
+            self.0.remove(emoji);
+        }
+        (added, count)
+    }
+
+    pub fn distinct_emojis_count(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn contains(&self, emoji: &str) -> bool {
+        self.0.contains_key(emoji)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReactionSummary<'a> {
+    emoji: &'a str,
+    count: usize,
+    user_public_ids: &'a BTreeSet<String>,
+}
+
+/// `ReactionSummary` as read back from a room snapshot; the count is implied.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReactionEntry {
+    emoji: String,
+    user_public_ids: BTreeSet<String>,
+}
+
+impl Serialize for Reactions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|(emoji, users)| ReactionSummary {
+            emoji,
+            count: users.len(),
+            user_public_ids: users,
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for Reactions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<ReactionEntry>::deserialize(deserializer)?;
+        Ok(Self(
+            entries
+                .into_iter()
+                .filter(|entry| !entry.user_public_ids.is_empty())
+                .map(|entry| (entry.emoji, entry.user_public_ids))
+                .collect(),
+        ))
+    }
+}
+
 #[derive(Clone, Debug, Serialize_unit_struct, Deserialize_unit_struct)]
 pub struct FromPlayerChatMessage;
 
@@ -131,14 +765,24 @@ pub struct FromPlayerChatMessage;
 pub struct FromBotChatMessage;
 
 impl ChatMessage {
-    pub fn from_player(author_name: String, content: String, attachment_ids: Vec<String>) -> Self {
+    pub fn from_player(
+        author_public_id: String,
+        author_name: String,
+        content: String,
+        attachment_ids: Vec<String>,
+        reply_to: Option<usize>,
+    ) -> Self {
         let id = NEXT_CHAT_MESSAGE_ID.fetch_add(1, Ordering::Relaxed);
         Self::FromPlayer {
             r#type: FromPlayerChatMessage {},
             id,
+            author_public_id,
             author_name,
             content,
             attachment_ids,
+            edited: false,
+            reply_to,
+            reactions: Reactions::default(),
         }
     }
     pub fn from_bot(content: BotMessagePayload) -> Self {
@@ -147,6 +791,7 @@ impl ChatMessage {
             r#type: FromBotChatMessage {},
             id,
             content,
+            reactions: Reactions::default(),
         }
     }
 
@@ -156,4 +801,28 @@ impl ChatMessage {
             Self::FromBot { id, .. } => *id,
         }
     }
+
+    /// `None` for bot messages.
+    pub fn author_public_id(&self) -> Option<&str> {
+        match self {
+            Self::FromPlayer {
+                author_public_id, ..
+            } => Some(author_public_id),
+            Self::FromBot { .. } => None,
+        }
+    }
+
+    pub fn reactions_mut(&mut self) -> &mut Reactions {
+        match self {
+            Self::FromPlayer { reactions, .. } => reactions,
+            Self::FromBot { reactions, .. } => reactions,
+        }
+    }
+
+    pub fn attachment_ids(&self) -> &[String] {
+        match self {
+            Self::FromPlayer { attachment_ids, .. } => attachment_ids,
+            Self::FromBot { .. } => &[],
+        }
+    }
 }
@@ -0,0 +1,278 @@
+use crate::app_context::{AppContext, RequestContext};
+use crate::moderation::{self, ModerationOutcome, RejectionReason};
+use crate::rooms::consts::{MAX_MESSAGE_LENGTH, MAX_REACTION_BYTES};
+use crate::rooms::message_types::{
+    self, ChatMessageRejectedPayload, ClientSentChatMessagePayload, MessageDeletedPayload,
+    MessageEditedPayload, ReactionToggledPayload, ServerSentChatMessagePayload,
+    ServerSentSocketMessage,
+};
+use crate::rooms::models::ChatMessage;
+use crate::storage::interface::IRoomStorage;
+use crate::storage::sockets::Audience;
+use crate::users::models::Permission;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub struct ChatWsHandler<'a, RS: IRoomStorage> {
+    app_context: AppContext<RS>,
+    request_context: &'a RequestContext,
+    socket_id: usize,
+}
+
+impl<'a, RS> ChatWsHandler<'a, RS>
+where
+    RS: IRoomStorage,
+{
+    pub fn new(
+        app_context: AppContext<RS>,
+        request_context: &'a RequestContext,
+        socket_id: usize,
+    ) -> Self {
+        Self {
+            app_context,
+            request_context,
+            socket_id,
+        }
+    }
+
+    /// The author adds the message to their chat on their own, so it isn't sent back to them.
+    pub async fn post(&self, payload: ClientSentChatMessagePayload) {
+        if !self
+            .app_context
+            .rooms
+            .has_user_with_such_private_id(
+                &self.request_context.room_id,
+                &self.request_context.private_id,
+            )
+            .await
+        {
+            eprintln!("Rejecting a message from a user that isn't in the room.");
+            return;
+        }
+        let Some(content) = self.moderate(payload.content).await else {
+            return;
+        };
+        let reply_to = match payload.reply_to {
+            Some(parent_id)
+                if !self
+                    .app_context
+                    .rooms
+                    .has_message(&self.request_context.room_id, parent_id)
+                    .await =>
+            {
+                eprintln!("Dropping the reference to unknown parent message {parent_id}.");
+                None
+            }
+            reply_to => reply_to,
+        };
+        let chat_message = ChatMessage::from_player(
+            self.request_context.public_id.clone(),
+            payload.from.clone(),
+            content.clone(),
+            payload.attachment_ids.clone(),
+            reply_to,
+        );
+        let ws_chat_message = ServerSentSocketMessage::ChatMessage {
+            r#type: message_types::ChatMessage,
+            payload: ServerSentChatMessagePayload {
+                id: chat_message.id(),
+                from: payload.from,
+                content,
+                attachment_ids: payload.attachment_ids,
+                reply_to,
+            },
+        };
+        self.app_context
+            .rooms
+            .add_message(&self.request_context.room_id, chat_message)
+            .await;
+        self.app_context
+            .broadcast_event(
+                &self.request_context.room_id,
+                &ws_chat_message,
+                Audience::EveryoneExcept(self.socket_id),
+            )
+            .await;
+    }
+
+    pub async fn edit(&self, message_id: usize, content: String) {
+        let Some(content) = self.moderate(content).await else {
+            return;
+        };
+        if let Err(refusal_reason) = self
+            .app_context
+            .rooms
+            .edit_message(
+                &self.request_context.room_id,
+                message_id,
+                &self.request_context.public_id,
+                content.clone(),
+            )
+            .await
+        {
+            eprintln!("Refusing to edit message {message_id}: {refusal_reason:?}.");
+            return;
+        }
+        let ws_event_msg = ServerSentSocketMessage::MessageEdited {
+            r#type: message_types::MessageEdited,
+            payload: MessageEditedPayload {
+                id: message_id,
+                content,
+            },
+        };
+        self.broadcast(&ws_event_msg).await;
+    }
+
+    pub async fn delete(&self, message_id: usize) {
+        let can_delete_any = self
+            .app_context
+            .rooms
+            .has_permission(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+                Permission::DeleteMessages,
+            )
+            .await;
+        if let Err(refusal_reason) = self
+            .app_context
+            .rooms
+            .delete_message(
+                &self.request_context.room_id,
+                message_id,
+                &self.request_context.public_id,
+                can_delete_any,
+            )
+            .await
+        {
+            eprintln!("Refusing to delete message {message_id}: {refusal_reason:?}.");
+            return;
+        }
+        let ws_event_msg = ServerSentSocketMessage::MessageDeleted {
+            r#type: message_types::MessageDeleted,
+            payload: MessageDeletedPayload { id: message_id },
+        };
+        self.broadcast(&ws_event_msg).await;
+    }
+
+    pub async fn toggle_reaction(&self, message_id: usize, emoji: String) {
+        if emoji.graphemes(true).count() != 1 || emoji.len() > MAX_REACTION_BYTES {
+            eprintln!("Rejecting a reaction because it isn't a single emoji: {emoji:?}.");
+            return;
+        }
+        if self
+            .app_context
+            .rooms
+            .is_muted(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+            )
+            .await
+        {
+            return;
+        }
+        let (added, count) = match self
+            .app_context
+            .rooms
+            .toggle_reaction(
+                &self.request_context.room_id,
+                message_id,
+                &self.request_context.public_id,
+                &emoji,
+            )
+            .await
+        {
+            Ok(toggled) => toggled,
+            Err(refusal_reason) => {
+                eprintln!("Refusing to react to message {message_id}: {refusal_reason:?}.");
+                return;
+            }
+        };
+        let ws_event_msg = ServerSentSocketMessage::ReactionToggled {
+            r#type: message_types::ReactionToggled,
+            payload: ReactionToggledPayload {
+                message_id,
+                emoji,
+                user_public_id: self.request_context.public_id.clone(),
+                added,
+                count,
+            },
+        };
+        self.broadcast(&ws_event_msg).await;
+    }
+
+    /// Returns the content as it should be shown in the chat, or `None` if it can't be posted.
+    async fn moderate(&self, content: String) -> Option<String> {
+        if self
+            .app_context
+            .rooms
+            .is_muted(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+            )
+            .await
+        {
+            return None;
+        }
+        if content.graphemes(true).count() > MAX_MESSAGE_LENGTH {
+            eprintln!(
+                "Rejecting a message because the it is too long: \
+                    {} symbols when at most {} is allowed.",
+                content.len(),
+                MAX_MESSAGE_LENGTH,
+            );
+            return None;
+        }
+        match moderation::pipeline().moderate(
+            &self.request_context.room_id,
+            &self.request_context.public_id,
+            content,
+        ) {
+            ModerationOutcome::Accepted { content } => Some(content),
+            ModerationOutcome::Rejected {
+                reason,
+                mute_author,
+            } => {
+                self.on_rejected(reason, mute_author).await;
+                None
+            }
+        }
+    }
+
+    async fn on_rejected(&self, reason: RejectionReason, mute_author: bool) {
+        // The socket may not have joined the room yet, in which case there is nobody to mute.
+        let muted = mute_author
+            && self
+                .app_context
+                .rooms
+                .mute(
+                    &self.request_context.room_id,
+                    &self.request_context.public_id,
+                )
+                .await;
+        let ws_message = ServerSentSocketMessage::ChatMessageRejected {
+            r#type: message_types::ChatMessageRejected,
+            payload: ChatMessageRejectedPayload { reason, muted },
+        };
+        let msg = serde_json::to_string(&ws_message).unwrap();
+        self.app_context
+            .sockets
+            .send_msg(&msg, self.socket_id)
+            .await;
+        if !muted {
+            return;
+        }
+        let ws_event_msg = ServerSentSocketMessage::UserMuted {
+            r#type: message_types::UserMuted,
+        };
+        self.broadcast(&ws_event_msg).await;
+    }
+
+    async fn broadcast(&self, ws_event_msg: &ServerSentSocketMessage) {
+        self.app_context
+            .broadcast_event(
+                &self.request_context.room_id,
+                ws_event_msg,
+                Audience::Everyone,
+            )
+            .await;
+    }
+}
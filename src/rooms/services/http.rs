@@ -1,10 +1,26 @@
 use crate::app_context::{AppContext, RequestContext};
-use crate::rooms::consts::MAX_USERNAME_LENGTH;
+use crate::auth::invite;
+use crate::http::requests::RoomCredentialsQueryParams;
+use crate::rooms::consts::{
+    MAX_PLAYERS_LIMIT, MAX_ROOM_DISPLAY_NAME_LENGTH, MAX_USERNAME_LENGTH,
+    QUICK_PLAY_ROOM_DISPLAY_NAME,
+};
+use crate::rooms::message_types::{self, ServerSentSocketMessage};
+use crate::rooms::models::{PublicListing, RoomAccess};
 use crate::rooms::services::responses::{
-    CanConnectToRoomResponse, ConnectionRefusalError, CreateRoomResponse, RoomMessagesResponse,
-    RoomMessagesResponseError, RoomUsersResponse, RoomUsersResponseError,
+    CanConnectToRoomResponse, ChangeCoHostPermissionsResponse, ChangeMaxPlayersResponse,
+    ChangeRoomAccessResponse, ChangeRoomListingResponse, CoHostPermissionsChangeError,
+    ConnectionRefusalError, CreateInviteResponse, CreateRoomResponse, InviteCreationError,
+    LockRoomResponse, MaxPlayersChangeError, PublicRoomsResponse, QuickPlayResponse,
+    RoomAccessChangeError, RoomBansResponse, RoomBansResponseError, RoomListingChangeError,
+    RoomLockingError, RoomMessagesResponse, RoomMessagesResponseError, RoomUnlockingError,
+    RoomUsersResponse, RoomUsersResponseError, UnlockRoomResponse,
 };
+use crate::storage::consts::{DEFAULT_MESSAGES_PAGE_SIZE, MAX_MESSAGES_PAGE_SIZE};
 use crate::storage::interface::IRoomStorage;
+use crate::storage::sockets::Audience;
+use crate::users::models::Permission;
+use std::time::Duration;
 use unicode_segmentation::UnicodeSegmentation;
 
 pub struct RoomHttpHandler<'a, RS: IRoomStorage> {
@@ -23,7 +39,11 @@ where
         }
     }
 
-    pub async fn can_connect(&self, username: String) -> CanConnectToRoomResponse {
+    pub async fn can_connect(
+        &self,
+        username: String,
+        credentials: RoomCredentialsQueryParams,
+    ) -> CanConnectToRoomResponse {
         if !self
             .app_context
             .rooms
@@ -50,18 +70,10 @@ where
                 error_code: Some(ConnectionRefusalError::UserAlreadyInRoom),
             };
         }
-        if self
-            .app_context
-            .rooms
-            .is_banned(
-                &self.request_context.room_id,
-                &self.request_context.public_id,
-            )
-            .await
-        {
+        if let Err(refusal_reason) = self.check_access(&credentials).await {
             return CanConnectToRoomResponse {
                 can_connect: false,
-                error_code: Some(ConnectionRefusalError::UserBanned),
+                error_code: Some(refusal_reason),
             };
         }
         if username.graphemes(true).count() > MAX_USERNAME_LENGTH {
@@ -82,7 +94,447 @@ where
         }
     }
 
-    pub async fn users(&self) -> RoomUsersResponse {
+    /// Checks whether the user is allowed into the room, assuming that the room exists.
+    pub async fn check_access(
+        &self,
+        credentials: &RoomCredentialsQueryParams,
+    ) -> Result<(), ConnectionRefusalError> {
+        if self
+            .app_context
+            .rooms
+            .has_user_with_such_private_id(
+                &self.request_context.room_id,
+                &self.request_context.private_id,
+            )
+            .await
+        {
+            return Ok(());
+        }
+        if self
+            .app_context
+            .rooms
+            .is_banned(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+            )
+            .await
+        {
+            return Err(ConnectionRefusalError::UserBanned);
+        }
+        if self
+            .app_context
+            .rooms
+            .is_locked(&self.request_context.room_id)
+            .await
+        {
+            return Err(ConnectionRefusalError::RoomLocked);
+        }
+        if self
+            .app_context
+            .rooms
+            .is_full(&self.request_context.room_id)
+            .await
+        {
+            return Err(ConnectionRefusalError::RoomFull);
+        }
+        self.check_credentials(credentials).await
+    }
+
+    /// Whether the user may see who is in the room and read its chat without joining it.
+    async fn can_look_into(&self, credentials: &RoomCredentialsQueryParams) -> bool {
+        if self
+            .app_context
+            .rooms
+            .has_user_with_such_private_id(
+                &self.request_context.room_id,
+                &self.request_context.private_id,
+            )
+            .await
+        {
+            return true;
+        }
+        if self
+            .app_context
+            .rooms
+            .is_banned(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+            )
+            .await
+        {
+            return false;
+        }
+        self.check_credentials(credentials).await.is_ok()
+    }
+
+    async fn check_credentials(
+        &self,
+        credentials: &RoomCredentialsQueryParams,
+    ) -> Result<(), ConnectionRefusalError> {
+        let access = self
+            .app_context
+            .rooms
+            .access(&self.request_context.room_id)
+            .await
+            .ok_or(ConnectionRefusalError::RoomNotFound)?;
+        if let RoomAccess::Open = access {
+            return Ok(());
+        }
+        if let Some(invite_token) = &credentials.invite {
+            return invite::verify(invite_token, &self.request_context.room_id)
+                .map_err(|_| ConnectionRefusalError::InvalidInvite);
+        }
+        match (access, &credentials.password) {
+            (RoomAccess::InviteOnly, _) => Err(ConnectionRefusalError::InviteRequired),
+            (access, Some(password)) if access.password_matches(password) => Ok(()),
+            (_, Some(_)) => Err(ConnectionRefusalError::WrongPassword),
+            (_, None) => Err(ConnectionRefusalError::PasswordRequired),
+        }
+    }
+
+    pub async fn change_access(
+        &self,
+        private: bool,
+        password: Option<String>,
+    ) -> ChangeRoomAccessResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return ChangeRoomAccessResponse {
+                error: true,
+                error_code: Some(RoomAccessChangeError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::ManageRoom).await {
+            return ChangeRoomAccessResponse {
+                error: true,
+                error_code: Some(RoomAccessChangeError::YouAreNotTheHost),
+            };
+        }
+        let access = match (private, password) {
+            (false, _) => RoomAccess::Open,
+            (true, Some(password)) if !password.is_empty() => {
+                RoomAccess::password_protected(&password)
+            }
+            (true, _) => RoomAccess::InviteOnly,
+        };
+        if !self
+            .app_context
+            .rooms
+            .set_access(&self.request_context.room_id, access)
+            .await
+        {
+            return ChangeRoomAccessResponse {
+                error: true,
+                error_code: Some(RoomAccessChangeError::RoomNotFound),
+            };
+        }
+        ChangeRoomAccessResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    pub async fn create_invite(&self, valid_for: Duration) -> CreateInviteResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return CreateInviteResponse {
+                error: true,
+                error_code: Some(InviteCreationError::RoomNotFound),
+                invite_token: None,
+            };
+        }
+        if !self.is_allowed_to(Permission::ManageRoom).await {
+            return CreateInviteResponse {
+                error: true,
+                error_code: Some(InviteCreationError::YouAreNotTheHost),
+                invite_token: None,
+            };
+        }
+        CreateInviteResponse {
+            error: false,
+            error_code: None,
+            invite_token: Some(invite::issue(&self.request_context.room_id, valid_for)),
+        }
+    }
+
+    pub async fn change_listing(
+        &self,
+        public: bool,
+        display_name: Option<String>,
+    ) -> ChangeRoomListingResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return ChangeRoomListingResponse {
+                error: true,
+                error_code: Some(RoomListingChangeError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::ManageRoom).await {
+            return ChangeRoomListingResponse {
+                error: true,
+                error_code: Some(RoomListingChangeError::YouAreNotTheHost),
+            };
+        }
+        let public_listing = match public_listing(public, display_name) {
+            Ok(public_listing) => public_listing,
+            Err(error_code) => {
+                return ChangeRoomListingResponse {
+                    error: true,
+                    error_code: Some(error_code),
+                }
+            }
+        };
+        if !self
+            .app_context
+            .rooms
+            .set_public_listing(&self.request_context.room_id, public_listing)
+            .await
+        {
+            return ChangeRoomListingResponse {
+                error: true,
+                error_code: Some(RoomListingChangeError::RoomNotFound),
+            };
+        }
+        ChangeRoomListingResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    pub async fn change_max_players(&self, max_players: usize) -> ChangeMaxPlayersResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return ChangeMaxPlayersResponse {
+                error: true,
+                error_code: Some(MaxPlayersChangeError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::ManageRoom).await {
+            return ChangeMaxPlayersResponse {
+                error: true,
+                error_code: Some(MaxPlayersChangeError::YouAreNotTheHost),
+            };
+        }
+        // Users that are already in the room are never kicked out, so the limit may be lower
+        // than the current number of users.
+        if !(1..=MAX_PLAYERS_LIMIT).contains(&max_players) {
+            return ChangeMaxPlayersResponse {
+                error: true,
+                error_code: Some(MaxPlayersChangeError::InvalidMaxPlayers),
+            };
+        }
+        if !self
+            .app_context
+            .rooms
+            .set_max_players(&self.request_context.room_id, max_players)
+            .await
+        {
+            return ChangeMaxPlayersResponse {
+                error: true,
+                error_code: Some(MaxPlayersChangeError::RoomNotFound),
+            };
+        }
+        ChangeMaxPlayersResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    pub async fn lock(&self) -> LockRoomResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return LockRoomResponse {
+                error: true,
+                error_code: Some(RoomLockingError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::ManageRoom).await {
+            return LockRoomResponse {
+                error: true,
+                error_code: Some(RoomLockingError::YouAreNotTheHost),
+            };
+        }
+        if !self
+            .app_context
+            .rooms
+            .set_locked(&self.request_context.room_id, true)
+            .await
+        {
+            return LockRoomResponse {
+                error: true,
+                error_code: Some(RoomLockingError::RoomNotFound),
+            };
+        }
+        let ws_event_msg = ServerSentSocketMessage::RoomLocked {
+            r#type: message_types::RoomLocked,
+        };
+        self.app_context
+            .broadcast_event(
+                &self.request_context.room_id,
+                &ws_event_msg,
+                Audience::Everyone,
+            )
+            .await;
+        LockRoomResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    pub async fn unlock(&self) -> UnlockRoomResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return UnlockRoomResponse {
+                error: true,
+                error_code: Some(RoomUnlockingError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::ManageRoom).await {
+            return UnlockRoomResponse {
+                error: true,
+                error_code: Some(RoomUnlockingError::YouAreNotTheHost),
+            };
+        }
+        if !self
+            .app_context
+            .rooms
+            .set_locked(&self.request_context.room_id, false)
+            .await
+        {
+            return UnlockRoomResponse {
+                error: true,
+                error_code: Some(RoomUnlockingError::RoomNotFound),
+            };
+        }
+        let ws_event_msg = ServerSentSocketMessage::RoomUnlocked {
+            r#type: message_types::RoomUnlocked,
+        };
+        self.app_context
+            .broadcast_event(
+                &self.request_context.room_id,
+                &ws_event_msg,
+                Audience::Everyone,
+            )
+            .await;
+        UnlockRoomResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    /// Chooses which of the host's permissions the co-hosts share. Managing roles always stays
+    /// with the host alone.
+    pub async fn change_co_host_permissions(
+        &self,
+        permissions: Vec<Permission>,
+    ) -> ChangeCoHostPermissionsResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return ChangeCoHostPermissionsResponse {
+                error: true,
+                error_code: Some(CoHostPermissionsChangeError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::ManageRoles).await {
+            return ChangeCoHostPermissionsResponse {
+                error: true,
+                error_code: Some(CoHostPermissionsChangeError::YouAreNotTheHost),
+            };
+        }
+        if permissions.contains(&Permission::ManageRoles) {
+            return ChangeCoHostPermissionsResponse {
+                error: true,
+                error_code: Some(CoHostPermissionsChangeError::PermissionNotShareable),
+            };
+        }
+        if !self
+            .app_context
+            .rooms
+            .set_co_host_permissions(
+                &self.request_context.room_id,
+                permissions.into_iter().collect(),
+            )
+            .await
+        {
+            return ChangeCoHostPermissionsResponse {
+                error: true,
+                error_code: Some(CoHostPermissionsChangeError::RoomNotFound),
+            };
+        }
+        ChangeCoHostPermissionsResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    pub async fn bans(&self) -> RoomBansResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return RoomBansResponse {
+                error: true,
+                error_code: Some(RoomBansResponseError::RoomNotFound),
+                bans: None,
+            };
+        }
+        if !self.is_allowed_to(Permission::Ban).await {
+            return RoomBansResponse {
+                error: true,
+                error_code: Some(RoomBansResponseError::YouAreNotTheHost),
+                bans: None,
+            };
+        }
+        match self
+            .app_context
+            .rooms
+            .bans(&self.request_context.room_id)
+            .await
+        {
+            Some(bans) => RoomBansResponse {
+                error: false,
+                error_code: None,
+                bans: Some(bans),
+            },
+            None => RoomBansResponse {
+                error: true,
+                error_code: Some(RoomBansResponseError::RoomNotFound),
+                bans: None,
+            },
+        }
+    }
+
+    pub async fn users(&self, credentials: &RoomCredentialsQueryParams) -> RoomUsersResponse {
         if !self
             .app_context
             .rooms
@@ -96,25 +548,48 @@ where
                 status: None,
             };
         }
+        if !self.can_look_into(credentials).await {
+            return RoomUsersResponse {
+                error: true,
+                error_code: Some(RoomUsersResponseError::NotAllowed),
+                users: None,
+                status: None,
+            };
+        }
+        let users = self
+            .app_context
+            .rooms
+            .users(&self.request_context.room_id)
+            .await;
+        let status = self
+            .app_context
+            .rooms
+            .status(&self.request_context.room_id)
+            .await;
+        let (Some(users), Some(status)) = (users, status) else {
+            return RoomUsersResponse {
+                error: true,
+                error_code: Some(RoomUsersResponseError::RoomNotFound),
+                users: None,
+                status: None,
+            };
+        };
         RoomUsersResponse {
             error: false,
             error_code: None,
-            users: Some(
-                self.app_context
-                    .rooms
-                    .users(&self.request_context.room_id)
-                    .await,
-            ),
-            status: Some(
-                self.app_context
-                    .rooms
-                    .status(&self.request_context.room_id)
-                    .await,
-            ),
+            users: Some(users),
+            status: Some(status),
         }
     }
 
-    pub async fn messages(&self) -> RoomMessagesResponse {
+    /// Pages forward from `after` if it's set, otherwise back from `before` or the latest message.
+    pub async fn messages(
+        &self,
+        credentials: &RoomCredentialsQueryParams,
+        after: Option<usize>,
+        before: Option<usize>,
+        limit: Option<usize>,
+    ) -> RoomMessagesResponse {
         if !self
             .app_context
             .rooms
@@ -125,19 +600,51 @@ where
                 error: true,
                 error_code: Some(RoomMessagesResponseError::RoomNotFound),
                 messages: None,
+                has_more: None,
+            };
+        }
+        if !self.can_look_into(credentials).await {
+            return RoomMessagesResponse {
+                error: true,
+                error_code: Some(RoomMessagesResponseError::NotAllowed),
+                messages: None,
+                has_more: None,
             };
         }
+        let limit = limit
+            .unwrap_or(DEFAULT_MESSAGES_PAGE_SIZE)
+            .clamp(1, MAX_MESSAGES_PAGE_SIZE);
+        let Some((messages, has_more)) = self
+            .app_context
+            .rooms
+            .messages(&self.request_context.room_id, after, before, limit)
+            .await
+        else {
+            return RoomMessagesResponse {
+                error: true,
+                error_code: Some(RoomMessagesResponseError::RoomNotFound),
+                messages: None,
+                has_more: None,
+            };
+        };
         RoomMessagesResponse {
             error: false,
             error_code: None,
-            messages: Some(
-                self.app_context
-                    .rooms
-                    .messages(&self.request_context.room_id)
-                    .await,
-            ),
+            messages: Some(messages),
+            has_more: Some(has_more),
         }
     }
+
+    async fn is_allowed_to(&self, permission: Permission) -> bool {
+        self.app_context
+            .rooms
+            .has_permission(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+                permission,
+            )
+            .await
+    }
 }
 
 pub struct CreateRoomHttpHandler<RS: IRoomStorage> {
@@ -152,9 +659,71 @@ where
         Self { app_context }
     }
 
-    pub async fn create(&self) -> CreateRoomResponse {
-        CreateRoomResponse {
-            room_id: self.app_context.rooms.create().await,
+    pub async fn create(&self, public: bool, display_name: Option<String>) -> CreateRoomResponse {
+        match public_listing(public, display_name) {
+            Ok(public_listing) => {
+                let room_id = self.app_context.rooms.create(public_listing).await;
+                self.app_context.cluster.claim(&room_id).await;
+                CreateRoomResponse {
+                    error: false,
+                    error_code: None,
+                    room_id: Some(room_id),
+                }
+            }
+            Err(error_code) => CreateRoomResponse {
+                error: true,
+                error_code: Some(error_code),
+                room_id: None,
+            },
         }
     }
 }
+
+pub struct PublicRoomsHttpHandler<RS: IRoomStorage> {
+    app_context: AppContext<RS>,
+}
+
+impl<RS> PublicRoomsHttpHandler<RS>
+where
+    RS: IRoomStorage,
+{
+    pub fn new(app_context: AppContext<RS>) -> Self {
+        Self { app_context }
+    }
+
+    pub async fn list(&self) -> PublicRoomsResponse {
+        PublicRoomsResponse {
+            rooms: self.app_context.rooms.public_rooms().await,
+        }
+    }
+
+    pub async fn quick_play(&self) -> QuickPlayResponse {
+        let public_listing = PublicListing {
+            display_name: QUICK_PLAY_ROOM_DISPLAY_NAME.to_string(),
+        };
+        let room_id = self
+            .app_context
+            .rooms
+            .find_or_create_quick_play_room(public_listing)
+            .await;
+        self.app_context.cluster.claim(&room_id).await;
+        QuickPlayResponse { room_id }
+    }
+}
+
+fn public_listing(
+    public: bool,
+    display_name: Option<String>,
+) -> Result<Option<PublicListing>, RoomListingChangeError> {
+    if !public {
+        return Ok(None);
+    }
+    let display_name = match display_name {
+        Some(display_name) if !display_name.trim().is_empty() => display_name.trim().to_string(),
+        _ => return Err(RoomListingChangeError::DisplayNameRequired),
+    };
+    if display_name.graphemes(true).count() > MAX_ROOM_DISPLAY_NAME_LENGTH {
+        return Err(RoomListingChangeError::DisplayNameTooLong);
+    }
+    Ok(Some(PublicListing { display_name }))
+}
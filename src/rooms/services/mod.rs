@@ -1,2 +1,4 @@
+pub mod chat;
 pub mod http;
 pub mod responses;
+pub mod votes;
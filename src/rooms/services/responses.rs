@@ -1,7 +1,7 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    rooms::models::{ChatMessage, RoomStatus},
+    rooms::models::{BannedUserInfo, ChatMessage, PublicRoomInfo, RoomStatus},
     users::models::User,
 };
 
@@ -25,6 +25,18 @@ pub enum ConnectionRefusalError {
     UserAlreadyInRoom,
     UsernameTooLong,
     UserBanned,
+    /// The user's public ID or IP is banned from the whole server.
+    BannedFromServer,
+    /// The room is password protected and neither a password nor an invite was provided.
+    PasswordRequired,
+    WrongPassword,
+    /// The room can only be joined with an invite.
+    InviteRequired,
+    /// The invite is expired, forged or was issued for another room.
+    InvalidInvite,
+    RoomFull,
+    /// The host doesn't let new users in.
+    RoomLocked,
 }
 
 #[derive(Serialize)]
@@ -44,6 +56,25 @@ pub struct RoomUsersResponse {
 #[serde(rename_all = "camelCase")]
 pub enum RoomUsersResponseError {
     RoomNotFound,
+    /// The room is private and the user is neither in it nor has a password or an invite.
+    NotAllowed,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomBansResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoomBansResponseError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bans: Option<Vec<BannedUserInfo>>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomBansResponseError {
+    RoomNotFound,
+    YouAreNotTheHost,
 }
 
 #[derive(Serialize)]
@@ -54,16 +85,148 @@ pub struct RoomMessagesResponse {
     pub error_code: Option<RoomMessagesResponseError>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub messages: Option<Vec<ChatMessage>>,
+    /// Whether there are more messages in the direction of paging.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_more: Option<bool>,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum RoomMessagesResponseError {
     RoomNotFound,
+    /// The room is private and the user is neither in it nor has a password or an invite.
+    NotAllowed,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateRoomResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoomListingChangeError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicRoomsResponse {
+    pub rooms: Vec<PublicRoomInfo>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickPlayResponse {
     pub room_id: String,
 }
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeRoomListingResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoomListingChangeError>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomListingChangeError {
+    RoomNotFound,
+    YouAreNotTheHost,
+    DisplayNameRequired,
+    DisplayNameTooLong,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeRoomAccessResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoomAccessChangeError>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomAccessChangeError {
+    RoomNotFound,
+    YouAreNotTheHost,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateInviteResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<InviteCreationError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_token: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InviteCreationError {
+    RoomNotFound,
+    YouAreNotTheHost,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeMaxPlayersResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<MaxPlayersChangeError>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MaxPlayersChangeError {
+    RoomNotFound,
+    YouAreNotTheHost,
+    InvalidMaxPlayers,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockRoomResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoomLockingError>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomLockingError {
+    RoomNotFound,
+    YouAreNotTheHost,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockRoomResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoomUnlockingError>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomUnlockingError {
+    RoomNotFound,
+    YouAreNotTheHost,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeCoHostPermissionsResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<CoHostPermissionsChangeError>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CoHostPermissionsChangeError {
+    RoomNotFound,
+    YouAreNotTheHost,
+    PermissionNotShareable,
+}
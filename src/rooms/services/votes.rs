@@ -0,0 +1,191 @@
+use crate::app_context::{AppContext, RequestContext};
+use crate::rooms::message_types::{
+    self, BotMessagePayload, ServerSentSocketMessage, VoteFinishedBotMessagePayload,
+    VoteFinishedBotMsg, VoteProgressBotMessagePayload, VoteProgressBotMsg,
+    VoteStartedBotMessagePayload, VoteStartedBotMsg,
+};
+use crate::rooms::models::ChatMessage;
+use crate::rooms::votes::{self, VoteProgress, VoteStatus, VoteSubject};
+use crate::storage::interface::{RoomInfoRepo, RoomRepo, RoomVotesRepo, UserPermissionsRepo};
+use crate::storage::rooms::HashMapRoomsStorage;
+use crate::storage::sockets::Audience;
+
+/// Runs the votes that players start over the WebSocket.
+// Not generic over rooms storage because the vote timeout is tracked in a spawned task.
+pub struct VotesWsHandler<'a> {
+    app_context: AppContext<HashMapRoomsStorage>,
+    request_context: &'a RequestContext,
+}
+
+impl<'a> VotesWsHandler<'a> {
+    pub fn new(
+        app_context: AppContext<HashMapRoomsStorage>,
+        request_context: &'a RequestContext,
+    ) -> Self {
+        Self {
+            app_context,
+            request_context,
+        }
+    }
+
+    pub async fn start(&self, subject: VoteSubject) {
+        let settings = votes::settings();
+        let progress = match self
+            .app_context
+            .rooms
+            .start_vote(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+                subject,
+                settings.majority_percent,
+            )
+            .await
+        {
+            Ok(progress) => progress,
+            Err(refusal_reason) => {
+                eprintln!("Refusing to start a vote: {refusal_reason:?}.");
+                return;
+            }
+        };
+        let initiator_name = self
+            .app_context
+            .rooms
+            .users(&self.request_context.room_id)
+            .await
+            .into_iter()
+            .flatten()
+            .find(|user| user.public_id == self.request_context.public_id)
+            .map(|user| user.name)
+            .unwrap_or_default();
+        let bot_message_payload = BotMessagePayload::VoteStarted {
+            r#type: VoteStartedBotMsg,
+            payload: VoteStartedBotMessagePayload {
+                vote_id: progress.vote_id,
+                initiator_name,
+                subject: progress.subject.clone(),
+                votes_needed: progress.votes_needed,
+                timeout_secs: settings.timeout.as_secs(),
+            },
+        };
+        send_bot_message(
+            &self.app_context,
+            &self.request_context.room_id,
+            bot_message_payload,
+        )
+        .await;
+        if progress.status != VoteStatus::Pending {
+            // The initiator's own vote was enough, say, because they are alone in the room.
+            finish(&self.app_context, &self.request_context.room_id, progress).await;
+            return;
+        }
+        let app_context = self.app_context.clone();
+        let room_id = self.request_context.room_id.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(settings.timeout).await;
+            let expired_vote = app_context
+                .rooms
+                .expire_vote(&room_id, progress.vote_id)
+                .await;
+            if let Some(progress) = expired_vote {
+                finish(&app_context, &room_id, progress).await;
+            }
+        });
+    }
+
+    pub async fn cast(&self, in_favor: bool) {
+        let Some(progress) = self
+            .app_context
+            .rooms
+            .cast_vote(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+                in_favor,
+            )
+            .await
+        else {
+            return;
+        };
+        let bot_message_payload = BotMessagePayload::VoteProgress {
+            r#type: VoteProgressBotMsg,
+            payload: VoteProgressBotMessagePayload {
+                vote_id: progress.vote_id,
+                votes_for: progress.votes_for,
+                votes_against: progress.votes_against,
+                votes_needed: progress.votes_needed,
+            },
+        };
+        send_bot_message(
+            &self.app_context,
+            &self.request_context.room_id,
+            bot_message_payload,
+        )
+        .await;
+        if progress.status != VoteStatus::Pending {
+            finish(&self.app_context, &self.request_context.room_id, progress).await;
+        }
+    }
+}
+
+/// Announces the result of the vote and, if it passed, carries it out.
+async fn finish(
+    app_context: &AppContext<HashMapRoomsStorage>,
+    room_id: &str,
+    progress: VoteProgress,
+) {
+    let passed = progress.status == VoteStatus::Passed;
+    let bot_message_payload = BotMessagePayload::VoteFinished {
+        r#type: VoteFinishedBotMsg,
+        payload: VoteFinishedBotMessagePayload {
+            vote_id: progress.vote_id,
+            subject: progress.subject.clone(),
+            passed,
+        },
+    };
+    send_bot_message(app_context, room_id, bot_message_payload).await;
+    if !passed {
+        return;
+    }
+    match progress.subject {
+        VoteSubject::Kick { target_public_id } => {
+            let Some(removed_user) = app_context.rooms.kick(room_id, &target_public_id).await
+            else {
+                // Left on their own before the vote was over.
+                return;
+            };
+            // The target may have become the host while the vote was running.
+            app_context.announce_kicked_user(removed_user).await;
+        }
+        VoteSubject::SkipLocation => {
+            if !app_context.rooms.skip_location(room_id).await {
+                // The round ended before the vote did.
+                return;
+            }
+            let ws_event_msg = ServerSentSocketMessage::LocationSkipped {
+                r#type: message_types::LocationSkipped,
+            };
+            app_context
+                .broadcast_event(room_id, &ws_event_msg, Audience::Everyone)
+                .await;
+        }
+    }
+}
+
+async fn send_bot_message(
+    app_context: &AppContext<HashMapRoomsStorage>,
+    room_id: &str,
+    bot_message_payload: BotMessagePayload,
+) {
+    if !app_context.rooms.exists(room_id).await {
+        return;
+    }
+    let bot_message = ChatMessage::from_bot(bot_message_payload.clone());
+    let bot_ws_msg = ServerSentSocketMessage::BotMessage {
+        r#type: message_types::BotMessage,
+        id: bot_message.id(),
+        payload: bot_message_payload,
+    };
+    app_context.rooms.add_message(room_id, bot_message).await;
+    app_context
+        .broadcast_event(room_id, &bot_ws_msg, Audience::Everyone)
+        .await;
+}
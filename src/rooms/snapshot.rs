@@ -0,0 +1,252 @@
+use crate::map::models::LatLng;
+use crate::rooms::consts::{MAX_PLAYERS_LIMIT, ROUNDS_PER_GAME};
+use crate::rooms::models::{
+    Ban, BannedUserInfo, ChatMessage, PublicListing, Room, RoomAccess, RoomStatus,
+    NEXT_CHAT_MESSAGE_ID,
+};
+use crate::users::models::{Permission, Presence, User, UserRole};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// Bumped whenever the snapshot format changes in a way that older snapshots can't be read with.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything needed to recreate a room, e.g. on another instance or to reproduce a bug report.
+/// Connections, the running vote and the event log are left out: the users have to reconnect to
+/// the recreated room anyway.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSnapshot {
+    pub version: u32,
+    pub users: Vec<UserSnapshot>,
+    pub chat_history: Vec<ChatMessage>,
+    pub status: RoomStatus,
+    pub rounds_left: u64,
+    pub bans: Vec<BannedUserInfo>,
+    pub access: AccessSnapshot,
+    pub public_listing: Option<PublicListing>,
+    pub max_players: usize,
+    pub is_locked: bool,
+    pub co_host_permissions: Vec<Permission>,
+}
+
+/// A user along with their private ID, which the users can't see about each other.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSnapshot {
+    pub public_id: String,
+    pub private_id: String,
+    pub name: String,
+    pub avatar_emoji: String,
+    pub score: u64,
+    pub role: UserRole,
+    pub last_guess: Option<LatLng>,
+    pub submitted_guess: bool,
+    pub last_round_score: Option<u64>,
+    pub is_muted: bool,
+    pub is_spectating: bool,
+}
+
+/// The password hash is left out of snapshots, so password protected rooms are restored as invite
+/// only until the host sets a new password.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessSnapshot {
+    Open,
+    InviteOnly,
+    PasswordProtected,
+}
+
+impl From<&RoomAccess> for AccessSnapshot {
+    fn from(access: &RoomAccess) -> Self {
+        match access {
+            RoomAccess::Open => Self::Open,
+            RoomAccess::InviteOnly => Self::InviteOnly,
+            RoomAccess::PasswordProtected { .. } => Self::PasswordProtected,
+        }
+    }
+}
+
+impl From<AccessSnapshot> for RoomAccess {
+    fn from(access: AccessSnapshot) -> Self {
+        match access {
+            AccessSnapshot::Open => Self::Open,
+            AccessSnapshot::InviteOnly | AccessSnapshot::PasswordProtected => Self::InviteOnly,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotRestoreError {
+    UnsupportedVersion,
+    /// Two users share a public or a private ID.
+    DuplicateUser,
+    /// A room with users in it needs exactly one host.
+    InvalidHost,
+    BanDurationTooLong,
+    /// More rounds are left than a game has.
+    InvalidRoundsLeft,
+    InvalidMaxPlayers,
+    /// The chat history must be sorted by message ID, without repeats.
+    UnorderedChatHistory,
+    /// A message ID so large that no new message could be numbered after it.
+    ChatMessageIdTooLarge,
+}
+
+impl From<&Room> for RoomSnapshot {
+    fn from(room: &Room) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            users: room.users.iter().map(UserSnapshot::from).collect(),
+            chat_history: room.chat_history.iter().cloned().collect(),
+            status: room.status,
+            rounds_left: room.rounds_left,
+            bans: room.active_bans(),
+            access: AccessSnapshot::from(&room.access),
+            public_listing: room.public_listing.clone(),
+            max_players: room.max_players,
+            is_locked: room.is_locked,
+            // Listed in a fixed order, so that snapshots of the same room are identical.
+            co_host_permissions: Permission::ALL
+                .into_iter()
+                .filter(|permission| room.co_host_permissions.contains(permission))
+                .collect(),
+        }
+    }
+}
+
+impl From<&User> for UserSnapshot {
+    fn from(user: &User) -> Self {
+        Self {
+            public_id: user.public_id.clone(),
+            private_id: user.private_id.clone(),
+            name: user.name.clone(),
+            avatar_emoji: user.avatar_emoji.clone(),
+            score: user.score,
+            role: user.role,
+            last_guess: user.last_guess,
+            submitted_guess: user.submitted_guess,
+            last_round_score: user.last_round_score,
+            is_muted: user.is_muted,
+            is_spectating: user.is_spectating,
+        }
+    }
+}
+
+impl TryFrom<RoomSnapshot> for Room {
+    type Error = SnapshotRestoreError;
+
+    /// The users are restored as away until they reconnect. A round that was in progress isn't
+    /// resumed, as its timer didn't come along; the room waits for the next one instead.
+    fn try_from(snapshot: RoomSnapshot) -> Result<Self, Self::Error> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotRestoreError::UnsupportedVersion);
+        }
+        let mut public_ids = HashSet::new();
+        let mut private_ids = HashSet::new();
+        for user in &snapshot.users {
+            if !public_ids.insert(&user.public_id) || !private_ids.insert(&user.private_id) {
+                return Err(SnapshotRestoreError::DuplicateUser);
+            }
+        }
+        let hosts_count = snapshot
+            .users
+            .iter()
+            .filter(|user| user.role == UserRole::Host)
+            .count();
+        if hosts_count != usize::from(!snapshot.users.is_empty()) {
+            return Err(SnapshotRestoreError::InvalidHost);
+        }
+        if snapshot.rounds_left > ROUNDS_PER_GAME {
+            return Err(SnapshotRestoreError::InvalidRoundsLeft);
+        }
+        if !(1..=MAX_PLAYERS_LIMIT).contains(&snapshot.max_players) {
+            return Err(SnapshotRestoreError::InvalidMaxPlayers);
+        }
+        if !snapshot
+            .chat_history
+            .windows(2)
+            .all(|pair| pair[0].id() < pair[1].id())
+        {
+            return Err(SnapshotRestoreError::UnorderedChatHistory);
+        }
+        let next_message_id = match snapshot.chat_history.last() {
+            Some(last_message) => Some(
+                last_message
+                    .id()
+                    .checked_add(1)
+                    .ok_or(SnapshotRestoreError::ChatMessageIdTooLarge)?,
+            ),
+            None => None,
+        };
+        let bans = snapshot
+            .bans
+            .into_iter()
+            .map(Ban::try_from)
+            .collect::<Result<_, _>>()?;
+        if let Some(next_message_id) = next_message_id {
+            // Keeps the IDs of new messages from clashing with the restored ones.
+            NEXT_CHAT_MESSAGE_ID.fetch_max(next_message_id, Ordering::Relaxed);
+        }
+        let status = match snapshot.status {
+            RoomStatus::Playing { current_location } => RoomStatus::Waiting {
+                previous_location: Some(current_location),
+            },
+            waiting @ RoomStatus::Waiting { .. } => waiting,
+        };
+        let mut room = Room::new(snapshot.public_listing);
+        room.users = snapshot.users.into_iter().map(User::from).collect();
+        room.chat_history = VecDeque::from(snapshot.chat_history);
+        room.status = status;
+        room.bans = bans;
+        room.rounds_left = snapshot.rounds_left;
+        room.access = RoomAccess::from(snapshot.access);
+        room.max_players = snapshot.max_players;
+        room.is_locked = snapshot.is_locked;
+        room.co_host_permissions = HashSet::from_iter(snapshot.co_host_permissions);
+        Ok(room)
+    }
+}
+
+impl From<UserSnapshot> for User {
+    fn from(snapshot: UserSnapshot) -> Self {
+        Self {
+            public_id: snapshot.public_id,
+            private_id: snapshot.private_id,
+            name: snapshot.name,
+            avatar_emoji: snapshot.avatar_emoji,
+            score: snapshot.score,
+            role: snapshot.role,
+            socket_id: None,
+            presence: Presence::Away,
+            away_since: Some(Instant::now()),
+            last_guess: snapshot.last_guess,
+            submitted_guess: snapshot.submitted_guess,
+            last_round_score: snapshot.last_round_score,
+            is_muted: snapshot.is_muted,
+            is_spectating: snapshot.is_spectating,
+        }
+    }
+}
+
+impl TryFrom<BannedUserInfo> for Ban {
+    type Error = SnapshotRestoreError;
+
+    fn try_from(info: BannedUserInfo) -> Result<Self, Self::Error> {
+        let expires_at = match info.expires_in_secs {
+            Some(secs) => Some(
+                Instant::now()
+                    .checked_add(Duration::from_secs(secs))
+                    .ok_or(SnapshotRestoreError::BanDurationTooLong)?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            public_id: info.public_id,
+            username: info.username,
+            expires_at,
+        })
+    }
+}
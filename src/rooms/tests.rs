@@ -0,0 +1,843 @@
+use crate::app_context::tests::{test_app_context, test_room, TestSocket};
+use crate::auth::tests::PASSCODE;
+use crate::http::requests::RoomCredentialsQueryParams;
+use crate::http::tests::test_server;
+use crate::rooms::consts::{
+    DEFAULT_MAX_PLAYERS, EVENT_LOG_CAPACITY, ROUNDS_PER_GAME, ROUND_DURATION_TICKS,
+};
+use crate::rooms::event_log::EventLog;
+use crate::rooms::handlers::ws::{on_new_message, spawn_heartbeat, write_queued_messages};
+use crate::rooms::message_types::{
+    self, ClientSentChatMessagePayload, ClientSentSocketMessage, ServerSentSocketMessage,
+};
+use crate::rooms::models::{PublicRoomInfo, RoomAccess, RoomStatusKind};
+use crate::rooms::services::chat::ChatWsHandler;
+use crate::rooms::services::http::RoomHttpHandler;
+use crate::rooms::services::responses::{
+    ChangeCoHostPermissionsResponse, CoHostPermissionsChangeError, CreateRoomResponse,
+    PublicRoomsResponse, QuickPlayResponse, RoomBansResponse, RoomBansResponseError,
+    RoomListingChangeError,
+};
+use crate::rooms::services::votes::VotesWsHandler;
+use crate::rooms::votes::{VoteStatus, VoteSubject};
+use crate::rooms::{max_missed_pongs, presence_grace_period};
+use crate::storage::consts::DEFAULT_MESSAGES_PAGE_SIZE;
+use crate::storage::interface::{
+    RoomAccessRepo, RoomConnectionHandler, RoomGameFlowHandler, RoomInfoRepo, RoomRepo,
+    RoomVotesRepo, UserPermissionsRepo,
+};
+use crate::storage::sockets::Audience;
+use crate::users::models::UserRole;
+use axum::extract::ws::Message;
+use axum::http::StatusCode;
+use serde_json::json;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_public_room_is_listed() {
+    let server = test_server();
+
+    let create_response = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .json(&json!({ "public": true, "displayName": "Europe only" }))
+        .await;
+    let room_id = create_response
+        .json::<CreateRoomResponse>()
+        .room_id
+        .expect("Room wasn't created.");
+    server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .await
+        .assert_status_ok();
+
+    let response = server.get("/rooms").add_header("Passcode", PASSCODE).await;
+
+    response.assert_status_ok();
+    response.assert_json(&PublicRoomsResponse {
+        rooms: vec![PublicRoomInfo {
+            room_id,
+            display_name: String::from("Europe only"),
+            players_count: 0,
+            status: RoomStatusKind::Waiting,
+            password_protected: false,
+            rounds_per_game: ROUNDS_PER_GAME,
+            max_players: DEFAULT_MAX_PLAYERS,
+        }],
+    });
+}
+
+#[tokio::test]
+async fn test_public_room_requires_display_name() {
+    let server = test_server();
+
+    let response = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .json(&json!({ "public": true, "displayName": "  " }))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&CreateRoomResponse {
+        error: true,
+        error_code: Some(RoomListingChangeError::DisplayNameRequired),
+        room_id: None,
+    });
+}
+
+#[tokio::test]
+async fn test_malformed_room_creation_body_is_refused() {
+    let server = test_server();
+
+    let response = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .json(&json!({ "public": "yes" }))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    let response = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .json(&json!({ "pubilc": true }))
+        .await;
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_quick_play_reuses_public_room() {
+    let server = test_server();
+
+    let first_response = server
+        .post("/rooms/quick-play")
+        .add_header("Passcode", PASSCODE)
+        .await;
+    let second_response = server
+        .post("/rooms/quick-play")
+        .add_header("Passcode", PASSCODE)
+        .await;
+
+    first_response.assert_status_ok();
+    let room_id = first_response.json::<QuickPlayResponse>().room_id;
+    second_response.assert_json(&QuickPlayResponse { room_id });
+}
+
+#[tokio::test]
+async fn test_outsider_cannot_change_co_host_permissions() {
+    let server = test_server();
+    let create_response = server.post("/rooms").add_header("Passcode", PASSCODE).await;
+    let room_id = create_response
+        .json::<CreateRoomResponse>()
+        .room_id
+        .expect("Room wasn't created.");
+
+    let response = server
+        .post(&format!("/rooms/{room_id}/co-host-permissions"))
+        .add_header("Passcode", PASSCODE)
+        .json(&json!({ "permissions": ["mute", "ban"] }))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&ChangeCoHostPermissionsResponse {
+        error: true,
+        error_code: Some(CoHostPermissionsChangeError::YouAreNotTheHost),
+    });
+}
+
+#[tokio::test]
+async fn test_outsider_cannot_see_ban_list() {
+    let server = test_server();
+    let create_response = server.post("/rooms").add_header("Passcode", PASSCODE).await;
+    let room_id = create_response
+        .json::<CreateRoomResponse>()
+        .room_id
+        .expect("Room wasn't created.");
+
+    let response = server
+        .get(&format!("/rooms/{room_id}/bans"))
+        .add_header("Passcode", PASSCODE)
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&RoomBansResponse {
+        error: true,
+        error_code: Some(RoomBansResponseError::YouAreNotTheHost),
+        bans: None,
+    });
+}
+
+#[tokio::test]
+async fn test_messages_after_latest_id_are_empty() {
+    let server = test_server();
+    let room_id = server
+        .post("/rooms")
+        .add_header("Passcode", PASSCODE)
+        .await
+        .json::<CreateRoomResponse>()
+        .room_id
+        .expect("Room wasn't created.");
+
+    let response = server
+        .get(&format!("/rooms/{room_id}/messages"))
+        .add_query_params(json!({ "after": usize::MAX, "limit": 10 }))
+        .add_header("Passcode", PASSCODE)
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({ "error": false, "messages": [], "hasMore": false }));
+}
+
+#[test]
+fn test_event_log_replays_missed_events() {
+    let mut event_log = EventLog::default();
+    event_log.record(r#"{"type":"Tick","payload":3}"#, vec![]);
+    event_log.record(r#"{"type":"ChatMessage"}"#, vec![String::from("author")]);
+    event_log.record(r#"{"type":"Tick","payload":2}"#, vec![]);
+
+    let missed_by_author = event_log.events_since(1, "author");
+    let missed_by_player = event_log.events_since(1, "player");
+
+    assert_eq!(
+        missed_by_author,
+        Some(vec![String::from(r#"{"seq":3,"type":"Tick","payload":2}"#)])
+    );
+    assert_eq!(missed_by_player.map(|events| events.len()), Some(2));
+}
+
+#[test]
+fn test_event_log_requires_resync_after_eviction() {
+    let mut event_log = EventLog::default();
+    for _ in 0..=EVENT_LOG_CAPACITY {
+        event_log.record(r#"{"type":"Tick","payload":0}"#, vec![]);
+    }
+
+    assert_eq!(event_log.events_since(0, "player"), None);
+    assert_eq!(
+        event_log
+            .events_since(1, "player")
+            .map(|events| events.len()),
+        Some(EVENT_LOG_CAPACITY)
+    );
+    assert_eq!(event_log.events_since(u64::MAX, "player"), None);
+}
+
+fn chat_message(content: &str) -> ClientSentChatMessagePayload {
+    ClientSentChatMessagePayload {
+        from: String::from("spammer"),
+        content: content.to_string(),
+        attachment_ids: vec![],
+        reply_to: None,
+    }
+}
+
+#[tokio::test]
+async fn test_repeated_spam_gets_author_muted() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let spammer = TestSocket::join(&app_context, &room_id, "spammer").await;
+    let chat = ChatWsHandler::new(
+        app_context.clone(),
+        &spammer.request_context,
+        spammer.socket_id,
+    );
+
+    for _ in 0..6 {
+        chat.post(chat_message("Buy gold!")).await;
+    }
+
+    let mut rejection = spammer.expect_message("ChatMessageRejected").await;
+    while rejection["payload"]["muted"] == false {
+        rejection = spammer.expect_message("ChatMessageRejected").await;
+    }
+    spammer.expect_message("UserMuted").await;
+    assert!(
+        app_context
+            .rooms
+            .is_muted(&room_id, &spammer.request_context.public_id)
+            .await
+    );
+}
+
+#[tokio::test]
+async fn test_spam_from_socket_that_has_not_joined_is_dropped() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let member = TestSocket::join(&app_context, &room_id, "member").await;
+    let outsider = TestSocket::open(&app_context, &room_id, "outsider").await;
+    let chat = ChatWsHandler::new(
+        app_context.clone(),
+        &outsider.request_context,
+        outsider.socket_id,
+    );
+
+    for _ in 0..6 {
+        chat.post(chat_message("Buy gold!")).await;
+    }
+
+    assert!(outsider.next_message().await.is_none());
+    assert!(member.next_message().await.is_none());
+    let (messages, _has_more) = app_context
+        .rooms
+        .messages(&room_id, None, None, DEFAULT_MESSAGES_PAGE_SIZE)
+        .await
+        .unwrap();
+    assert!(messages.is_empty());
+}
+
+#[tokio::test]
+async fn test_closed_rooms_are_reported_as_not_found() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    app_context.rooms.remove(&room_id).await;
+
+    assert!(app_context.rooms.users(&room_id).await.is_none());
+    assert!(app_context.rooms.finish_game(&room_id).await.is_none());
+    assert!(app_context
+        .rooms
+        .set_role(&room_id, &host.request_context.public_id, UserRole::Player)
+        .await
+        .is_none());
+    assert!(!app_context.rooms.set_locked(&room_id, true).await);
+    ChatWsHandler::new(app_context.clone(), &host.request_context, host.socket_id)
+        .toggle_reaction(1, String::from("👍"))
+        .await;
+    let response = RoomHttpHandler::new(app_context.clone(), &host.request_context)
+        .lock()
+        .await;
+    assert_eq!(
+        serde_json::to_value(response).unwrap(),
+        json!({ "error": true, "errorCode": "roomNotFound" })
+    );
+}
+
+#[tokio::test]
+async fn test_kicked_users_can_no_longer_chat_or_react() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    let player = TestSocket::join(&app_context, &room_id, "player").await;
+    let player_chat = ChatWsHandler::new(
+        app_context.clone(),
+        &player.request_context,
+        player.socket_id,
+    );
+    player_chat.post(chat_message("Hi")).await;
+    let message_id = host.expect_message("ChatMessage").await["payload"]["id"]
+        .as_u64()
+        .unwrap() as usize;
+    app_context
+        .rooms
+        .kick(&room_id, &player.request_context.public_id)
+        .await
+        .expect("Player wasn't kicked.");
+
+    player_chat.post(chat_message("Still here")).await;
+    player_chat
+        .toggle_reaction(message_id, String::from("👍"))
+        .await;
+
+    assert!(host.next_message().await.is_none());
+}
+
+#[tokio::test]
+async fn test_socket_is_dropped_once_the_close_frame_is_written() {
+    let app_context = test_app_context();
+    let (_socket_id, queue) = app_context.sockets.add().await;
+    queue.push_control(Message::Text(String::from("removedFromRoom")));
+    queue.push_control(Message::Close(None));
+    let written = Arc::new(AtomicU32::new(0));
+    let sink_written = written.clone();
+    let sink = Box::pin(futures_util::sink::unfold((), move |_, _: Message| {
+        sink_written.fetch_add(1, Ordering::Relaxed);
+        async { Ok::<(), &str>(()) }
+    }));
+
+    write_queued_messages(queue.clone(), sink).await;
+
+    assert_eq!(written.load(Ordering::Relaxed), 2);
+    assert!(queue.pop().await.is_none());
+}
+
+#[tokio::test]
+async fn test_password_protected_room_is_hidden_from_outsiders() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let member = TestSocket::join(&app_context, &room_id, "member").await;
+    let outsider = TestSocket::open(&app_context, &room_id, "outsider").await;
+    app_context
+        .rooms
+        .set_access(&room_id, RoomAccess::password_protected("hunter2"))
+        .await;
+    let wrong_password = RoomCredentialsQueryParams {
+        password: Some(String::from("hunter3")),
+        invite: None,
+    };
+    let right_password = RoomCredentialsQueryParams {
+        password: Some(String::from("hunter2")),
+        invite: None,
+    };
+
+    let outsider_handler = RoomHttpHandler::new(app_context.clone(), &outsider.request_context);
+    let member_handler = RoomHttpHandler::new(app_context.clone(), &member.request_context);
+
+    assert_eq!(
+        serde_json::to_value(outsider_handler.users(&wrong_password).await).unwrap(),
+        json!({ "error": true, "errorCode": "notAllowed" })
+    );
+    assert_eq!(
+        serde_json::to_value(
+            outsider_handler
+                .messages(&RoomCredentialsQueryParams::default(), None, None, None)
+                .await
+        )
+        .unwrap(),
+        json!({ "error": true, "errorCode": "notAllowed" })
+    );
+    assert!(!outsider_handler.users(&right_password).await.error);
+    assert!(
+        !member_handler
+            .messages(&RoomCredentialsQueryParams::default(), None, None, None)
+            .await
+            .error
+    );
+}
+
+fn kick_vote(target: &TestSocket) -> VoteSubject {
+    VoteSubject::Kick {
+        target_public_id: target.request_context.public_id.clone(),
+    }
+}
+
+#[tokio::test]
+async fn test_kick_vote_needs_majority_of_the_room() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    let first = TestSocket::join(&app_context, &room_id, "first").await;
+    let second = TestSocket::join(&app_context, &room_id, "second").await;
+    let target = TestSocket::join(&app_context, &room_id, "target").await;
+
+    let started = app_context
+        .rooms
+        .start_vote(
+            &room_id,
+            &first.request_context.public_id,
+            kick_vote(&target),
+            50,
+        )
+        .await
+        .unwrap();
+    let second_vote = app_context
+        .rooms
+        .cast_vote(&room_id, &second.request_context.public_id, true)
+        .await
+        .unwrap();
+    let host_vote = app_context
+        .rooms
+        .cast_vote(&room_id, &host.request_context.public_id, true)
+        .await
+        .unwrap();
+
+    assert_eq!(started.votes_needed, 3);
+    assert_eq!(started.status, VoteStatus::Pending);
+    assert_eq!(second_vote.status, VoteStatus::Pending);
+    assert_eq!(host_vote.votes_for, 3);
+    assert_eq!(host_vote.status, VoteStatus::Passed);
+}
+
+#[tokio::test]
+async fn test_repeated_ballots_replace_earlier_ones() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let _host = TestSocket::join(&app_context, &room_id, "host").await;
+    let initiator = TestSocket::join(&app_context, &room_id, "initiator").await;
+    let target = TestSocket::join(&app_context, &room_id, "target").await;
+    app_context
+        .rooms
+        .start_vote(
+            &room_id,
+            &initiator.request_context.public_id,
+            kick_vote(&target),
+            50,
+        )
+        .await
+        .unwrap();
+
+    let repeated_vote = app_context
+        .rooms
+        .cast_vote(&room_id, &initiator.request_context.public_id, true)
+        .await
+        .unwrap();
+    let target_vote = app_context
+        .rooms
+        .cast_vote(&room_id, &target.request_context.public_id, false)
+        .await;
+    let changed_vote = app_context
+        .rooms
+        .cast_vote(&room_id, &initiator.request_context.public_id, false)
+        .await
+        .unwrap();
+
+    assert_eq!(repeated_vote.votes_for, 1);
+    assert_eq!(repeated_vote.status, VoteStatus::Pending);
+    assert!(target_vote.is_none());
+    assert_eq!(changed_vote.votes_for, 0);
+    assert_eq!(changed_vote.votes_against, 1);
+}
+
+#[tokio::test]
+async fn test_expired_vote_fails_and_takes_no_more_ballots() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    let initiator = TestSocket::join(&app_context, &room_id, "initiator").await;
+    let target = TestSocket::join(&app_context, &room_id, "target").await;
+    let started = app_context
+        .rooms
+        .start_vote(
+            &room_id,
+            &initiator.request_context.public_id,
+            kick_vote(&target),
+            50,
+        )
+        .await
+        .unwrap();
+
+    let expired = app_context
+        .rooms
+        .expire_vote(&room_id, started.vote_id)
+        .await
+        .unwrap();
+
+    assert_eq!(expired.status, VoteStatus::Failed);
+    assert!(app_context
+        .rooms
+        .expire_vote(&room_id, started.vote_id)
+        .await
+        .is_none());
+    assert!(app_context
+        .rooms
+        .cast_vote(&room_id, &host.request_context.public_id, true)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_passed_kick_vote_hands_host_role_over() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    let initiator = TestSocket::join(&app_context, &room_id, "initiator").await;
+    let target = TestSocket::join(&app_context, &room_id, "target").await;
+    VotesWsHandler::new(app_context.clone(), &initiator.request_context)
+        .start(kick_vote(&target))
+        .await;
+    app_context
+        .rooms
+        .transfer_host(&room_id, &target.request_context.public_id)
+        .await;
+
+    VotesWsHandler::new(app_context.clone(), &host.request_context)
+        .cast(true)
+        .await;
+
+    initiator.expect_message("UserKicked").await;
+    let host_changed = initiator.expect_message("HostChanged").await;
+    let new_host_public_id = host_changed["payload"]["publicId"].as_str().unwrap();
+    assert_ne!(new_host_public_id, target.request_context.public_id);
+    assert!(
+        app_context
+            .rooms
+            .user_is_host(&room_id, new_host_public_id)
+            .await
+    );
+}
+
+#[test]
+fn test_connection_upkeep_and_chat_skip_the_websocket_rate_limit() {
+    let parse = |raw: serde_json::Value| -> ClientSentSocketMessage {
+        serde_json::from_value(raw).unwrap()
+    };
+    assert!(!parse(json!({ "type": "Ping" })).is_rate_limited());
+    assert!(!parse(json!({ "type": "ChatMessage", "payload": { "from": "alice", "content": "hi", "attachmentIds": [] } })).is_rate_limited());
+    assert!(parse(json!({ "type": "RoundStarted" })).is_rate_limited());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_skipping_the_location_restarts_the_round_timer() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    app_context
+        .rooms
+        .start_game(&room_id, app_context.sockets.clone())
+        .await;
+    tokio::time::sleep(Duration::from_secs(60)).await;
+
+    assert!(app_context.rooms.skip_location(&room_id).await);
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    assert_eq!(
+        RoomStatusKind::from(app_context.rooms.status(&room_id).await.unwrap()),
+        RoomStatusKind::Playing
+    );
+    tokio::time::sleep(Duration::from_secs(ROUND_DURATION_TICKS as u64)).await;
+    assert_eq!(
+        RoomStatusKind::from(app_context.rooms.status(&room_id).await.unwrap()),
+        RoomStatusKind::Waiting
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_reconnecting_after_the_grace_period_is_refused() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let alice = TestSocket::join(&app_context, &room_id, "alice").await;
+    let bob = TestSocket::join(&app_context, &room_id, "bob").await;
+    app_context
+        .rooms
+        .disconnect_user(&room_id, bob.socket_id)
+        .await;
+    app_context
+        .rooms
+        .on_user_disconnected(
+            &room_id,
+            &bob.request_context.private_id,
+            app_context.sockets.clone(),
+        )
+        .await;
+    tokio::time::sleep(presence_grace_period() + Duration::from_secs(1)).await;
+
+    let bob = TestSocket::open(&app_context, &room_id, "bob").await;
+    let reconnection = json!({
+        "type": "UserReConnected",
+        "payload": { "username": "bob", "avatarEmoji": "🦊" },
+    });
+    on_new_message(
+        app_context.clone(),
+        bob.request_context.clone(),
+        Message::Text(reconnection.to_string()),
+        bob.socket_id,
+        String::from("203.0.113.7"),
+    )
+    .await;
+
+    let removal = bob.expect_message("RemovedFromRoom").await;
+    assert_eq!(removal["payload"]["reason"], "gracePeriodOver");
+    assert_eq!(app_context.rooms.users(&room_id).await.unwrap().len(), 1);
+    let carol = TestSocket::join(&app_context, &room_id, "carol").await;
+    ChatWsHandler::new(app_context.clone(), &alice.request_context, alice.socket_id)
+        .post(ClientSentChatMessagePayload {
+            from: String::from("alice"),
+            content: String::from("Anyone here?"),
+            attachment_ids: vec![],
+            reply_to: None,
+        })
+        .await;
+    carol.expect_message("ChatMessage").await;
+    assert!(bob.next_message().await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_events_arrive_in_seq_order() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let alice = TestSocket::join(&app_context, &room_id, "alice").await;
+    while alice.next_message().await.is_some() {}
+
+    let broadcasts = (0..50).map(|_| {
+        let app_context = app_context.clone();
+        let room_id = room_id.clone();
+        tokio::spawn(async move {
+            let ws_event = ServerSentSocketMessage::RoomLocked {
+                r#type: message_types::RoomLocked,
+            };
+            app_context
+                .broadcast_event(&room_id, &ws_event, Audience::Everyone)
+                .await;
+        })
+    });
+    for broadcast in broadcasts.collect::<Vec<_>>() {
+        broadcast.await.unwrap();
+    }
+
+    let mut seqs = vec![];
+    while let Some(message) = alice.next_message().await {
+        seqs.push(message["seq"].as_u64().unwrap());
+    }
+    assert_eq!(seqs.len(), 50);
+    assert!(seqs.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[tokio::test]
+async fn test_only_the_author_can_edit_a_message() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let alice = TestSocket::join(&app_context, &room_id, "alice").await;
+    let bob = TestSocket::join(&app_context, &room_id, "bob").await;
+    let alice_chat =
+        ChatWsHandler::new(app_context.clone(), &alice.request_context, alice.socket_id);
+    let bob_chat = ChatWsHandler::new(app_context.clone(), &bob.request_context, bob.socket_id);
+    alice_chat.post(chat_message("Helo")).await;
+    let message_id = bob.expect_message("ChatMessage").await["payload"]["id"]
+        .as_u64()
+        .unwrap() as usize;
+
+    bob_chat.edit(message_id, String::from("Bye")).await;
+    assert!(alice.next_message().await.is_none());
+    alice_chat.edit(message_id, String::from("Hello")).await;
+
+    let edited = bob.expect_message("MessageEdited").await;
+    assert_eq!(edited["payload"]["content"], "Hello");
+    let (history, _) = app_context
+        .rooms
+        .messages(&room_id, None, None, 10)
+        .await
+        .unwrap();
+    let stored = serde_json::to_value(&history[0]).unwrap();
+    assert_eq!(stored["content"], "Hello");
+    assert_eq!(stored["edited"], true);
+}
+
+#[tokio::test]
+async fn test_players_delete_only_their_own_messages_and_hosts_any() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let alice = TestSocket::join(&app_context, &room_id, "alice").await;
+    let bob = TestSocket::join(&app_context, &room_id, "bob").await;
+    let alice_chat =
+        ChatWsHandler::new(app_context.clone(), &alice.request_context, alice.socket_id);
+    let bob_chat = ChatWsHandler::new(app_context.clone(), &bob.request_context, bob.socket_id);
+    alice_chat.post(chat_message("Hosting today")).await;
+    let alice_message_id = bob.expect_message("ChatMessage").await["payload"]["id"]
+        .as_u64()
+        .unwrap() as usize;
+    bob_chat.post(chat_message("Something rude")).await;
+    let bob_message_id = alice.expect_message("ChatMessage").await["payload"]["id"]
+        .as_u64()
+        .unwrap() as usize;
+
+    bob_chat.delete(alice_message_id).await;
+    assert!(alice.next_message().await.is_none());
+    alice_chat.delete(bob_message_id).await;
+
+    let deleted = bob.expect_message("MessageDeleted").await;
+    assert_eq!(deleted["payload"]["id"], bob_message_id);
+    let (history, _) = app_context
+        .rooms
+        .messages(&room_id, None, None, 10)
+        .await
+        .unwrap();
+    let ids: Vec<usize> = history.iter().map(|message| message.id()).collect();
+    assert_eq!(ids, vec![alice_message_id]);
+}
+
+#[tokio::test]
+async fn test_reacting_twice_takes_the_reaction_back() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let alice = TestSocket::join(&app_context, &room_id, "alice").await;
+    let bob = TestSocket::join(&app_context, &room_id, "bob").await;
+    ChatWsHandler::new(app_context.clone(), &alice.request_context, alice.socket_id)
+        .post(chat_message("Guess where I am"))
+        .await;
+    let message_id = bob.expect_message("ChatMessage").await["payload"]["id"]
+        .as_u64()
+        .unwrap() as usize;
+    let bob_chat = ChatWsHandler::new(app_context.clone(), &bob.request_context, bob.socket_id);
+
+    bob_chat
+        .toggle_reaction(message_id, String::from("👀"))
+        .await;
+    let added = alice.expect_message("ReactionToggled").await;
+    assert_eq!(added["payload"]["added"], true);
+    assert_eq!(added["payload"]["count"], 1);
+    bob_chat
+        .toggle_reaction(message_id, String::from("👀"))
+        .await;
+    let removed = alice.expect_message("ReactionToggled").await;
+    assert_eq!(removed["payload"]["added"], false);
+    assert_eq!(removed["payload"]["count"], 0);
+
+    bob_chat
+        .toggle_reaction(message_id, String::from("not an emoji"))
+        .await;
+    assert!(alice.next_message().await.is_none());
+}
+
+#[tokio::test]
+async fn test_replies_to_unknown_messages_are_posted_without_the_reference() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let alice = TestSocket::join(&app_context, &room_id, "alice").await;
+    let bob = TestSocket::join(&app_context, &room_id, "bob").await;
+    let alice_chat =
+        ChatWsHandler::new(app_context.clone(), &alice.request_context, alice.socket_id);
+    alice_chat.post(chat_message("Is it Peru?")).await;
+    let parent_id = bob.expect_message("ChatMessage").await["payload"]["id"]
+        .as_u64()
+        .unwrap() as usize;
+
+    alice_chat
+        .post(ClientSentChatMessagePayload {
+            reply_to: Some(parent_id),
+            ..chat_message("No, Chile")
+        })
+        .await;
+    let reply = bob.expect_message("ChatMessage").await;
+    assert_eq!(reply["payload"]["replyTo"], parent_id);
+    alice_chat
+        .post(ClientSentChatMessagePayload {
+            reply_to: Some(usize::MAX),
+            ..chat_message("Replying to nothing")
+        })
+        .await;
+    let orphan = bob.expect_message("ChatMessage").await;
+    assert!(orphan["payload"]["replyTo"].is_null());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_sockets_that_stop_answering_pings_are_dropped() {
+    let app_context = test_app_context();
+    let (socket_id, queue) = app_context.sockets.add().await;
+    let missed_pongs = Arc::new(AtomicU32::new(0));
+    let heartbeat = spawn_heartbeat(
+        Duration::from_secs(10),
+        queue.clone(),
+        missed_pongs.clone(),
+        socket_id,
+    );
+
+    for _ in 0..5 {
+        assert!(matches!(queue.pop().await, Some(Message::Ping(_))));
+        // What the socket's reader does once the pong arrives.
+        missed_pongs.store(0, Ordering::Relaxed);
+    }
+    for _ in 0..max_missed_pongs() {
+        assert!(matches!(queue.pop().await, Some(Message::Ping(_))));
+    }
+
+    assert!(queue.pop().await.is_none());
+    heartbeat.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_socket_is_dropped_on_the_first_failed_write() {
+    let app_context = test_app_context();
+    let (_socket_id, queue) = app_context.sockets.add().await;
+    queue.push_control(Message::Text(String::from("first")));
+    queue.push_control(Message::Text(String::from("second")));
+    let attempts = Arc::new(AtomicU32::new(0));
+    let sink_attempts = attempts.clone();
+    let broken_sink = Box::pin(futures_util::sink::unfold((), move |_, _: Message| {
+        sink_attempts.fetch_add(1, Ordering::Relaxed);
+        async { Err::<(), _>("connection reset") }
+    }));
+
+    write_queued_messages(queue.clone(), broken_sink).await;
+
+    assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    assert!(queue.pop().await.is_none());
+}
@@ -0,0 +1,122 @@
+use crate::cli::Args;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub static NEXT_VOTE_ID: AtomicUsize = AtomicUsize::new(1);
+
+static VOTE_SETTINGS: OnceLock<VoteSettings> = OnceLock::new();
+
+#[derive(Copy, Clone, Debug)]
+pub struct VoteSettings {
+    /// A vote passes once strictly more than this share of the room has voted in favor.
+    pub majority_percent: u64,
+    pub timeout: Duration,
+}
+
+pub fn init(args: &Args) {
+    VOTE_SETTINGS.get_or_init(|| VoteSettings {
+        majority_percent: args.vote_majority_percent.min(99),
+        timeout: Duration::from_secs(args.vote_timeout_secs),
+    });
+}
+
+pub fn settings() -> VoteSettings {
+    *VOTE_SETTINGS
+        .get()
+        .expect("Somehow vote settings are used before `init`.")
+}
+
+/// What the players are voting for.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum VoteSubject {
+    #[serde(rename_all = "camelCase")]
+    Kick {
+        target_public_id: String,
+    },
+    SkipLocation,
+}
+
+#[derive(Clone, Debug)]
+pub struct Vote {
+    pub id: usize,
+    pub subject: VoteSubject,
+    /// Public IDs of the users that have voted, mapped to whether they are in favor.
+    pub ballots: HashMap<String, bool>,
+    pub votes_needed: usize,
+    /// How many users can vote; fixed when the vote starts.
+    pub electorate_size: usize,
+}
+
+impl Vote {
+    pub fn new(
+        subject: VoteSubject,
+        initiator_public_id: &str,
+        room_size: usize,
+        electorate_size: usize,
+        majority_percent: u64,
+    ) -> Self {
+        // The majority is taken of the whole room, so that e.g. one of two players can't kick the
+        // other one on their own.
+        let votes_needed = (room_size * majority_percent as usize / 100 + 1).min(room_size);
+        Self {
+            id: NEXT_VOTE_ID.fetch_add(1, Ordering::Relaxed),
+            subject,
+            ballots: HashMap::from([(initiator_public_id.to_string(), true)]),
+            votes_needed,
+            electorate_size,
+        }
+    }
+
+    pub fn progress(&self) -> VoteProgress {
+        let votes_for = self.ballots.values().filter(|in_favor| **in_favor).count();
+        let votes_against = self.ballots.len() - votes_for;
+        let votes_left = self.electorate_size.saturating_sub(self.ballots.len());
+        let status = if votes_for >= self.votes_needed {
+            VoteStatus::Passed
+        } else if votes_for + votes_left < self.votes_needed {
+            VoteStatus::Failed
+        } else {
+            VoteStatus::Pending
+        };
+        VoteProgress {
+            vote_id: self.id,
+            subject: self.subject.clone(),
+            votes_for,
+            votes_against,
+            votes_needed: self.votes_needed,
+            status,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VoteProgress {
+    pub vote_id: usize,
+    pub subject: VoteSubject,
+    pub votes_for: usize,
+    pub votes_against: usize,
+    pub votes_needed: usize,
+    pub status: VoteStatus,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VoteStatus {
+    Pending,
+    Passed,
+    Failed,
+}
+
+#[derive(Debug)]
+pub enum VoteStartError {
+    RoomNotFound,
+    VoteAlreadyInProgress,
+    NotInTheRoom,
+    TargetNotFound,
+    TargetIsTheHost,
+    CannotVoteAgainstYourself,
+    NotPlaying,
+}
@@ -0,0 +1,99 @@
+use crate::app_context::AppContext;
+use crate::cli::Args;
+use crate::rooms::message_types::{self, ServerSentSocketMessage, ServerShuttingDownPayload};
+use crate::storage::interface::IRoomStorage;
+use crate::storage::sockets::Audience;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::signal;
+use tokio::time::Instant;
+
+#[cfg(test)]
+pub mod tests;
+
+static SHUTDOWN_SETTINGS: OnceLock<ShutdownSettings> = OnceLock::new();
+
+#[derive(Copy, Clone, Debug)]
+pub struct ShutdownSettings {
+    pub deadline: Duration,
+    pub reconnect_after: Duration,
+}
+
+pub fn init(args: &Args) {
+    SHUTDOWN_SETTINGS.get_or_init(|| ShutdownSettings {
+        deadline: Duration::from_secs(args.shutdown_deadline_secs),
+        reconnect_after: Duration::from_secs(args.shutdown_reconnect_after_secs),
+    });
+}
+
+pub fn settings() -> ShutdownSettings {
+    *SHUTDOWN_SETTINGS
+        .get()
+        .expect("Somehow shutdown settings are used before `init`.")
+}
+
+/// Resolves on Ctrl+C or, on Unix, on SIGTERM.
+pub async fn signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C.");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM.")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Stops new rooms, sockets and rounds from being started, warns every room that the server is
+/// going away and waits for the rounds in progress to finish, for up to the deadline. Then closes
+/// the remaining sockets.
+pub async fn drain<RS: IRoomStorage>(app_context: AppContext<RS>) {
+    let settings = settings();
+    app_context.shutdown.start_draining();
+    tracing::info!("Shutting down, draining rooms...");
+
+    let ws_event = ServerSentSocketMessage::ServerShuttingDown {
+        r#type: message_types::ServerShuttingDown,
+        payload: ServerShuttingDownPayload {
+            deadline_secs: settings.deadline.as_secs(),
+            reconnect_after_secs: settings.reconnect_after.as_secs(),
+        },
+    };
+    for room_id in app_context.rooms.ids().await {
+        app_context
+            .broadcast_event(&room_id, &ws_event, Audience::Everyone)
+            .await;
+    }
+
+    let deadline = Instant::now() + settings.deadline;
+    loop {
+        let playing_count = app_context.rooms.playing_count().await;
+        if playing_count == 0 {
+            break;
+        }
+        if Instant::now() >= deadline {
+            tracing::info!("Shutdown deadline reached with {playing_count} rounds in progress.");
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    // Rooms only live in memory, so there is no state to flush before exiting.
+    for socket_id in app_context.sockets.ids().await {
+        app_context.sockets.close(socket_id).await;
+    }
+    // Gives the writer tasks a moment to send the close frames.
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    tracing::info!("Finished draining rooms.");
+}
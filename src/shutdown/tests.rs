@@ -0,0 +1,23 @@
+use crate::cli::tests::fake_args;
+use crate::http::router;
+use crate::{app_context, auth, bans, rooms, shutdown};
+use axum::http::{header, StatusCode};
+use axum_test::TestServer;
+
+#[tokio::test]
+async fn test_new_rooms_are_refused_while_draining() {
+    let args = fake_args();
+    auth::init(&args);
+    bans::init(&args);
+    rooms::init(&args);
+    shutdown::init(&args);
+    let app_context = app_context::init(&args);
+    let server = TestServer::new(router::new(&args, app_context.clone()))
+        .expect("Failed to run test server.");
+    app_context.shutdown.start_draining();
+
+    let response = server.post("/rooms").await;
+
+    response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.header(header::RETRY_AFTER), "10");
+}
@@ -0,0 +1,114 @@
+use crate::storage::consts::ROOM_CHANNEL_CAPACITY;
+use crate::storage::socket_queue::CoalescingKey;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+/// Carries room broadcasts between server instances, so that sockets of the same room can be
+/// connected to different instances. Each instance subscribes to the rooms its sockets are in
+/// and forwards what it receives to them.
+///
+/// Only the broadcasts go through the broker: the room state still lives in the memory of a
+/// single instance, so requests for a room are routed to the instance that holds it (see
+/// `cluster`).
+pub trait RoomBroker: Send + Sync {
+    fn publish(&self, room_id: &str, broadcast: RoomBroadcast);
+    /// The room's broadcasts published from now on, until the room is closed.
+    fn subscribe(&self, room_id: &str) -> BoxStream<'static, RoomBroadcast>;
+    /// Stops relaying the room's broadcasts; the subscribers still get what has been published
+    /// before.
+    fn close_room(&self, room_id: &str);
+}
+
+/// Identifies the server instance a broadcast comes from. Socket IDs are only unique within an
+/// instance, so they mean nothing to the other ones.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceId(Uuid);
+
+impl InstanceId {
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+/// Who in the room gets a broadcast.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Audience {
+    Everyone,
+    /// Usually the socket whose message caused the broadcast.
+    EveryoneExcept(usize),
+}
+
+impl Audience {
+    pub fn includes(&self, socket_id: usize) -> bool {
+        match self {
+            Audience::Everyone => true,
+            Audience::EveryoneExcept(excluded_socket_id) => *excluded_socket_id != socket_id,
+        }
+    }
+}
+
+/// A message serialized once and shared by all of the room's subscribers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomBroadcast {
+    pub raw_msg: Arc<str>,
+    pub origin: InstanceId,
+    pub audience: Audience,
+    pub coalescing_key: Option<CoalescingKey>,
+}
+
+impl RoomBroadcast {
+    pub fn is_for(&self, instance_id: InstanceId, socket_id: usize) -> bool {
+        self.origin != instance_id || self.audience.includes(socket_id)
+    }
+}
+
+/// Keeps the broadcasts within the process: used when the server runs as a single instance, and
+/// in tests.
+#[derive(Default)]
+pub struct LocalRoomBroker {
+    /// Every room has its own channel, so broadcasting costs the same no matter how many other
+    /// rooms there are.
+    room_channels: Mutex<HashMap<String, broadcast::Sender<RoomBroadcast>>>,
+}
+
+impl RoomBroker for LocalRoomBroker {
+    fn publish(&self, room_id: &str, broadcast: RoomBroadcast) {
+        if let Some(channel) = self.room_channels.lock().unwrap().get(room_id) {
+            // Fails only if nobody is subscribed, in which case there is nobody to tell.
+            let _ = channel.send(broadcast);
+        }
+    }
+
+    fn subscribe(&self, room_id: &str) -> BoxStream<'static, RoomBroadcast> {
+        let rx = self
+            .room_channels
+            .lock()
+            .unwrap()
+            .entry(room_id.to_string())
+            .or_insert_with(|| broadcast::channel(ROOM_CHANNEL_CAPACITY).0)
+            .subscribe();
+        stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(broadcast) => return Some((broadcast, rx)),
+                    Err(RecvError::Lagged(skipped_count)) => {
+                        eprintln!(
+                            "[room_broadcast]: a subscriber skipped {skipped_count} messages"
+                        );
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+
+    fn close_room(&self, room_id: &str) {
+        self.room_channels.lock().unwrap().remove(room_id);
+    }
+}
@@ -1 +1,4 @@
-pub const HOW_MUCH_LAST_MESSAGES_TO_STORE: usize = 50;
+pub const DEFAULT_MESSAGES_PAGE_SIZE: usize = 50;
+pub const MAX_MESSAGES_PAGE_SIZE: usize = 200;
+/// How many broadcasts a room's channel holds for subscribers that haven't picked them up yet.
+pub const ROOM_CHANNEL_CAPACITY: usize = 256;
@@ -0,0 +1,14 @@
+use async_trait::async_trait;
+use std::time::Duration;
+use url::Url;
+
+/// Keeps track of which instance holds which room, so that requests for a room can be routed to
+/// the instance that has its state.
+#[async_trait]
+pub trait RoomDirectory: Send + Sync {
+    /// Records that the room is held by the instance reachable at `instance_url`. The claim lapses
+    /// after `ttl` unless renewed, so that rooms of an instance that went away are forgotten.
+    async fn claim(&self, room_id: &str, instance_url: &Url, ttl: Duration);
+    async fn release(&self, room_id: &str);
+    async fn owner(&self, room_id: &str) -> Option<Url>;
+}
@@ -1,10 +1,18 @@
 use crate::map::models::LatLng;
 use crate::rooms::message_types::BriefUserInfoPayload;
-use crate::rooms::models::{ChatMessage, RoomStatus};
+use crate::rooms::models::{
+    BannedUserInfo, ChatMessage, MessageModificationError, PublicListing, PublicRoomInfo, Room,
+    RoomAccess, RoomStatus, RoomSummary,
+};
+use crate::rooms::snapshot::RoomSnapshot;
 
-use crate::storage::rooms::UserConnectedResult;
-use crate::storage::sockets::HashMapClientSocketsStorage;
-use crate::users::models::User;
+use crate::rooms::votes::{VoteProgress, VoteStartError, VoteSubject};
+use crate::storage::rooms::{RemovedRoom, RemovedUser, UserConnectedResult, UserLookupError};
+use crate::storage::socket_queue::CoalescingKey;
+use crate::storage::sockets::{Audience, HashMapClientSocketsStorage};
+use crate::users::models::{Permission, User, UserRole};
+use std::collections::HashSet;
+use std::time::Duration;
 
 // TODO: use newtypes for user ids, room ids etc.
 
@@ -12,18 +20,23 @@ pub trait IRoomStorage:
     RoomRepo
     + RoomGameFlowHandler
     + RoomConnectionHandler
-    + RoomSocketsRepo
+    + RoomEventsRepo
     + UserScoreRepo
     + UserGuessRepo
     + UserPermissionsRepo
     + RoomInfoRepo
+    + RoomAttachmentsRepo
+    + RoomAccessRepo
+    + RoomVotesRepo
+    + ChatMessagesRepo
+    + RoomSnapshotRepo
 {
 }
 
 pub trait RoomRepo {
     async fn exists(&self, room_id: &str) -> bool;
 
-    async fn create(&self) -> String;
+    async fn create(&self, public_listing: Option<PublicListing>) -> String;
 
     async fn has_different_user_with_same_username(
         &self,
@@ -36,15 +49,47 @@ pub trait RoomRepo {
 
     async fn user_is_host(&self, room_id: &str, public_user_id: &str) -> bool;
 
-    async fn add_message(&self, room_id: &str, message: ChatMessage);
+    /// Returns `false` if the room is gone.
+    async fn add_message(&self, room_id: &str, message: ChatMessage) -> bool;
+
+    async fn count(&self) -> usize;
+
+    async fn remove(&self, room_id: &str) -> Option<RemovedRoom>;
+
+    async fn ids(&self) -> Vec<String>;
+
+    /// How many rooms have a round in progress.
+    async fn playing_count(&self) -> usize;
+
+    /// Returns `false` if there is no such room.
+    async fn set_public_listing(
+        &self,
+        room_id: &str,
+        public_listing: Option<PublicListing>,
+    ) -> bool;
+
+    /// Public rooms that can be joined without an invite, most populated first.
+    async fn public_rooms(&self) -> Vec<PublicRoomInfo>;
+
+    /// Picks a public room that anyone can join right away, creating one if there are none.
+    async fn find_or_create_quick_play_room(&self, public_listing: PublicListing) -> String;
+
+    /// Removes the rooms that have been empty for `empty_room_ttl`, or had nobody online and no
+    /// activity for `idle_ttl`, returning the sockets of the users that were still in them.
+    async fn delete_expired(
+        &self,
+        idle_ttl: Duration,
+        empty_room_ttl: Duration,
+    ) -> Vec<RemovedRoom>;
 }
 
 pub trait RoomGameFlowHandler {
     async fn start_game(&self, room_id: &str, client_sockets: HashMapClientSocketsStorage);
 
-    async fn finish_game(&self, room_id: &str) -> bool;
+    /// Returns whether the whole game is over, or `None` if there is no such room.
+    async fn finish_game(&self, room_id: &str) -> Option<bool>;
 
-    async fn current_round_number(&self, room_id: &str) -> u64;
+    async fn current_round_number(&self, room_id: &str) -> Option<u64>;
 }
 
 pub trait RoomConnectionHandler {
@@ -57,64 +102,238 @@ pub trait RoomConnectionHandler {
         private_user_id: &str,
     ) -> Result<UserConnectedResult, ()>;
 
+    /// Returns whether the user was away, or an error if they are no longer in the room.
     async fn on_user_reconnected(
         &self,
         room_id: &str,
         _msg_payload: BriefUserInfoPayload,
         socket_id: usize,
         private_user_id: &str,
-    );
+    ) -> Result<bool, ()>;
 
+    /// Removes the user from the room unless they come back within the grace period. Does
+    /// nothing if the user is still connected.
     async fn on_user_disconnected(
         &self,
         room_id: &str,
-        raw_msg: String,
         private_user_id: &str,
-        socket_id: usize,
         client_sockets: HashMapClientSocketsStorage,
     );
 
-    async fn disconnect_user(&self, room_id: &str, socket_id: usize);
+    /// Marks the user with this socket as away. Returns their public ID, if there is such a user.
+    async fn disconnect_user(&self, room_id: &str, socket_id: usize) -> Option<String>;
 }
 
-pub trait RoomSocketsRepo {
-    async fn all_socket_ids(&self, room_id: &str) -> Vec<Option<usize>>;
+pub trait RoomEventsRepo {
+    /// Adds the room's next sequence number to the event, keeps it for replaying to users that
+    /// reconnect and publishes it, all under the room's lock so that events go out in order.
+    async fn publish_event(
+        &self,
+        room_id: &str,
+        raw_event: &str,
+        audience: Audience,
+        coalescing_key: Option<CoalescingKey>,
+        client_sockets: &HashMapClientSocketsStorage,
+    );
 
-    async fn socket_ids_except_sender(
+    /// Events sent after `last_seen_seq` that were meant for the user, or `None` if the log
+    /// doesn't reach back that far.
+    async fn events_since(
         &self,
         room_id: &str,
-        sender_socket_id: usize,
-    ) -> Vec<Option<usize>>;
+        public_user_id: &str,
+        last_seen_seq: u64,
+    ) -> Option<Vec<String>>;
+
+    async fn last_seq(&self, room_id: &str) -> u64;
 }
 
 pub trait UserScoreRepo {
-    async fn change_score(&self, room_id: &str, target_user_public_id: &str, amount: i64);
+    /// Returns `false` if there is no such user in the room.
+    async fn change_score(&self, room_id: &str, target_user_public_id: &str, amount: i64) -> bool;
 }
 
 pub trait UserGuessRepo {
-    async fn save_guess(&self, room_id: &str, private_user_id: &str, guess: LatLng);
+    async fn save_guess(
+        &self,
+        room_id: &str,
+        private_user_id: &str,
+        guess: LatLng,
+    ) -> Result<(), UserLookupError>;
 
-    async fn submit_guess(&self, room_id: &str, private_user_id: &str, guess: LatLng) -> bool;
+    /// Returns whether every player has submitted their guess now.
+    async fn submit_guess(
+        &self,
+        room_id: &str,
+        private_user_id: &str,
+        guess: LatLng,
+    ) -> Result<bool, UserLookupError>;
 
-    async fn revoke_guess(&self, room_id: &str, private_user_id: &str);
+    async fn revoke_guess(
+        &self,
+        room_id: &str,
+        private_user_id: &str,
+    ) -> Result<(), UserLookupError>;
 }
 
 pub trait UserPermissionsRepo {
-    async fn mute(&self, room_id: &str, target_user_public_id: &str);
+    /// Returns `false` if there is no such user in the room.
+    async fn mute(&self, room_id: &str, target_user_public_id: &str) -> bool;
+
+    /// Returns `false` if there is no such user in the room.
+    async fn unmute(&self, room_id: &str, target_user_public_id: &str) -> bool;
+
+    /// Returns the removed user, or `None` if there was no such user in the room. Hands the host
+    /// role over if needed.
+    async fn kick(&self, room_id: &str, target_user_public_id: &str) -> Option<RemovedUser>;
 
-    async fn unmute(&self, room_id: &str, target_user_public_id: &str);
+    /// Removes the user from every room they are in, handing the host role over where needed.
+    async fn kick_everywhere(&self, target_user_public_id: &str) -> Vec<RemovedUser>;
 
-    async fn ban(&self, room_id: &str, target_user_public_id: &str);
+    /// Returns the removed user, if they were in the room, or `None` if there is no such room.
+    async fn ban(
+        &self,
+        room_id: &str,
+        target_user_public_id: &str,
+        duration: Option<Duration>,
+    ) -> Option<Option<User>>;
+
+    /// Returns `false` if the user wasn't banned, or `None` if there is no such room.
+    async fn unban(&self, room_id: &str, target_user_public_id: &str) -> Option<bool>;
+
+    async fn bans(&self, room_id: &str) -> Option<Vec<BannedUserInfo>>;
 
     async fn is_muted(&self, room_id: &str, public_user_id: &str) -> bool;
 
     async fn is_banned(&self, room_id: &str, public_user_id: &str) -> bool;
+
+    /// The single place where it's decided whether a user is allowed to do something.
+    async fn has_permission(
+        &self,
+        room_id: &str,
+        public_user_id: &str,
+        permission: Permission,
+    ) -> bool;
+
+    async fn permissions(&self, room_id: &str, public_user_id: &str) -> HashSet<Permission>;
+
+    /// Returns `false` if there is no such user in the room, or `None` if there is no such room.
+    async fn transfer_host(&self, room_id: &str, new_host_public_id: &str) -> Option<bool>;
+
+    /// Returns `false` if there is no such user in the room, or `None` if there is no such room.
+    async fn set_role(
+        &self,
+        room_id: &str,
+        target_user_public_id: &str,
+        role: UserRole,
+    ) -> Option<bool>;
+
+    /// Returns `false` if there is no such room.
+    async fn set_co_host_permissions(
+        &self,
+        room_id: &str,
+        permissions: HashSet<Permission>,
+    ) -> bool;
 }
 
 pub trait RoomInfoRepo {
-    async fn status(&self, room_id: &str) -> RoomStatus;
+    /// All rooms, most populated first.
+    async fn summaries(&self) -> Vec<RoomSummary>;
+
+    /// The room's summary, users and active bans, all taken at the same moment.
+    async fn details(&self, room_id: &str)
+        -> Option<(RoomSummary, Vec<User>, Vec<BannedUserInfo>)>;
+
+    async fn status(&self, room_id: &str) -> Option<RoomStatus>;
+
+    async fn users(&self, room_id: &str) -> Option<Vec<User>>;
+
+    /// Returns a page of the chat history and whether there are more messages past it.
+    async fn messages(
+        &self,
+        room_id: &str,
+        after: Option<usize>,
+        before: Option<usize>,
+        limit: usize,
+    ) -> Option<(Vec<ChatMessage>, bool)>;
+}
+
+pub trait RoomAttachmentsRepo {
+    /// IDs of all attachments referenced by the messages stored in any of the rooms.
+    async fn attachment_ids_in_use(&self) -> HashSet<String>;
+}
+
+pub trait RoomAccessRepo {
+    async fn access(&self, room_id: &str) -> Option<RoomAccess>;
+
+    /// Returns `false` if there is no such room.
+    async fn set_access(&self, room_id: &str, access: RoomAccess) -> bool;
+
+    /// Returns `false` if there is no such room.
+    async fn set_max_players(&self, room_id: &str, max_players: usize) -> bool;
+
+    /// Returns `false` if there is no such room.
+    async fn set_locked(&self, room_id: &str, is_locked: bool) -> bool;
+
+    async fn is_locked(&self, room_id: &str) -> bool;
+
+    async fn is_full(&self, room_id: &str) -> bool;
+}
+
+pub trait RoomVotesRepo {
+    async fn start_vote(
+        &self,
+        room_id: &str,
+        initiator_public_id: &str,
+        subject: VoteSubject,
+        majority_percent: u64,
+    ) -> Result<VoteProgress, VoteStartError>;
+
+    async fn cast_vote(
+        &self,
+        room_id: &str,
+        voter_public_id: &str,
+        in_favor: bool,
+    ) -> Option<VoteProgress>;
+
+    /// Ends the vote as failed if it's still running. Returns `None` if it's already over.
+    async fn expire_vote(&self, room_id: &str, vote_id: usize) -> Option<VoteProgress>;
+
+    async fn skip_location(&self, room_id: &str) -> bool;
+}
+
+pub trait ChatMessagesRepo {
+    async fn has_message(&self, room_id: &str, message_id: usize) -> bool;
+
+    /// Returns whether the reaction was added and how many users have reacted with this emoji.
+    async fn toggle_reaction(
+        &self,
+        room_id: &str,
+        message_id: usize,
+        user_public_id: &str,
+        emoji: &str,
+    ) -> Result<(bool, usize), MessageModificationError>;
+
+    async fn edit_message(
+        &self,
+        room_id: &str,
+        message_id: usize,
+        editor_public_id: &str,
+        content: String,
+    ) -> Result<(), MessageModificationError>;
+
+    async fn delete_message(
+        &self,
+        room_id: &str,
+        message_id: usize,
+        requester_public_id: &str,
+        can_delete_any: bool,
+    ) -> Result<(), MessageModificationError>;
+}
 
-    async fn users(&self, room_id: &str) -> Vec<User>;
+pub trait RoomSnapshotRepo {
+    async fn snapshot(&self, room_id: &str) -> Option<RoomSnapshot>;
 
-    async fn messages(&self, room_id: &str) -> Vec<ChatMessage>;
+    /// Adds the room under a new ID and returns it.
+    async fn restore(&self, room: Room) -> String;
 }
@@ -1,4 +1,10 @@
+pub mod broker;
 pub mod consts;
+pub mod directory;
 pub mod interface;
+pub mod redis;
 pub mod rooms;
+pub mod socket_queue;
 pub mod sockets;
+#[cfg(test)]
+pub mod tests;
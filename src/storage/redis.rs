@@ -0,0 +1,215 @@
+use crate::storage::broker::{RoomBroadcast, RoomBroker};
+use crate::storage::directory::RoomDirectory;
+use async_trait::async_trait;
+use futures_util::future;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, Client, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use url::Url;
+
+/// What goes through a room's Redis channel.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum ChannelMessage {
+    Broadcast(RoomBroadcast),
+    /// Tells the subscribers that nothing else is coming.
+    RoomClosed,
+}
+
+impl ChannelMessage {
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn decode(payload: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(payload)
+    }
+}
+
+fn room_channel(room_id: &str) -> String {
+    format!("room:{room_id}")
+}
+
+fn room_owner_key(room_id: &str) -> String {
+    format!("room-owner:{room_id}")
+}
+
+/// Relays room broadcasts through Redis pub/sub, so that all instances get them.
+pub struct RedisRoomBroker {
+    client: Client,
+    /// Everything is published by a single task, so that a room's messages keep their order.
+    outgoing: mpsc::UnboundedSender<(String, ChannelMessage)>,
+}
+
+impl RedisRoomBroker {
+    pub fn new(client: Client) -> Self {
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<(String, ChannelMessage)>();
+        let connection = LazyConnection::new(client.clone());
+        tokio::spawn(async move {
+            while let Some((channel, message)) = outgoing_rx.recv().await {
+                let result = connection
+                    .run(|mut connection| async move {
+                        connection
+                            .publish::<_, _, ()>(&channel, message.encode())
+                            .await
+                    })
+                    .await;
+                if let Err(error) = result {
+                    eprintln!("[room_broadcast]: failed to publish to Redis: {error}");
+                }
+            }
+        });
+        Self { client, outgoing }
+    }
+}
+
+impl RoomBroker for RedisRoomBroker {
+    fn publish(&self, room_id: &str, broadcast: RoomBroadcast) {
+        // Fails only if the publishing task is gone, i.e. the runtime is shutting down.
+        let _ = self
+            .outgoing
+            .send((room_channel(room_id), ChannelMessage::Broadcast(broadcast)));
+    }
+
+    /// Subscribes once the stream is first polled, so broadcasts published right before that
+    /// are missed; clients catch up on them by replaying the room's events.
+    fn subscribe(&self, room_id: &str) -> BoxStream<'static, RoomBroadcast> {
+        let client = self.client.clone();
+        let channel = room_channel(room_id);
+        stream::once(async move {
+            let mut pubsub = client.get_async_pubsub().await?;
+            pubsub.subscribe(&channel).await?;
+            RedisResult::Ok(pubsub.into_on_message())
+        })
+        .filter_map(|subscription| {
+            future::ready(
+                subscription
+                    .map_err(|error| {
+                        eprintln!("[room_broadcast]: failed to subscribe via Redis: {error}");
+                    })
+                    .ok(),
+            )
+        })
+        .flatten()
+        .filter_map(
+            |msg| match ChannelMessage::decode(msg.get_payload_bytes()) {
+                Ok(message) => future::ready(Some(message)),
+                Err(error) => {
+                    eprintln!("[room_broadcast]: failed to decode a Redis message: {error}");
+                    future::ready(None)
+                }
+            },
+        )
+        .take_while(|message| future::ready(*message != ChannelMessage::RoomClosed))
+        .filter_map(|message| match message {
+            ChannelMessage::Broadcast(broadcast) => future::ready(Some(broadcast)),
+            ChannelMessage::RoomClosed => future::ready(None),
+        })
+        .boxed()
+    }
+
+    fn close_room(&self, room_id: &str) {
+        let _ = self
+            .outgoing
+            .send((room_channel(room_id), ChannelMessage::RoomClosed));
+    }
+}
+
+/// Keeps the room claims as expiring Redis keys.
+pub struct RedisRoomDirectory {
+    connection: LazyConnection,
+}
+
+impl RedisRoomDirectory {
+    pub fn new(client: Client) -> Self {
+        Self {
+            connection: LazyConnection::new(client),
+        }
+    }
+}
+
+#[async_trait]
+impl RoomDirectory for RedisRoomDirectory {
+    async fn claim(&self, room_id: &str, instance_url: &Url, ttl: Duration) {
+        let key = room_owner_key(room_id);
+        let result = self
+            .connection
+            .run(|mut connection| async move {
+                connection
+                    .set_ex::<_, _, ()>(key, instance_url.as_str(), ttl.as_secs().max(1))
+                    .await
+            })
+            .await;
+        if let Err(error) = result {
+            eprintln!("[room_directory]: failed to claim room {room_id}: {error}");
+        }
+    }
+
+    async fn release(&self, room_id: &str) {
+        let key = room_owner_key(room_id);
+        let result = self
+            .connection
+            .run(|mut connection| async move { connection.del::<_, ()>(key).await })
+            .await;
+        if let Err(error) = result {
+            eprintln!("[room_directory]: failed to release room {room_id}: {error}");
+        }
+    }
+
+    async fn owner(&self, room_id: &str) -> Option<Url> {
+        let key = room_owner_key(room_id);
+        let result = self
+            .connection
+            .run(|mut connection| async move { connection.get::<_, Option<String>>(key).await })
+            .await;
+        match result {
+            Ok(owner) => owner.and_then(|owner| Url::parse(&owner).ok()),
+            Err(error) => {
+                eprintln!("[room_directory]: failed to look up room {room_id}: {error}");
+                None
+            }
+        }
+    }
+}
+
+/// Connects on first use and again after the connection fails.
+struct LazyConnection {
+    client: Client,
+    connection: Mutex<Option<MultiplexedConnection>>,
+}
+
+impl LazyConnection {
+    fn new(client: Client) -> Self {
+        Self {
+            client,
+            connection: Mutex::default(),
+        }
+    }
+
+    async fn run<T, F, Fut>(&self, command: F) -> RedisResult<T>
+    where
+        F: FnOnce(MultiplexedConnection) -> Fut,
+        Fut: std::future::Future<Output = RedisResult<T>>,
+    {
+        let connection = {
+            let mut connection = self.connection.lock().await;
+            match connection.as_ref() {
+                Some(connection) => connection.clone(),
+                None => connection
+                    .insert(self.client.get_multiplexed_async_connection().await?)
+                    .clone(),
+            }
+        };
+        let result = command(connection).await;
+        if result
+            .as_ref()
+            .is_err_and(|error| error.is_io_error() || error.is_connection_dropped())
+        {
+            *self.connection.lock().await = None;
+        }
+        result
+    }
+}
@@ -1,21 +1,28 @@
 use crate::map::models::LatLng;
-use crate::rooms::consts::ROUNDS_PER_GAME;
+use crate::rooms;
+use crate::rooms::consts::{ROUNDS_PER_GAME, ROUND_DURATION_TICKS};
 use crate::rooms::message_types::{
-    self, BotMessagePayload, BriefUserInfoPayload, RoundStartedBotMessagePayload,
-    RoundStartedBotMsg, ServerSentSocketMessage, UserDisconnectedBotMessagePayload,
-    UserDisconnectedBotMsg,
+    self, BotMessagePayload, BriefUserInfoPayload, PresenceChangedPayload,
+    RoundStartedBotMessagePayload, RoundStartedBotMsg, ServerSentSocketMessage,
+    UserDisconnectedBotMessagePayload, UserDisconnectedBotMsg, UserPubIdInfoPayload,
 };
-use crate::rooms::models::{ChatMessage, Room, RoomStatus};
-use crate::storage::consts::HOW_MUCH_LAST_MESSAGES_TO_STORE;
+use crate::rooms::models::{
+    BannedUserInfo, ChatMessage, MessageModificationError, PublicListing, PublicRoomInfo, Room,
+    RoomAccess, RoomStatus, RoomStatusKind, RoomSummary,
+};
+use crate::rooms::snapshot::RoomSnapshot;
+use crate::rooms::votes::{VoteProgress, VoteStartError, VoteSubject};
 use crate::storage::interface::{
-    IRoomStorage, RoomConnectionHandler, RoomGameFlowHandler, RoomInfoRepo, RoomRepo,
-    RoomSocketsRepo, UserGuessRepo, UserPermissionsRepo, UserScoreRepo,
+    ChatMessagesRepo, IRoomStorage, RoomAccessRepo, RoomAttachmentsRepo, RoomConnectionHandler,
+    RoomEventsRepo, RoomGameFlowHandler, RoomInfoRepo, RoomRepo, RoomSnapshotRepo, RoomVotesRepo,
+    UserGuessRepo, UserPermissionsRepo, UserScoreRepo,
 };
-use crate::storage::sockets::HashMapClientSocketsStorage;
-use crate::users::models::User;
+use crate::storage::socket_queue::CoalescingKey;
+use crate::storage::sockets::{Audience, HashMapClientSocketsStorage};
+use crate::users::models::{Permission, Presence, User, UserRole};
 use rand::{distributions::Alphanumeric, Rng};
 use std::collections::HashMap;
-use std::collections::VecDeque;
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
@@ -32,17 +39,9 @@ impl RoomRepo for HashMapRoomsStorage {
         self.storage.read().await.contains_key(room_id)
     }
 
-    async fn create(&self) -> String {
+    async fn create(&self, public_listing: Option<PublicListing>) -> String {
         let room_id = generate_room_id();
-        let room = Room {
-            users: vec![],
-            last_messages: VecDeque::with_capacity(HOW_MUCH_LAST_MESSAGES_TO_STORE),
-            status: RoomStatus::Waiting {
-                previous_location: None,
-            },
-            banned_public_users_ids: vec![],
-            rounds_left: ROUNDS_PER_GAME,
-        };
+        let room = Room::new(public_listing);
         self.storage.write().await.insert(room_id.clone(), room);
         room_id
     }
@@ -53,104 +52,223 @@ impl RoomRepo for HashMapRoomsStorage {
         public_user_id: &str,
         username: &str,
     ) -> bool {
-        self.storage
-            .read()
-            .await
-            .get(room_id)
-            .unwrap()
-            .users
-            .iter()
-            .any(|user| user.public_id != public_user_id && user.name == username)
+        self.storage.read().await.get(room_id).is_some_and(|room| {
+            room.users
+                .iter()
+                .any(|user| user.public_id != public_user_id && user.name == username)
+        })
     }
 
     async fn has_user_with_such_private_id(&self, room_id: &str, private_user_id: &str) -> bool {
+        self.storage.read().await.get(room_id).is_some_and(|room| {
+            room.users
+                .iter()
+                .any(|user| user.private_id == private_user_id)
+        })
+    }
+
+    async fn user_is_host(&self, room_id: &str, public_user_id: &str) -> bool {
+        self.storage.read().await.get(room_id).is_some_and(|room| {
+            room.users
+                .iter()
+                .any(|user| user.public_id == public_user_id && user.is_host())
+        })
+    }
+
+    async fn add_message(&self, room_id: &str, message: ChatMessage) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(room) = storage_guard.get_mut(room_id) else {
+            return false;
+        };
+        room.add_message(message);
+        true
+    }
+
+    async fn count(&self) -> usize {
+        self.storage.read().await.len()
+    }
+
+    async fn remove(&self, room_id: &str) -> Option<RemovedRoom> {
+        let room = self.storage.write().await.remove(room_id)?;
+        Some(RemovedRoom {
+            room_id: room_id.to_string(),
+            socket_ids: room.users.iter().map(|user| user.socket_id).collect(),
+        })
+    }
+
+    async fn ids(&self) -> Vec<String> {
+        self.storage.read().await.keys().cloned().collect()
+    }
+
+    async fn playing_count(&self) -> usize {
         self.storage
             .read()
             .await
-            .get(room_id)
-            .unwrap()
-            .users
-            .iter()
-            .any(|user| user.private_id == private_user_id)
+            .values()
+            .filter(|room| matches!(room.status, RoomStatus::Playing { .. }))
+            .count()
     }
 
-    async fn user_is_host(&self, room_id: &str, public_user_id: &str) -> bool {
-        self.storage
+    async fn set_public_listing(
+        &self,
+        room_id: &str,
+        public_listing: Option<PublicListing>,
+    ) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(room) = storage_guard.get_mut(room_id) else {
+            return false;
+        };
+        room.touch();
+        room.public_listing = public_listing;
+        true
+    }
+
+    async fn public_rooms(&self) -> Vec<PublicRoomInfo> {
+        let mut public_rooms = self
+            .storage
             .read()
             .await
-            .get(room_id)
-            .unwrap()
-            .users
             .iter()
-            .find(|user| user.public_id == public_user_id)
-            .map_or(false, |user| user.is_host)
+            .filter(|(_room_id, room)| !matches!(room.access, RoomAccess::InviteOnly))
+            .filter_map(|(room_id, room)| {
+                let public_listing = room.public_listing.as_ref()?;
+                Some(PublicRoomInfo {
+                    room_id: room_id.clone(),
+                    display_name: public_listing.display_name.clone(),
+                    players_count: room.users.len(),
+                    status: RoomStatusKind::from(room.status),
+                    password_protected: matches!(room.access, RoomAccess::PasswordProtected { .. }),
+                    rounds_per_game: ROUNDS_PER_GAME,
+                    max_players: room.max_players,
+                })
+            })
+            .collect::<Vec<_>>();
+        public_rooms.sort_by(|a, b| {
+            b.players_count
+                .cmp(&a.players_count)
+                .then_with(|| a.display_name.cmp(&b.display_name))
+        });
+        public_rooms
     }
 
-    async fn add_message(&self, room_id: &str, message: ChatMessage) {
-        self.storage
-            .write()
-            .await
-            .get_mut(room_id)
-            .unwrap()
-            .add_message(message);
+    async fn find_or_create_quick_play_room(&self, public_listing: PublicListing) -> String {
+        let mut storage_guard = self.storage.write().await;
+        // Prefer rooms that are between rounds and fill up the most populated ones first, so that
+        // players don't end up scattered across many half-empty rooms.
+        let best_room_id = storage_guard
+            .iter()
+            .filter(|(_room_id, room)| {
+                room.public_listing.is_some()
+                    && matches!(room.access, RoomAccess::Open)
+                    && !room.is_locked
+                    && !room.is_full()
+            })
+            .max_by_key(|(_room_id, room)| {
+                (
+                    matches!(room.status, RoomStatus::Waiting { .. }),
+                    room.users.len(),
+                )
+            })
+            .map(|(room_id, _room)| room_id.clone());
+        if let Some(room_id) = best_room_id {
+            return room_id;
+        }
+        let room_id = generate_room_id();
+        storage_guard.insert(room_id.clone(), Room::new(Some(public_listing)));
+        room_id
+    }
+
+    async fn delete_expired(
+        &self,
+        idle_ttl: Duration,
+        empty_room_ttl: Duration,
+    ) -> Vec<RemovedRoom> {
+        let mut storage_guard = self.storage.write().await;
+        let expired_rooms_ids = storage_guard
+            .iter()
+            .filter(|(_room_id, room)| room.is_expired(idle_ttl, empty_room_ttl))
+            .map(|(room_id, _room)| room_id.clone())
+            .collect::<Vec<_>>();
+        expired_rooms_ids
+            .into_iter()
+            .filter_map(|room_id| {
+                let room = storage_guard.remove(&room_id)?;
+                Some(RemovedRoom {
+                    room_id,
+                    socket_ids: room.users.iter().map(|user| user.socket_id).collect(),
+                })
+            })
+            .collect()
     }
 }
 
 impl RoomGameFlowHandler for HashMapRoomsStorage {
     async fn start_game(&self, room_id: &str, client_sockets: HashMapClientSocketsStorage) {
-        self.storage
-            .write()
-            .await
-            .get_mut(room_id)
-            .unwrap()
-            .start_playing();
+        let mut storage_guard = self.storage.write().await;
+        let Some(room) = storage_guard.get_mut(room_id) else {
+            return;
+        };
+        room.start_playing();
+        let mut skipped_locations = room.skipped_locations;
         let room_id = room_id.to_string();
         let storage_handle = self.storage.clone();
         tokio::spawn(async move {
-            for tick in (0..=100).rev() {
-                let all_sockets_ids = storage_handle
+            let mut tick = ROUND_DURATION_TICKS;
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                // Check if the game was finished because all players submitted a guess
+                // before the timer counted all the way down
+                let Some(room_status) = storage_handle
                     .read()
                     .await
                     .get(&room_id)
-                    .unwrap()
-                    .users
-                    .iter()
-                    .map(|user| user.socket_id)
-                    .collect::<Vec<_>>();
-                let ws_event_msg = ServerSentSocketMessage::Tick {
-                    r#type: message_types::Tick,
-                    payload: tick,
+                    .map(|room| room.status)
+                else {
+                    return;
                 };
-                let raw_ws_event_msg = serde_json::to_string(&ws_event_msg).unwrap();
-                tokio::time::sleep(Duration::from_secs(1)).await;
-                // Check if the game was finished because all players submitted a guess
-                // before the timer counted all the way down
-                let room_status = storage_handle.read().await.get(&room_id).unwrap().status;
                 if let RoomStatus::Waiting {
                     previous_location: _previous_location,
                 } = room_status
                 {
                     return;
                 }
+                let mut storage_guard = storage_handle.write().await;
+                let Some(room) = storage_guard.get_mut(&room_id) else {
+                    // The room was closed in the middle of the round.
+                    return;
+                };
+                if room.skipped_locations != skipped_locations {
+                    // A new location gets the full round time.
+                    skipped_locations = room.skipped_locations;
+                    tick = ROUND_DURATION_TICKS;
+                }
+                let ws_event_msg = ServerSentSocketMessage::Tick {
+                    r#type: message_types::Tick,
+                    payload: tick,
+                };
+                let raw_ws_event_msg = serde_json::to_string(&ws_event_msg).unwrap();
+                let raw_ws_event_msg = room.record_event(&raw_ws_event_msg, Audience::Everyone);
                 client_sockets
-                    .broadcast_msg(&raw_ws_event_msg, &all_sockets_ids)
+                    .publish(
+                        &room_id,
+                        raw_ws_event_msg,
+                        Audience::Everyone,
+                        Some(CoalescingKey::Tick),
+                    )
                     .await;
+                if tick == 0 {
+                    break;
+                }
+                tick -= 1;
             }
-            let game_finished = storage_handle
+            let Some((game_finished, rounds_left)) = storage_handle
                 .write()
                 .await
                 .get_mut(&room_id)
-                .unwrap()
-                .finish_game();
-            let all_sockets_ids = storage_handle
-                .read()
-                .await
-                .get(&room_id)
-                .unwrap()
-                .users
-                .iter()
-                .map(|user| user.socket_id)
-                .collect::<Vec<_>>();
+                .map(|room| (room.finish_game(), room.rounds_left))
+            else {
+                return;
+            };
             let game_or_round_finished_msg = if game_finished {
                 ServerSentSocketMessage::GameFinished {
                     r#type: message_types::GameFinished,
@@ -162,13 +280,6 @@ impl RoomGameFlowHandler for HashMapRoomsStorage {
             };
             let raw_game_or_round_finished_msg =
                 serde_json::to_string(&game_or_round_finished_msg).unwrap();
-            // TODO: bad because duplicates the `self.get_current_round_number()` code
-            let rounds_left = storage_handle
-                .read()
-                .await
-                .get(&room_id)
-                .unwrap()
-                .rounds_left;
             let round_number = match rounds_left {
                 ROUNDS_PER_GAME => ROUNDS_PER_GAME,
                 _ => ROUNDS_PER_GAME + 1 - rounds_left,
@@ -188,31 +299,42 @@ impl RoomGameFlowHandler for HashMapRoomsStorage {
             };
             let raw_bot_ws_msg = serde_json::to_string(&bot_ws_msg).unwrap();
             // TODO: bad because duplicates the `self.add_new_message()` code
-            storage_handle
-                .write()
-                .await
-                .get_mut(&room_id)
-                .unwrap()
-                .add_message(bot_message);
+            let mut storage_guard = storage_handle.write().await;
+            let Some(room) = storage_guard.get_mut(&room_id) else {
+                return;
+            };
+            room.add_message(bot_message);
+            let raw_bot_ws_msg = room.record_event(&raw_bot_ws_msg, Audience::Everyone);
+            let raw_game_or_round_finished_msg =
+                room.record_event(&raw_game_or_round_finished_msg, Audience::Everyone);
             client_sockets
-                .broadcast_msg(&raw_bot_ws_msg, &all_sockets_ids)
+                .publish(&room_id, raw_bot_ws_msg, Audience::Everyone, None)
                 .await;
             client_sockets
-                .broadcast_msg(&raw_game_or_round_finished_msg, &all_sockets_ids)
+                .publish(
+                    &room_id,
+                    raw_game_or_round_finished_msg,
+                    Audience::Everyone,
+                    None,
+                )
                 .await;
         });
     }
 
-    async fn finish_game(&self, room_id: &str) -> bool {
+    async fn finish_game(&self, room_id: &str) -> Option<bool> {
         self.storage
             .write()
             .await
             .get_mut(room_id)
-            .unwrap()
-            .finish_game()
+            .map(|room| room.finish_game())
     }
-    async fn current_round_number(&self, room_id: &str) -> u64 {
-        self.storage.read().await.get(room_id).unwrap().rounds_left
+
+    async fn current_round_number(&self, room_id: &str) -> Option<u64> {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .map(|room| room.rounds_left)
     }
 }
 
@@ -226,37 +348,35 @@ impl RoomConnectionHandler for HashMapRoomsStorage {
         private_user_id: &str,
     ) -> Result<UserConnectedResult, ()> {
         let mut storage_guard = self.storage.write().await;
-        let room_has_no_members = storage_guard.get(room_id).unwrap().users.is_empty();
-        let such_user_already_in_the_room = storage_guard
-            .get(room_id)
-            .unwrap()
+        // The room might have been closed since the socket was opened.
+        let room = storage_guard.get_mut(room_id).ok_or(())?;
+        room.touch();
+        let room_has_no_members = room.users.is_empty();
+        if let Some(user) = room
             .users
-            .iter()
-            .any(|user| user.private_id == private_user_id);
-        if such_user_already_in_the_room {
-            storage_guard
-                .get_mut(room_id)
-                .unwrap()
-                .users
-                .iter_mut()
-                .find(|user| user.private_id == private_user_id)
-                .unwrap()
-                .socket_id = Some(socket_id);
+            .iter_mut()
+            .find(|user| user.private_id == private_user_id)
+        {
             // TODO: comparison by user ID, not by usernames - return Err if exists
-            return Ok(UserConnectedResult::AlreadyInTheRoom);
+            return Ok(UserConnectedResult::AlreadyInTheRoom {
+                came_back: user.go_online(socket_id),
+            });
         }
-        storage_guard
-            .get_mut(room_id)
-            .unwrap()
-            .users
-            .push(User::new(
-                public_user_id.to_string(),
-                private_user_id.to_string(),
-                msg_payload.username,
-                msg_payload.avatar_emoji,
-                room_has_no_members,
-                socket_id,
-            ));
+        // Has been checked before the socket was opened, but the room could've been locked or
+        // filled up, or the user banned, since then.
+        if room.is_locked || room.is_full() || room.is_banned(public_user_id) {
+            return Err(());
+        }
+        let is_spectating = room.game_in_progress();
+        room.users.push(User::new(
+            public_user_id.to_string(),
+            private_user_id.to_string(),
+            msg_payload.username,
+            msg_payload.avatar_emoji,
+            room_has_no_members,
+            socket_id,
+            is_spectating,
+        ));
         Ok(UserConnectedResult::NewUser)
     }
 
@@ -266,238 +386,397 @@ impl RoomConnectionHandler for HashMapRoomsStorage {
         _msg_payload: BriefUserInfoPayload,
         socket_id: usize,
         private_user_id: &str,
-    ) {
-        self.storage
-            .write()
-            .await
-            .get_mut(room_id)
-            .unwrap()
+    ) -> Result<bool, ()> {
+        let mut storage_guard = self.storage.write().await;
+        let room = storage_guard.get_mut(room_id).ok_or(())?;
+        let Some(user) = room
             .users
             .iter_mut()
             .find(|user| user.private_id == private_user_id)
-            .unwrap()
-            .socket_id = Some(socket_id);
+        else {
+            eprintln!("[user_reconnected]: the grace period is over, the user has left already");
+            return Err(());
+        };
+        let came_back = user.go_online(socket_id);
+        room.touch();
+        Ok(came_back)
     }
 
     async fn on_user_disconnected(
         &self,
         room_id: &str,
-        raw_msg: String,
         private_user_id: &str,
-        socket_id: usize,
         client_sockets: HashMapClientSocketsStorage,
     ) {
+        let away_since = self.storage.read().await.get(room_id).and_then(|room| {
+            room.users
+                .iter()
+                .find(|user| user.private_id == private_user_id)?
+                .away_since
+        });
+        let Some(away_since) = away_since else {
+            // Still connected, or already gone.
+            return;
+        };
         let storage_handle = self.storage.clone();
         let room_id = room_id.to_string();
         let private_user_id = private_user_id.to_string();
-        let relevant_socket_ids = self.socket_ids_except_sender(&room_id, socket_id).await;
         tokio::spawn(async move {
             println!("[handle_user_disconnected]: waiting before disconnecting user...");
-            tokio::time::sleep(Duration::from_secs(5)).await;
-            println!("[handle_user_disconnected]: disconnecting user");
+            tokio::time::sleep(rooms::presence_grace_period()).await;
             let mut storage_guard = storage_handle.write().await;
-            let such_user_already_in_the_room = storage_guard
-                .get(&room_id)
-                .unwrap()
-                .users
-                .iter()
-                .any(|user| user.private_id == private_user_id && user.socket_id.is_some());
-            if such_user_already_in_the_room {
+            let Some(room) = storage_guard.get_mut(&room_id) else {
+                // The room was closed while the user was gone.
                 return;
-            }
-            let (index_of_user_to_remove, _user) = storage_guard
-                .get(&room_id)
-                .unwrap()
-                .users
-                .iter()
-                .enumerate()
-                .find(|(_idx, user)| user.private_id == private_user_id)
-                .unwrap();
-            let removed_user = storage_guard
-                .get_mut(&room_id)
-                .unwrap()
-                .users
-                .remove(index_of_user_to_remove);
-            if removed_user.is_host {
-                storage_guard.get_mut(&room_id).unwrap().reassign_host()
+            };
+            let Some(index_of_user_to_remove) = room.users.iter().position(|user| {
+                user.private_id == private_user_id && user.away_since == Some(away_since)
+            }) else {
+                // The user came back, or was kicked or banned in the meantime.
+                return;
+            };
+            println!("[handle_user_disconnected]: disconnecting user");
+            let removed_user = room.users.remove(index_of_user_to_remove);
+            room.touch();
+            let mut ws_events = vec![];
+            if removed_user.is_host() {
+                if let Some(new_host_public_id) = room.reassign_host() {
+                    ws_events.push(ServerSentSocketMessage::HostChanged {
+                        r#type: message_types::HostChanged,
+                        payload: UserPubIdInfoPayload {
+                            public_id: new_host_public_id,
+                        },
+                    });
+                }
             }
             let bot_message_payload = BotMessagePayload::UserDisconnected {
                 r#type: UserDisconnectedBotMsg,
                 payload: UserDisconnectedBotMessagePayload {
-                    username: removed_user.name,
+                    username: removed_user.name.clone(),
                 },
             };
             let bot_message = ChatMessage::from_bot(bot_message_payload.clone());
-            let ws_message = ServerSentSocketMessage::BotMessage {
+            ws_events.push(ServerSentSocketMessage::BotMessage {
                 r#type: message_types::BotMessage,
                 id: bot_message.id(),
                 payload: bot_message_payload,
-            };
-            let bot_message_content = serde_json::to_string(&ws_message).unwrap();
-            let mut all_sockets_ids = relevant_socket_ids.clone();
-            all_sockets_ids.push(Some(socket_id));
-            storage_guard
-                .get_mut(&room_id)
-                .unwrap()
-                .add_message(bot_message);
-            client_sockets
-                .broadcast_msg(&bot_message_content, &all_sockets_ids)
-                .await;
-            client_sockets
-                .broadcast_msg(&raw_msg, &relevant_socket_ids)
-                .await;
+            });
+            room.add_message(bot_message);
+            ws_events.push(ServerSentSocketMessage::UserDisconnected {
+                r#type: message_types::UserDisconnected,
+                payload: BriefUserInfoPayload {
+                    username: removed_user.name,
+                    avatar_emoji: removed_user.avatar_emoji,
+                },
+            });
+            ws_events.push(ServerSentSocketMessage::PresenceChanged {
+                r#type: message_types::PresenceChanged,
+                payload: PresenceChangedPayload {
+                    public_id: removed_user.public_id,
+                    presence: Presence::Left,
+                },
+            });
+            let raw_ws_events = ws_events
+                .iter()
+                .map(|ws_event| {
+                    let raw_ws_event = room.record_event(
+                        &serde_json::to_string(ws_event).unwrap(),
+                        Audience::Everyone,
+                    );
+                    (raw_ws_event, CoalescingKey::of(ws_event))
+                })
+                .collect::<Vec<_>>();
+            for (raw_ws_event, coalescing_key) in raw_ws_events {
+                client_sockets
+                    .publish(&room_id, raw_ws_event, Audience::Everyone, coalescing_key)
+                    .await;
+            }
         });
     }
 
-    async fn disconnect_user(&self, room_id: &str, socket_id: usize) {
+    async fn disconnect_user(&self, room_id: &str, socket_id: usize) -> Option<String> {
         let mut storage_guard = self.storage.write().await;
-        let user = storage_guard
-            .get_mut(room_id)
-            .unwrap()
+        let Some(room) = storage_guard.get_mut(room_id) else {
+            // The room was closed, so there is nobody left to disconnect.
+            return None;
+        };
+        let user = room
             .users
             .iter_mut()
             .find(|user| user.socket_id == Some(socket_id));
         match user {
             Some(user) => {
                 // socket closed not on behalf of the user
-                user.socket_id = None;
+                user.go_away();
+                Some(user.public_id.clone())
             }
             None => {
                 println!("[user_disconnected]: user with such socket id not found: {socket_id}");
+                None
             }
         }
     }
 }
 
-impl RoomSocketsRepo for HashMapRoomsStorage {
-    async fn all_socket_ids(&self, room_id: &str) -> Vec<Option<usize>> {
+impl RoomEventsRepo for HashMapRoomsStorage {
+    async fn publish_event(
+        &self,
+        room_id: &str,
+        raw_event: &str,
+        audience: Audience,
+        coalescing_key: Option<CoalescingKey>,
+        client_sockets: &HashMapClientSocketsStorage,
+    ) {
+        let mut storage_guard = self.storage.write().await;
+        let raw_event = match storage_guard.get_mut(room_id) {
+            Some(room) => room.record_event(raw_event, audience),
+            // Closed in the meantime, so there is nobody to replay the event to.
+            None => raw_event.to_string(),
+        };
+        client_sockets
+            .publish(room_id, raw_event, audience, coalescing_key)
+            .await;
+    }
+
+    async fn events_since(
+        &self,
+        room_id: &str,
+        public_user_id: &str,
+        last_seen_seq: u64,
+    ) -> Option<Vec<String>> {
         self.storage
             .read()
             .await
-            .get(room_id)
-            .unwrap()
-            .users
-            .iter()
-            .map(|user| user.socket_id)
-            .collect::<Vec<_>>()
+            .get(room_id)?
+            .events
+            .events_since(last_seen_seq, public_user_id)
     }
 
-    async fn socket_ids_except_sender(
-        &self,
-        room_id: &str,
-        sender_socket_id: usize,
-    ) -> Vec<Option<usize>> {
+    async fn last_seq(&self, room_id: &str) -> u64 {
         self.storage
             .read()
             .await
             .get(room_id)
-            .unwrap()
-            .users
-            .iter()
-            // TODO: maybe compare by username ?
-            .filter(|user| user.socket_id != Some(sender_socket_id))
-            .map(|user| user.socket_id)
-            .collect::<Vec<_>>()
+            .map_or(0, |room| room.events.last_seq())
     }
 }
 
 impl UserScoreRepo for HashMapRoomsStorage {
-    async fn change_score(&self, room_id: &str, target_user_public_id: &str, amount: i64) {
-        self.storage
-            .write()
-            .await
-            .get_mut(room_id)
-            .unwrap()
-            .users
-            .iter_mut()
-            .find(|user| user.public_id == *target_user_public_id)
-            .unwrap()
-            .change_score(amount)
+    async fn change_score(&self, room_id: &str, target_user_public_id: &str, amount: i64) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(user) = storage_guard.get_mut(room_id).and_then(|room| {
+            room.users
+                .iter_mut()
+                .find(|user| user.public_id == *target_user_public_id)
+        }) else {
+            return false;
+        };
+        user.change_score(amount);
+        true
     }
 }
 
 impl UserGuessRepo for HashMapRoomsStorage {
-    async fn save_guess(&self, room_id: &str, private_user_id: &str, guess: LatLng) {
+    async fn save_guess(
+        &self,
+        room_id: &str,
+        private_user_id: &str,
+        guess: LatLng,
+    ) -> Result<(), UserLookupError> {
         let mut storage_guard = self.storage.write().await;
-        let room = storage_guard.get_mut(room_id).unwrap();
+        let room = storage_guard
+            .get_mut(room_id)
+            .ok_or(UserLookupError::RoomNotFound)?;
         room.users
             .iter_mut()
             .find(|user| user.private_id == *private_user_id)
-            .unwrap()
+            .ok_or(UserLookupError::UserNotFound)?
             .save_guess(guess);
+        room.touch();
+        Ok(())
     }
 
-    async fn submit_guess(&self, room_id: &str, private_user_id: &str, guess: LatLng) -> bool {
+    async fn submit_guess(
+        &self,
+        room_id: &str,
+        private_user_id: &str,
+        guess: LatLng,
+    ) -> Result<bool, UserLookupError> {
         let mut storage_guard = self.storage.write().await;
-        let room = storage_guard.get_mut(room_id).unwrap();
+        let room = storage_guard
+            .get_mut(room_id)
+            .ok_or(UserLookupError::RoomNotFound)?;
+        let room_status = room.status;
         room.users
             .iter_mut()
             .find(|user| user.private_id == *private_user_id)
-            .unwrap()
-            .submit_guess(guess, room.status);
-        room.users.iter().all(|user| user.submitted_guess)
+            .ok_or(UserLookupError::UserNotFound)?
+            .submit_guess(guess, room_status);
+        room.touch();
+        Ok(room.all_players_submitted_guesses())
     }
 
-    async fn revoke_guess(&self, room_id: &str, private_user_id: &str) {
-        self.storage
-            .write()
-            .await
+    async fn revoke_guess(
+        &self,
+        room_id: &str,
+        private_user_id: &str,
+    ) -> Result<(), UserLookupError> {
+        let mut storage_guard = self.storage.write().await;
+        let room = storage_guard
             .get_mut(room_id)
-            .unwrap()
-            .users
+            .ok_or(UserLookupError::RoomNotFound)?;
+        room.users
             .iter_mut()
             .find(|user| user.private_id == *private_user_id)
-            .unwrap()
+            .ok_or(UserLookupError::UserNotFound)?
             .revoke_guess();
+        Ok(())
     }
 }
 
 impl UserPermissionsRepo for HashMapRoomsStorage {
-    async fn mute(&self, room_id: &str, target_user_public_id: &str) {
+    async fn mute(&self, room_id: &str, target_user_public_id: &str) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(user) = storage_guard.get_mut(room_id).and_then(|room| {
+            room.users
+                .iter_mut()
+                .find(|user| user.public_id == *target_user_public_id)
+        }) else {
+            return false;
+        };
+        user.mute();
+        true
+    }
+
+    async fn unmute(&self, room_id: &str, target_user_public_id: &str) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(user) = storage_guard.get_mut(room_id).and_then(|room| {
+            room.users
+                .iter_mut()
+                .find(|user| user.public_id == *target_user_public_id)
+        }) else {
+            return false;
+        };
+        user.unmute();
+        true
+    }
+
+    async fn kick(&self, room_id: &str, target_user_public_id: &str) -> Option<RemovedUser> {
+        let mut storage_guard = self.storage.write().await;
+        let room = storage_guard.get_mut(room_id)?;
+        RemovedUser::kicked_from(room_id, room, target_user_public_id)
+    }
+
+    async fn kick_everywhere(&self, target_user_public_id: &str) -> Vec<RemovedUser> {
         self.storage
             .write()
             .await
-            .get_mut(room_id)
-            .unwrap()
-            .users
             .iter_mut()
-            .find(|user| user.public_id == *target_user_public_id)
-            .unwrap()
-            .mute();
+            .filter_map(|(room_id, room)| {
+                RemovedUser::kicked_from(room_id, room, target_user_public_id)
+            })
+            .collect()
     }
 
-    async fn unmute(&self, room_id: &str, target_user_public_id: &str) {
+    async fn ban(
+        &self,
+        room_id: &str,
+        target_user_public_id: &str,
+        duration: Option<Duration>,
+    ) -> Option<Option<User>> {
         self.storage
             .write()
             .await
             .get_mut(room_id)
-            .unwrap()
-            .users
-            .iter_mut()
-            .find(|user| user.public_id == *target_user_public_id)
-            .unwrap()
-            .unmute();
+            .map(|room| room.ban_user(target_user_public_id, duration))
     }
 
-    async fn ban(&self, room_id: &str, target_user_public_id: &str) {
+    async fn unban(&self, room_id: &str, target_user_public_id: &str) -> Option<bool> {
         self.storage
             .write()
             .await
             .get_mut(room_id)
-            .unwrap()
-            .ban_user(target_user_public_id)
+            .map(|room| room.unban_user(target_user_public_id))
+    }
+
+    async fn bans(&self, room_id: &str) -> Option<Vec<BannedUserInfo>> {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .map(Room::active_bans)
     }
 
     async fn is_muted(&self, room_id: &str, public_user_id: &str) -> bool {
+        self.storage.read().await.get(room_id).is_some_and(|room| {
+            room.users
+                .iter()
+                .any(|user| user.public_id == public_user_id && user.is_muted)
+        })
+    }
+
+    async fn has_permission(
+        &self,
+        room_id: &str,
+        public_user_id: &str,
+        permission: Permission,
+    ) -> bool {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .is_some_and(|room| room.has_permission(public_user_id, permission))
+    }
+
+    async fn permissions(&self, room_id: &str, public_user_id: &str) -> HashSet<Permission> {
         self.storage
             .read()
             .await
             .get(room_id)
-            .unwrap()
+            .map(|room| room.permissions_of(public_user_id))
+            .unwrap_or_default()
+    }
+
+    async fn transfer_host(&self, room_id: &str, new_host_public_id: &str) -> Option<bool> {
+        self.storage
+            .write()
+            .await
+            .get_mut(room_id)
+            .map(|room| room.transfer_host(new_host_public_id))
+    }
+
+    async fn set_role(
+        &self,
+        room_id: &str,
+        target_user_public_id: &str,
+        role: UserRole,
+    ) -> Option<bool> {
+        let mut storage_guard = self.storage.write().await;
+        let target_user = storage_guard
+            .get_mut(room_id)?
             .users
-            .iter()
-            .find(|user| user.public_id == public_user_id)
-            .map_or(false, |user| user.is_muted)
+            .iter_mut()
+            .find(|user| user.public_id == target_user_public_id);
+        match target_user {
+            Some(user) => {
+                user.role = role;
+                Some(true)
+            }
+            None => Some(false),
+        }
+    }
+
+    async fn set_co_host_permissions(
+        &self,
+        room_id: &str,
+        permissions: HashSet<Permission>,
+    ) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(room) = storage_guard.get_mut(room_id) else {
+            return false;
+        };
+        room.co_host_permissions = permissions;
+        true
     }
 
     async fn is_banned(&self, room_id: &str, public_user_id: &str) -> bool {
@@ -505,35 +784,177 @@ impl UserPermissionsRepo for HashMapRoomsStorage {
             .read()
             .await
             .get(room_id)
-            .unwrap()
-            .banned_public_users_ids
-            .iter()
-            .any(|public_id| public_id.as_str() == public_user_id)
+            .is_some_and(|room| room.is_banned(public_user_id))
     }
 }
 
 impl RoomInfoRepo for HashMapRoomsStorage {
-    async fn status(&self, room_id: &str) -> RoomStatus {
-        self.storage.read().await.get(room_id).unwrap().status
+    async fn summaries(&self) -> Vec<RoomSummary> {
+        let mut summaries = self
+            .storage
+            .read()
+            .await
+            .iter()
+            .map(|(room_id, room)| room.summary(room_id))
+            .collect::<Vec<_>>();
+        summaries.sort_by_key(|summary| std::cmp::Reverse(summary.users_count));
+        summaries
     }
 
-    async fn users(&self, room_id: &str) -> Vec<User> {
-        self.storage.read().await.get(room_id).unwrap().users()
+    async fn details(
+        &self,
+        room_id: &str,
+    ) -> Option<(RoomSummary, Vec<User>, Vec<BannedUserInfo>)> {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .map(|room| (room.summary(room_id), room.users(), room.active_bans()))
     }
 
-    async fn messages(&self, room_id: &str) -> Vec<ChatMessage> {
+    async fn status(&self, room_id: &str) -> Option<RoomStatus> {
         self.storage
             .read()
             .await
             .get(room_id)
-            .unwrap()
-            .last_messages
-            .iter()
+            .map(|room| room.status)
+    }
+
+    async fn users(&self, room_id: &str) -> Option<Vec<User>> {
+        self.storage.read().await.get(room_id).map(Room::users)
+    }
+
+    async fn messages(
+        &self,
+        room_id: &str,
+        after: Option<usize>,
+        before: Option<usize>,
+        limit: usize,
+    ) -> Option<(Vec<ChatMessage>, bool)> {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .map(|room| room.messages_page(after, before, limit))
+    }
+}
+
+impl RoomAttachmentsRepo for HashMapRoomsStorage {
+    async fn attachment_ids_in_use(&self) -> HashSet<String> {
+        self.storage
+            .read()
+            .await
+            .values()
+            .flat_map(|room| room.chat_history.iter())
+            .flat_map(|message| message.attachment_ids())
             .cloned()
             .collect()
     }
 }
 
+impl RoomAccessRepo for HashMapRoomsStorage {
+    async fn access(&self, room_id: &str) -> Option<RoomAccess> {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .map(|room| room.access.clone())
+    }
+
+    async fn set_access(&self, room_id: &str, access: RoomAccess) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(room) = storage_guard.get_mut(room_id) else {
+            return false;
+        };
+        room.touch();
+        room.access = access;
+        true
+    }
+
+    async fn set_max_players(&self, room_id: &str, max_players: usize) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(room) = storage_guard.get_mut(room_id) else {
+            return false;
+        };
+        room.touch();
+        room.max_players = max_players;
+        true
+    }
+
+    async fn set_locked(&self, room_id: &str, is_locked: bool) -> bool {
+        let mut storage_guard = self.storage.write().await;
+        let Some(room) = storage_guard.get_mut(room_id) else {
+            return false;
+        };
+        room.touch();
+        room.is_locked = is_locked;
+        true
+    }
+
+    async fn is_locked(&self, room_id: &str) -> bool {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .is_some_and(|room| room.is_locked)
+    }
+
+    async fn is_full(&self, room_id: &str) -> bool {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .is_some_and(|room| room.is_full())
+    }
+}
+
+impl RoomVotesRepo for HashMapRoomsStorage {
+    async fn start_vote(
+        &self,
+        room_id: &str,
+        initiator_public_id: &str,
+        subject: VoteSubject,
+        majority_percent: u64,
+    ) -> Result<VoteProgress, VoteStartError> {
+        self.storage
+            .write()
+            .await
+            .get_mut(room_id)
+            .ok_or(VoteStartError::RoomNotFound)?
+            .start_vote(initiator_public_id, subject, majority_percent)
+    }
+
+    async fn cast_vote(
+        &self,
+        room_id: &str,
+        voter_public_id: &str,
+        in_favor: bool,
+    ) -> Option<VoteProgress> {
+        self.storage
+            .write()
+            .await
+            .get_mut(room_id)?
+            .cast_vote(voter_public_id, in_favor)
+    }
+
+    async fn expire_vote(&self, room_id: &str, vote_id: usize) -> Option<VoteProgress> {
+        // The room might have been closed before the vote timed out.
+        self.storage
+            .write()
+            .await
+            .get_mut(room_id)?
+            .expire_vote(vote_id)
+    }
+
+    async fn skip_location(&self, room_id: &str) -> bool {
+        self.storage
+            .write()
+            .await
+            .get_mut(room_id)
+            .is_some_and(|room| room.skip_location())
+    }
+}
+
 fn generate_room_id() -> String {
     rand::thread_rng()
         .sample_iter(&Alphanumeric)
@@ -544,5 +965,116 @@ fn generate_room_id() -> String {
 
 pub enum UserConnectedResult {
     NewUser,
-    AlreadyInTheRoom,
+    /// `came_back` is set if the user was away and is back within the grace period.
+    AlreadyInTheRoom {
+        came_back: bool,
+    },
+}
+
+/// Why something couldn't be done for one of the users of a room, e.g. after they were kicked.
+#[derive(Debug, PartialEq)]
+pub enum UserLookupError {
+    RoomNotFound,
+    UserNotFound,
+}
+
+/// A room taken out of the storage, with the sockets of the users that were still in it.
+pub struct RemovedRoom {
+    pub room_id: String,
+    pub socket_ids: Vec<Option<usize>>,
+}
+
+/// A user removed from one of the rooms they were in.
+pub struct RemovedUser {
+    pub room_id: String,
+    pub user: User,
+    /// Set if the user was the host and someone else took over.
+    pub new_host_public_id: Option<String>,
+}
+
+impl RemovedUser {
+    fn kicked_from(room_id: &str, room: &mut Room, target_user_public_id: &str) -> Option<Self> {
+        let user = room.kick_user(target_user_public_id)?;
+        let new_host_public_id = if user.is_host() {
+            room.reassign_host()
+        } else {
+            None
+        };
+        Some(Self {
+            room_id: room_id.to_string(),
+            user,
+            new_host_public_id,
+        })
+    }
+}
+
+impl ChatMessagesRepo for HashMapRoomsStorage {
+    async fn has_message(&self, room_id: &str, message_id: usize) -> bool {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .is_some_and(|room| room.has_message(message_id))
+    }
+
+    async fn toggle_reaction(
+        &self,
+        room_id: &str,
+        message_id: usize,
+        user_public_id: &str,
+        emoji: &str,
+    ) -> Result<(bool, usize), MessageModificationError> {
+        self.storage
+            .write()
+            .await
+            .get_mut(room_id)
+            .ok_or(MessageModificationError::RoomNotFound)?
+            .toggle_reaction(message_id, user_public_id, emoji)
+    }
+
+    async fn edit_message(
+        &self,
+        room_id: &str,
+        message_id: usize,
+        editor_public_id: &str,
+        content: String,
+    ) -> Result<(), MessageModificationError> {
+        self.storage
+            .write()
+            .await
+            .get_mut(room_id)
+            .ok_or(MessageModificationError::RoomNotFound)?
+            .edit_message(message_id, editor_public_id, content)
+    }
+
+    async fn delete_message(
+        &self,
+        room_id: &str,
+        message_id: usize,
+        requester_public_id: &str,
+        can_delete_any: bool,
+    ) -> Result<(), MessageModificationError> {
+        self.storage
+            .write()
+            .await
+            .get_mut(room_id)
+            .ok_or(MessageModificationError::RoomNotFound)?
+            .delete_message(message_id, requester_public_id, can_delete_any)
+    }
+}
+
+impl RoomSnapshotRepo for HashMapRoomsStorage {
+    async fn snapshot(&self, room_id: &str) -> Option<RoomSnapshot> {
+        self.storage
+            .read()
+            .await
+            .get(room_id)
+            .map(RoomSnapshot::from)
+    }
+
+    async fn restore(&self, room: Room) -> String {
+        let room_id = generate_room_id();
+        self.storage.write().await.insert(room_id.clone(), room);
+        room_id
+    }
 }
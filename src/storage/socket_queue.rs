@@ -0,0 +1,170 @@
+use crate::cli::Args;
+use crate::rooms::message_types::ServerSentSocketMessage;
+use axum::extract::ws::Message;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::Notify;
+
+static SOCKET_QUEUE_SETTINGS: OnceLock<SocketQueueSettings> = OnceLock::new();
+
+#[derive(Copy, Clone, Debug)]
+pub struct SocketQueueSettings {
+    pub capacity: usize,
+    pub slow_client_policy: SlowClientPolicy,
+}
+
+pub fn init(args: &Args) {
+    SOCKET_QUEUE_SETTINGS.get_or_init(|| SocketQueueSettings {
+        capacity: args.socket_queue_capacity.max(1),
+        slow_client_policy: args.slow_client_policy,
+    });
+}
+
+pub fn settings() -> SocketQueueSettings {
+    *SOCKET_QUEUE_SETTINGS
+        .get()
+        .expect("Somehow socket queue settings are used before `init`.")
+}
+
+/// What to do when a client doesn't read its messages as fast as they are sent. Each policy also
+/// does what the previous ones do; clients are disconnected once there is nothing left to drop.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum SlowClientPolicy {
+    /// Disconnect the client as soon as its queue is full.
+    Disconnect,
+    /// Make room in a full queue by dropping `Tick`s, which are outdated by the newer ones anyway.
+    DropStaleTicks,
+    /// Keep only the latest of the queued messages that update the same piece of state.
+    Coalesce,
+}
+
+/// The piece of state a message updates, for messages that make the previous ones of the same
+/// kind obsolete. Coalesced messages leave gaps in `seq`, same as the events meant for others.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CoalescingKey {
+    Tick,
+    Presence { public_id: String },
+}
+
+impl CoalescingKey {
+    pub fn of(ws_message: &ServerSentSocketMessage) -> Option<Self> {
+        match ws_message {
+            ServerSentSocketMessage::Tick { .. } => Some(Self::Tick),
+            ServerSentSocketMessage::PresenceChanged { payload, .. } => Some(Self::Presence {
+                public_id: payload.public_id.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct QueuedMessage {
+    message: Message,
+    coalescing_key: Option<CoalescingKey>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EnqueueError {
+    /// The client has fallen too far behind and has to be disconnected.
+    Overflow,
+    Closed,
+}
+
+/// Messages waiting to be written to a socket.
+pub struct SocketQueue {
+    messages: Mutex<VecDeque<QueuedMessage>>,
+    settings: SocketQueueSettings,
+    is_closed: AtomicBool,
+    /// Wakes up the task that writes to the socket.
+    message_queued: Notify,
+    /// Wakes up the task that reads from the socket, so that it runs the disconnection logic.
+    disconnect_requested: Notify,
+}
+
+impl SocketQueue {
+    pub fn new(settings: SocketQueueSettings) -> Self {
+        Self {
+            messages: Mutex::new(VecDeque::with_capacity(settings.capacity)),
+            settings,
+            is_closed: AtomicBool::new(false),
+            message_queued: Notify::new(),
+            disconnect_requested: Notify::new(),
+        }
+    }
+
+    pub fn push(
+        &self,
+        message: Message,
+        coalescing_key: Option<CoalescingKey>,
+    ) -> Result<(), EnqueueError> {
+        if self.is_closed.load(Ordering::Relaxed) {
+            return Err(EnqueueError::Closed);
+        }
+        let mut messages = self.messages.lock().unwrap();
+        let policy = self.settings.slow_client_policy;
+        if policy == SlowClientPolicy::Coalesce && coalescing_key.is_some() {
+            messages.retain(|queued| queued.coalescing_key != coalescing_key);
+        }
+        if messages.len() >= self.settings.capacity && policy != SlowClientPolicy::Disconnect {
+            let oldest_tick_index = messages
+                .iter()
+                .position(|queued| queued.coalescing_key == Some(CoalescingKey::Tick));
+            if let Some(oldest_tick_index) = oldest_tick_index {
+                messages.remove(oldest_tick_index);
+            }
+        }
+        if messages.len() >= self.settings.capacity {
+            return Err(EnqueueError::Overflow);
+        }
+        messages.push_back(QueuedMessage {
+            message,
+            coalescing_key,
+        });
+        drop(messages);
+        self.message_queued.notify_one();
+        Ok(())
+    }
+
+    /// Queues a message that must not be dropped, such as a close frame, even if the queue is full.
+    pub fn push_control(&self, message: Message) {
+        self.messages.lock().unwrap().push_back(QueuedMessage {
+            message,
+            coalescing_key: None,
+        });
+        self.message_queued.notify_one();
+    }
+
+    /// Waits for the next message; `None` once the queue is closed.
+    pub async fn pop(&self) -> Option<Message> {
+        loop {
+            if self.is_closed.load(Ordering::Relaxed) {
+                return None;
+            }
+            if let Some(queued) = self.messages.lock().unwrap().pop_front() {
+                return Some(queued.message);
+            }
+            self.message_queued.notified().await;
+        }
+    }
+
+    /// Drops the queued messages and asks the socket's tasks to wrap up, as if the client went
+    /// away on its own.
+    pub fn disconnect(&self) {
+        self.is_closed.store(true, Ordering::Relaxed);
+        self.messages.lock().unwrap().clear();
+        self.message_queued.notify_one();
+        self.disconnect_requested.notify_one();
+    }
+
+    pub async fn disconnect_requested(&self) {
+        self.disconnect_requested.notified().await;
+    }
+
+    pub fn depth(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+}
@@ -1,60 +1,176 @@
+use crate::rooms::message_types::{self, RemovedFromRoomPayload, ServerSentSocketMessage};
+pub use crate::storage::broker::Audience;
+use crate::storage::broker::{InstanceId, LocalRoomBroker, RoomBroadcast, RoomBroker};
+use crate::storage::socket_queue::{self, CoalescingKey, EnqueueError, SocketQueue};
 use axum::extract::ws::Message;
+use futures_util::StreamExt;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::RwLock;
+use tokio::task::AbortHandle;
 
 pub static NEXT_SOCKET_ID: AtomicUsize = AtomicUsize::new(1);
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct HashMapClientSocketsStorage {
-    storage: Arc<RwLock<HashMap<usize, mpsc::UnboundedSender<Message>>>>,
+    storage: Arc<RwLock<HashMap<usize, Arc<SocketQueue>>>>,
+    instance_id: InstanceId,
+    broker: Arc<dyn RoomBroker>,
+    /// Tasks that forward room broadcasts to the sockets subscribed to them.
+    subscriptions: Arc<RwLock<HashMap<usize, AbortHandle>>>,
+}
+
+impl Default for HashMapClientSocketsStorage {
+    fn default() -> Self {
+        Self::with_broker(Arc::new(LocalRoomBroker::default()))
+    }
 }
 
 impl HashMapClientSocketsStorage {
-    pub async fn add(&self, socket: mpsc::UnboundedSender<Message>) -> usize {
+    pub fn with_broker(broker: Arc<dyn RoomBroker>) -> Self {
+        Self {
+            storage: Arc::default(),
+            instance_id: InstanceId::generate(),
+            broker,
+            subscriptions: Arc::default(),
+        }
+    }
+
+    /// Returns the new socket's ID and the queue of messages to write to it.
+    pub async fn add(&self) -> (usize, Arc<SocketQueue>) {
         let socket_id = NEXT_SOCKET_ID.fetch_add(1, Ordering::Relaxed);
-        self.storage.write().await.insert(socket_id, socket);
-        socket_id
+        let queue = Arc::new(SocketQueue::new(socket_queue::settings()));
+        self.storage.write().await.insert(socket_id, queue.clone());
+        (socket_id, queue)
     }
 
     pub async fn remove(&self, socket_id: usize) {
-        self.storage.write().await.remove(&socket_id);
+        self.unsubscribe(socket_id).await;
+        if let Some(queue) = self.storage.write().await.remove(&socket_id) {
+            // Lets the writer task finish.
+            queue.disconnect();
+        }
     }
 
-    pub async fn send_msg(&self, msg: &str, socket_id: usize) {
-        if let Err(_disconnected) = self
-            .storage
-            .read()
+    /// Starts forwarding the room's broadcasts to the socket, instead of any other room's.
+    pub async fn subscribe(&self, room_id: &str, socket_id: usize) {
+        let Some(queue) = self.storage.read().await.get(&socket_id).cloned() else {
+            return;
+        };
+        let mut broadcasts = self.broker.subscribe(room_id);
+        let instance_id = self.instance_id;
+        let forwarding_task = tokio::spawn(async move {
+            while let Some(broadcast) = broadcasts.next().await {
+                if !broadcast.is_for(instance_id, socket_id) {
+                    continue;
+                }
+                let message = Message::Text(broadcast.raw_msg.to_string());
+                if let Err(error) = queue.push(message, broadcast.coalescing_key) {
+                    on_enqueue_error(&queue, socket_id, error);
+                    return;
+                }
+            }
+        });
+        let previous_subscription = self
+            .subscriptions
+            .write()
             .await
-            .get(&socket_id)
-            .unwrap()
-            .send(Message::from(msg))
-        {
-            // The tx is disconnected, our `user_disconnected` code
-            // should be happening in another task, nothing more to
-            // do here.
-            eprintln!("[user_message]: error sending pong to user: {socket_id:?}")
+            .insert(socket_id, forwarding_task.abort_handle());
+        if let Some(previous_subscription) = previous_subscription {
+            previous_subscription.abort();
         }
     }
 
-    // TODO: refactor so that `msg` is a struct or enum, not `&str`
-    pub async fn broadcast_msg(&self, msg: &str, sockets_ids: &[Option<usize>]) {
-        for (&uid, tx) in self.storage.read().await.iter() {
-            if sockets_ids.contains(&Some(uid)) {
-                if let Err(_disconnected) = tx.send(Message::from(msg)) {
-                    // The tx is disconnected, our `user_disconnected` code
-                    // should be happening in another task, nothing more to
-                    // do here.
-                    eprintln!(
-                        "[user_message]: error broadcasting message {msg} to user ith id {uid:?}"
-                    );
-                }
-            }
+    pub async fn unsubscribe(&self, socket_id: usize) {
+        if let Some(subscription) = self.subscriptions.write().await.remove(&socket_id) {
+            subscription.abort();
         }
     }
 
+    /// Sends the message to the sockets subscribed to the room. Slow clients may only get the
+    /// latest of the messages with the same `coalescing_key`.
+    pub async fn publish(
+        &self,
+        room_id: &str,
+        raw_msg: String,
+        audience: Audience,
+        coalescing_key: Option<CoalescingKey>,
+    ) {
+        let broadcast = RoomBroadcast {
+            raw_msg: Arc::from(raw_msg),
+            origin: self.instance_id,
+            audience,
+            coalescing_key,
+        };
+        self.broker.publish(room_id, broadcast);
+    }
+
+    /// Drops the room's channel; the subscribers still get what has been published before.
+    pub async fn close_room(&self, room_id: &str) {
+        self.broker.close_room(room_id);
+    }
+
+    pub async fn send_msg(&self, msg: &str, socket_id: usize) {
+        let Some(queue) = self.storage.read().await.get(&socket_id).cloned() else {
+            // The socket is gone, our `user_disconnected` code should be happening in another
+            // task, nothing more to do here.
+            eprintln!("[user_message]: error sending message to user: {socket_id:?}");
+            return;
+        };
+        if let Err(error) = queue.push(Message::from(msg), None) {
+            on_enqueue_error(&queue, socket_id, error);
+        }
+    }
+
+    /// Asks the socket's writer task to send a close frame, after which the socket is dropped
+    /// without waiting for the client to hang up, running the usual disconnection logic.
+    pub async fn close(&self, socket_id: usize) {
+        if let Some(queue) = self.storage.read().await.get(&socket_id) {
+            queue.push_control(Message::Close(None));
+        }
+    }
+
+    /// How many messages are waiting to be written to each of the sockets.
+    pub async fn queue_depths(&self) -> Vec<(usize, usize)> {
+        self.storage
+            .read()
+            .await
+            .iter()
+            .map(|(&socket_id, queue)| (socket_id, queue.depth()))
+            .collect()
+    }
+
+    /// Tells the user why they were removed from the room and closes their socket.
+    pub async fn remove_from_room(&self, socket_id: usize, payload: RemovedFromRoomPayload) {
+        self.unsubscribe(socket_id).await;
+        let ws_msg = ServerSentSocketMessage::RemovedFromRoom {
+            r#type: message_types::RemovedFromRoom,
+            payload,
+        };
+        let raw_ws_msg = serde_json::to_string(&ws_msg).unwrap();
+        self.send_msg(&raw_ws_msg, socket_id).await;
+        self.close(socket_id).await;
+    }
+
+    pub async fn ids(&self) -> Vec<usize> {
+        self.storage.read().await.keys().copied().collect()
+    }
+
     pub async fn count(&self) -> usize {
         self.storage.read().await.len()
     }
 }
+
+fn on_enqueue_error(queue: &SocketQueue, socket_id: usize, error: EnqueueError) {
+    match error {
+        EnqueueError::Overflow => {
+            eprintln!(
+                "[user_message]: socket {socket_id} has fallen too far behind, disconnecting"
+            );
+            queue.disconnect();
+        }
+        // The socket is being closed, `remove` is about to unsubscribe it.
+        EnqueueError::Closed => {}
+    }
+}
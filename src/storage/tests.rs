@@ -0,0 +1,198 @@
+use crate::app_context::tests::{test_app_context, TestSocket};
+use crate::rooms::message_types::{
+    AnnouncementBotMessagePayload, AnnouncementBotMsg, BotMessagePayload, BriefUserInfoPayload,
+};
+use crate::rooms::models::ChatMessage;
+use crate::storage::broker::{Audience, InstanceId, LocalRoomBroker, RoomBroadcast, RoomBroker};
+use crate::storage::interface::{RoomConnectionHandler, RoomInfoRepo, RoomRepo};
+use crate::storage::rooms::HashMapRoomsStorage;
+use crate::storage::socket_queue::{
+    CoalescingKey, EnqueueError, SlowClientPolicy, SocketQueue, SocketQueueSettings,
+};
+use axum::extract::ws::Message;
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn queue(capacity: usize, slow_client_policy: SlowClientPolicy) -> SocketQueue {
+    SocketQueue::new(SocketQueueSettings {
+        capacity,
+        slow_client_policy,
+    })
+}
+
+#[tokio::test]
+async fn test_full_queue_drops_stale_ticks() {
+    let queue = queue(2, SlowClientPolicy::DropStaleTicks);
+    queue
+        .push(Message::from("tick 2"), Some(CoalescingKey::Tick))
+        .unwrap();
+    queue.push(Message::from("chat"), None).unwrap();
+
+    let result = queue.push(Message::from("tick 1"), Some(CoalescingKey::Tick));
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(queue.pop().await, Some(Message::from("chat")));
+    assert_eq!(queue.pop().await, Some(Message::from("tick 1")));
+}
+
+#[tokio::test]
+async fn test_coalesced_messages_keep_the_latest() {
+    let queue = queue(10, SlowClientPolicy::Coalesce);
+    let presence_key = || CoalescingKey::Presence {
+        public_id: String::from("player"),
+    };
+    queue
+        .push(Message::from("away"), Some(presence_key()))
+        .unwrap();
+    queue.push(Message::from("chat"), None).unwrap();
+    queue
+        .push(Message::from("online"), Some(presence_key()))
+        .unwrap();
+
+    assert_eq!(queue.depth(), 2);
+    assert_eq!(queue.pop().await, Some(Message::from("chat")));
+    assert_eq!(queue.pop().await, Some(Message::from("online")));
+}
+
+#[test]
+fn test_full_queue_overflows_without_anything_to_drop() {
+    let queue = queue(1, SlowClientPolicy::Coalesce);
+    queue.push(Message::from("chat"), None).unwrap();
+
+    let result = queue.push(Message::from("another chat"), None);
+
+    assert_eq!(result, Err(EnqueueError::Overflow));
+}
+
+#[test]
+fn test_excluded_socket_id_only_applies_to_its_instance() {
+    let origin = InstanceId::generate();
+    let broadcast = RoomBroadcast {
+        raw_msg: Arc::from("{}"),
+        origin,
+        audience: Audience::EveryoneExcept(1),
+        coalescing_key: None,
+    };
+
+    assert!(!broadcast.is_for(origin, 1));
+    assert!(broadcast.is_for(origin, 2));
+    assert!(broadcast.is_for(InstanceId::generate(), 1));
+}
+
+#[tokio::test]
+async fn test_room_broadcasts_stream_ends_when_room_is_closed() {
+    let broker = LocalRoomBroker::default();
+    let mut broadcasts = broker.subscribe("someRoom");
+    let broadcast = |raw_msg: &str| RoomBroadcast {
+        raw_msg: Arc::from(raw_msg),
+        origin: InstanceId::generate(),
+        audience: Audience::Everyone,
+        coalescing_key: None,
+    };
+
+    broker.publish("someRoom", broadcast("{\"n\":1}"));
+    broker.publish("otherRoom", broadcast("{\"n\":2}"));
+    broker.close_room("someRoom");
+
+    let received = broadcasts.next().await.unwrap();
+    assert_eq!(&*received.raw_msg, "{\"n\":1}");
+    assert!(broadcasts.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_writes_to_closed_room_are_dropped() {
+    let rooms = HashMapRoomsStorage::default();
+    let room_id = rooms.create(None).await;
+    rooms.remove(&room_id).await;
+
+    let message = ChatMessage::from_bot(BotMessagePayload::Announcement {
+        r#type: AnnouncementBotMsg,
+        payload: AnnouncementBotMessagePayload {
+            content: String::from("Maintenance at noon"),
+        },
+    });
+
+    assert!(!rooms.add_message(&room_id, message).await);
+    assert!(rooms.details(&room_id).await.is_none());
+}
+
+#[tokio::test]
+async fn test_sweeper_keeps_idle_rooms_with_users_online() {
+    let rooms = HashMapRoomsStorage::default();
+    let empty_room_id = rooms.create(None).await;
+    let lobby_room_id = rooms.create(None).await;
+    let away_room_id = rooms.create(None).await;
+    for (room_id, socket_id) in [(&lobby_room_id, 1), (&away_room_id, 2)] {
+        rooms
+            .on_user_connected(
+                room_id,
+                BriefUserInfoPayload {
+                    username: String::from("player"),
+                    avatar_emoji: String::from("🦊"),
+                },
+                socket_id,
+                "playerPublicId",
+                "playerPrivateId",
+            )
+            .await
+            .unwrap();
+    }
+    rooms.disconnect_user(&away_room_id, 2).await;
+
+    let mut removed_room_ids = rooms
+        .delete_expired(Duration::ZERO, Duration::ZERO)
+        .await
+        .into_iter()
+        .map(|removed_room| removed_room.room_id)
+        .collect::<Vec<_>>();
+    removed_room_ids.sort();
+
+    let mut expected_room_ids = vec![empty_room_id, away_room_id];
+    expected_room_ids.sort();
+    assert_eq!(removed_room_ids, expected_room_ids);
+    assert!(rooms.exists(&lobby_room_id).await);
+}
+
+#[tokio::test]
+async fn test_sockets_get_broadcasts_of_their_current_room_only() {
+    let app_context = test_app_context();
+    let alice = TestSocket::open(&app_context, "someRoom", "alice").await;
+    let bob = TestSocket::open(&app_context, "someRoom", "bob").await;
+    app_context
+        .sockets
+        .subscribe("someRoom", alice.socket_id)
+        .await;
+    app_context
+        .sockets
+        .subscribe("someRoom", bob.socket_id)
+        .await;
+
+    app_context
+        .sockets
+        .publish(
+            "someRoom",
+            String::from(r#"{"type":"Hello"}"#),
+            Audience::EveryoneExcept(alice.socket_id),
+            None,
+        )
+        .await;
+    bob.expect_message("Hello").await;
+    assert!(alice.next_message().await.is_none());
+
+    app_context
+        .sockets
+        .subscribe("otherRoom", bob.socket_id)
+        .await;
+    app_context
+        .sockets
+        .publish(
+            "someRoom",
+            String::from(r#"{"type":"Bye"}"#),
+            Audience::Everyone,
+            None,
+        )
+        .await;
+    alice.expect_message("Bye").await;
+    assert!(bob.next_message().await.is_none());
+}
@@ -1,2 +1,3 @@
 pub const PREVIEW_IMAGE_WIDTH: u32 = 50;
 pub const PREVIEW_IMAGE_HEIGHT: u32 = 30;
+pub const ATTACHMENTS_GC_CONCURRENCY_LIMIT: usize = 16;
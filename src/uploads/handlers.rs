@@ -59,14 +59,20 @@ pub async fn attachment_links(
     Json(payload): Json<AttachmentLinkRequest>,
 ) -> Json<AttachmentLinksResponse> {
     let s3_client = S3Client::new();
-    let mut links = Vec::new();
+    let mut attachment_ids = Vec::new();
+    let mut keys = Vec::new();
 
     for attachment_id in payload.attachment_ids {
-        let key = match uuid::Uuid::parse_str(&attachment_id) {
-            Ok(uuid) => S3ObjectKey::from_uuid(uuid),
-            Err(_) => continue,
-        };
-        match s3_client.generate_presigned_url(&key).await {
+        if let Ok(uuid) = uuid::Uuid::parse_str(&attachment_id) {
+            keys.push(S3ObjectKey::from_uuid(uuid));
+            attachment_ids.push(attachment_id);
+        }
+    }
+
+    let mut links = Vec::new();
+    let presigned_urls = s3_client.generate_presigned_urls(keys).await;
+    for (attachment_id, presigned_url) in attachment_ids.into_iter().zip(presigned_urls) {
+        match presigned_url {
             Ok((full, preview)) => links.push(AttachmentLink { full, preview }),
             Err(err) => {
                 eprintln!(
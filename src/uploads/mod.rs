@@ -1,8 +1,11 @@
+use crate::app_context::AppContext;
 use crate::cli::Args;
+use crate::storage::rooms::HashMapRoomsStorage;
 use crate::uploads::env::{
     AWS_ACCESS_KEY_ID, AWS_ENDPOINT_URL, AWS_REGION, AWS_SECRET_ACCESS_KEY, S3_FORCE_PATH_STYLE,
 };
 use crate::warn_if_env_var_is_missing;
+use std::time::Duration;
 
 pub mod consts;
 pub mod env;
@@ -10,12 +13,26 @@ pub mod handlers;
 pub mod img;
 pub mod requests;
 pub mod responses;
+pub mod retention;
 pub mod s3;
+#[cfg(test)]
+pub mod tests;
 
-pub fn init(_args: &Args) {
+pub fn init(args: &Args, app_context: AppContext<HashMapRoomsStorage>) {
     warn_if_env_var_is_missing!(AWS_ACCESS_KEY_ID, "Image uploads won't work.");
     warn_if_env_var_is_missing!(AWS_SECRET_ACCESS_KEY, "Image uploads won't work.");
     warn_if_env_var_is_missing!(AWS_REGION, "Image uploads won't work.");
     warn_if_env_var_is_missing!(AWS_ENDPOINT_URL, "Will use global AWS S3.");
     warn_if_env_var_is_missing!(S3_FORCE_PATH_STYLE, "Will use global AWS S3.");
+
+    if let Some(retention_hours) = args.attachments_retention_hours {
+        let policy = retention::AttachmentsRetentionPolicy {
+            max_age: Duration::from_secs(retention_hours * 60 * 60),
+            interval: Duration::from_secs(args.attachments_gc_interval_secs),
+            dry_run: args.attachments_gc_dry_run,
+        };
+        retention::spawn(policy, app_context);
+    } else {
+        tracing::info!("Attachments retention period isn't set, uploads will be kept forever.");
+    }
 }
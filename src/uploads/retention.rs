@@ -0,0 +1,139 @@
+use crate::app_context::AppContext;
+use crate::storage::interface::RoomAttachmentsRepo;
+use crate::storage::rooms::HashMapRoomsStorage;
+use crate::uploads::consts::ATTACHMENTS_GC_CONCURRENCY_LIMIT;
+use crate::uploads::s3::{S3Client, S3ObjectKey, S3ObjectSummary};
+use futures_util::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+
+pub struct AttachmentsRetentionPolicy {
+    /// How long an attachment must stay unreferenced by any room before it gets deleted.
+    pub max_age: Duration,
+    /// How often the bucket gets scanned.
+    pub interval: Duration,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct RetentionReport {
+    pub scanned_objects: usize,
+    pub attachments_in_use: usize,
+    pub expired_attachment_ids: Vec<String>,
+    pub deleted_objects: usize,
+    pub failed_deletions: usize,
+}
+
+pub fn spawn(policy: AttachmentsRetentionPolicy, app_context: AppContext<HashMapRoomsStorage>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(policy.interval).await;
+            match collect_garbage(&policy, &app_context).await {
+                Some(report) if policy.dry_run => tracing::info!(
+                    scanned_objects = report.scanned_objects,
+                    attachments_in_use = report.attachments_in_use,
+                    expired_attachment_ids = ?report.expired_attachment_ids,
+                    "Attachments garbage collection dry run: {} attachments would be deleted.",
+                    report.expired_attachment_ids.len(),
+                ),
+                Some(report) => tracing::info!(
+                    scanned_objects = report.scanned_objects,
+                    attachments_in_use = report.attachments_in_use,
+                    deleted_objects = report.deleted_objects,
+                    failed_deletions = report.failed_deletions,
+                    "Attachments garbage collection deleted {} attachments.",
+                    report.expired_attachment_ids.len(),
+                ),
+                None => {}
+            }
+        }
+    });
+}
+
+pub async fn collect_garbage(
+    policy: &AttachmentsRetentionPolicy,
+    app_context: &AppContext<HashMapRoomsStorage>,
+) -> Option<RetentionReport> {
+    let s3_client = S3Client::new();
+    let objects = match s3_client.list_objects().await {
+        Ok(objects) => objects,
+        Err(err) => {
+            tracing::error!("Failed to list uploaded attachments: {err:?}");
+            return None;
+        }
+    };
+    // Has to be queried after listing the bucket: an image uploaded in between would otherwise
+    // look unreferenced. The age threshold covers images whose message wasn't sent yet.
+    let attachment_ids_in_use = app_context.rooms.attachment_ids_in_use().await;
+    let (expired_attachment_ids, expired_keys) = find_expired(
+        &objects,
+        &attachment_ids_in_use,
+        policy.max_age,
+        SystemTime::now(),
+    );
+
+    let mut report = RetentionReport {
+        scanned_objects: objects.len(),
+        attachments_in_use: attachment_ids_in_use.len(),
+        expired_attachment_ids,
+        ..Default::default()
+    };
+    if policy.dry_run {
+        return Some(report);
+    }
+
+    let deletion_results = stream::iter(expired_keys)
+        .map(|key| {
+            let s3_client = &s3_client;
+            async move { (s3_client.delete_object(&key).await, key) }
+        })
+        .buffer_unordered(ATTACHMENTS_GC_CONCURRENCY_LIMIT)
+        .collect::<Vec<_>>()
+        .await;
+    for (result, key) in deletion_results {
+        match result {
+            Ok(_) => report.deleted_objects += 1,
+            Err(err) => {
+                tracing::error!("Failed to delete attachment {}: {err:?}", key.as_ref());
+                report.failed_deletions += 1;
+            }
+        }
+    }
+    Some(report)
+}
+
+/// Returns the IDs of the attachments that are unreferenced and older than `max_age`, along with
+/// the keys of all of their objects.
+pub fn find_expired(
+    objects: &[S3ObjectSummary],
+    attachment_ids_in_use: &HashSet<String>,
+    max_age: Duration,
+    now: SystemTime,
+) -> (Vec<String>, Vec<S3ObjectKey>) {
+    // Group the original images with their previews so that both are deleted together.
+    let mut objects_by_attachment_id = HashMap::<String, Vec<(S3ObjectKey, SystemTime)>>::new();
+    for object in objects.iter() {
+        objects_by_attachment_id
+            .entry(object.key.attachment_id().to_string())
+            .or_default()
+            .push((object.key.clone(), object.last_modified));
+    }
+
+    let mut expired_attachment_ids = Vec::new();
+    let mut expired_keys = Vec::new();
+    for (attachment_id, objects) in objects_by_attachment_id {
+        if attachment_ids_in_use.contains(&attachment_id) {
+            continue;
+        }
+        let all_objects_expired = objects.iter().all(|(_key, last_modified)| {
+            now.duration_since(*last_modified)
+                .is_ok_and(|age| age > max_age)
+        });
+        if !all_objects_expired {
+            continue;
+        }
+        expired_attachment_ids.push(attachment_id);
+        expired_keys.extend(objects.into_iter().map(|(key, _last_modified)| key));
+    }
+    (expired_attachment_ids, expired_keys)
+}
@@ -3,14 +3,20 @@ use aws_config::environment::EnvironmentVariableCredentialsProvider;
 use aws_config::{BehaviorVersion, Region};
 use aws_sdk_s3::config::http::HttpResponse;
 use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::delete_object::{DeleteObjectError, DeleteObjectOutput};
 use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error;
 use aws_sdk_s3::operation::put_object::{PutObjectError, PutObjectOutput};
 use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::{Client, Config};
+use futures_util::stream::{self, StreamExt};
+use std::collections::HashMap;
 use std::env;
 use std::sync::LazyLock;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+use tokio::sync::RwLock;
+use tokio::time::Instant;
 use uuid::Uuid;
 
 static S3_CLIENT: LazyLock<Client> = LazyLock::new(|| {
@@ -38,12 +44,27 @@ static S3_CLIENT: LazyLock<Client> = LazyLock::new(|| {
     Client::from_conf(config)
 });
 
+/// Presigned URLs that are still valid, keyed by the S3 object key they were generated for.
+static PRESIGNED_URLS_CACHE: LazyLock<RwLock<HashMap<String, CachedPresignedUrl>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Debug, Clone)]
+struct CachedPresignedUrl {
+    url: String,
+    /// The moment after which the URL should no longer be handed out to clients, set slightly
+    /// before the actual expiration so that clients have time to use it.
+    reusable_until: Instant,
+}
+
 #[derive(Debug)]
 pub struct S3Client {}
 
 impl S3Client {
     const BUCKET_NAME: &str = "ert-chat-message-images";
     const PRESIGNED_URL_DURATION: Duration = Duration::from_secs(3600);
+    /// How long before the expiration a cached presigned URL stops being reused.
+    const PRESIGNED_URL_REUSE_MARGIN: Duration = Duration::from_secs(300);
+    const PRESIGNING_CONCURRENCY_LIMIT: usize = 16;
 
     pub fn new() -> Self {
         Self {}
@@ -64,35 +85,122 @@ impl S3Client {
             .await
     }
 
+    /// Lists all objects in the bucket, following the pagination until the end.
+    pub async fn list_objects(
+        &self,
+    ) -> Result<Vec<S3ObjectSummary>, SdkError<ListObjectsV2Error, HttpResponse>> {
+        let mut objects = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let page = S3_CLIENT
+                .list_objects_v2()
+                .bucket(Self::BUCKET_NAME)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await?;
+            for object in page.contents() {
+                let Some(key) = object.key() else {
+                    continue;
+                };
+                let last_modified = object
+                    .last_modified()
+                    .and_then(|last_modified| SystemTime::try_from(*last_modified).ok())
+                    .unwrap_or_else(SystemTime::now);
+                objects.push(S3ObjectSummary {
+                    key: S3ObjectKey(key.to_string()),
+                    last_modified,
+                });
+            }
+            match page.next_continuation_token() {
+                Some(token) if page.is_truncated().unwrap_or(false) => {
+                    continuation_token = Some(token.to_string());
+                }
+                _ => break,
+            }
+        }
+        Ok(objects)
+    }
+
+    pub async fn delete_object(
+        &self,
+        key: &S3ObjectKey,
+    ) -> Result<DeleteObjectOutput, SdkError<DeleteObjectError, HttpResponse>> {
+        PRESIGNED_URLS_CACHE.write().await.remove(key.as_ref());
+        S3_CLIENT
+            .delete_object()
+            .bucket(Self::BUCKET_NAME)
+            .key(key.as_ref())
+            .send()
+            .await
+    }
+
     pub async fn generate_presigned_url(
         &self,
         key: &S3ObjectKey,
     ) -> Result<(String, String), SdkError<GetObjectError, HttpResponse>> {
+        let preview_key = S3ObjectKey::preview_for(key);
+        let (original_attachment_url, preview_attachment_url) = tokio::try_join!(
+            self.presign_get_object(key),
+            self.presign_get_object(&preview_key),
+        )?;
+        Ok((original_attachment_url, preview_attachment_url))
+    }
+
+    /// Generates presigned URLs for many objects at once, running at most
+    /// `PRESIGNING_CONCURRENCY_LIMIT` presigning operations concurrently. The results are returned
+    /// in the same order as `keys`.
+    pub async fn generate_presigned_urls(
+        &self,
+        keys: Vec<S3ObjectKey>,
+    ) -> Vec<Result<(String, String), SdkError<GetObjectError, HttpResponse>>> {
+        evict_expired_presigned_urls().await;
+        stream::iter(keys)
+            .map(|key| async move { self.generate_presigned_url(&key).await })
+            .buffered(Self::PRESIGNING_CONCURRENCY_LIMIT)
+            .collect()
+            .await
+    }
+
+    async fn presign_get_object(
+        &self,
+        key: &S3ObjectKey,
+    ) -> Result<String, SdkError<GetObjectError, HttpResponse>> {
+        if let Some(cached) = PRESIGNED_URLS_CACHE.read().await.get(key.as_ref()) {
+            if cached.reusable_until > Instant::now() {
+                return Ok(cached.url.clone());
+            }
+        }
         let presigning_config = PresigningConfig::expires_in(Self::PRESIGNED_URL_DURATION)
             .expect("Failed to create a presigning config.");
-        // TODO: Perform those two requests im parallel, or maybe there is a way to get presigned
-        // URLs in batches.
-        // TODO: Cache the responses for the duration of the presigned URL.
-        let original_attachment_url = S3_CLIENT
+        let presigned_at = Instant::now();
+        let url = S3_CLIENT
             .get_object()
             .bucket(Self::BUCKET_NAME)
             .key(key.as_ref())
-            .presigned(presigning_config.clone())
-            .await?
-            .uri()
-            .to_string();
-        let preview_attachment_url = S3_CLIENT
-            .get_object()
-            .bucket(Self::BUCKET_NAME)
-            .key(format!("{}-preview", key.as_ref()))
             .presigned(presigning_config)
             .await?
             .uri()
             .to_string();
-        Ok((original_attachment_url, preview_attachment_url))
+        PRESIGNED_URLS_CACHE.write().await.insert(
+            key.as_ref().to_string(),
+            CachedPresignedUrl {
+                url: url.clone(),
+                reusable_until: presigned_at + Self::PRESIGNED_URL_DURATION
+                    - Self::PRESIGNED_URL_REUSE_MARGIN,
+            },
+        );
+        Ok(url)
     }
 }
 
+async fn evict_expired_presigned_urls() {
+    let now = Instant::now();
+    PRESIGNED_URLS_CACHE
+        .write()
+        .await
+        .retain(|_key, cached| cached.reusable_until > now);
+}
+
 #[derive(Debug)]
 pub struct S3Object<'ct> {
     pub bytes: ByteStream,
@@ -101,19 +209,33 @@ pub struct S3Object<'ct> {
     pub content_type: &'ct str,
 }
 
+#[derive(Debug)]
+pub struct S3ObjectSummary {
+    pub key: S3ObjectKey,
+    pub last_modified: SystemTime,
+}
+
 #[derive(Debug, Clone)]
 pub struct S3ObjectKey(String);
 
 impl S3ObjectKey {
+    const PREVIEW_SUFFIX: &str = "-preview";
+
     pub fn random() -> Self {
         Self(Uuid::new_v4().to_string())
     }
 
     pub fn preview_for(key: &Self) -> Self {
-        let inner = format!("{}-preview", key.clone().into_inner());
+        let inner = format!("{}{}", key.clone().into_inner(), Self::PREVIEW_SUFFIX);
         Self(inner)
     }
 
+    /// The ID of the attachment the object belongs to, which is the same for the original image
+    /// and its preview.
+    pub fn attachment_id(&self) -> &str {
+        self.0.strip_suffix(Self::PREVIEW_SUFFIX).unwrap_or(&self.0)
+    }
+
     pub fn from_uuid(uuid: Uuid) -> Self {
         Self(uuid.to_string())
     }
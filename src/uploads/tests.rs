@@ -0,0 +1,99 @@
+use crate::app_context::tests::{test_app_context, test_room};
+use crate::rooms::models::ChatMessage;
+use crate::storage::consts::DEFAULT_MESSAGES_PAGE_SIZE;
+use crate::storage::interface::{RoomAttachmentsRepo, RoomInfoRepo, RoomRepo};
+use crate::uploads::retention::find_expired;
+use crate::uploads::s3::{S3ObjectKey, S3ObjectSummary};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+fn uploaded(attachment_id: &str, last_modified: SystemTime) -> Vec<S3ObjectSummary> {
+    let key = S3ObjectKey::from_uuid(Uuid::parse_str(attachment_id).unwrap());
+    vec![
+        S3ObjectSummary {
+            key: S3ObjectKey::preview_for(&key),
+            last_modified,
+        },
+        S3ObjectSummary { key, last_modified },
+    ]
+}
+
+fn message_with(attachment_ids: Vec<String>) -> ChatMessage {
+    ChatMessage::from_player(
+        String::from("alicePublicId"),
+        String::from("alice"),
+        String::from("Look at this"),
+        attachment_ids,
+        None,
+    )
+}
+
+#[tokio::test]
+async fn test_gc_keeps_attachments_referenced_from_older_history_pages() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let old_attachment_id = Uuid::new_v4().to_string();
+    let recent_attachment_id = Uuid::new_v4().to_string();
+    let orphan_attachment_id = Uuid::new_v4().to_string();
+    app_context
+        .rooms
+        .add_message(&room_id, message_with(vec![old_attachment_id.clone()]))
+        .await;
+    for _ in 0..DEFAULT_MESSAGES_PAGE_SIZE {
+        app_context
+            .rooms
+            .add_message(&room_id, message_with(vec![]))
+            .await;
+    }
+    app_context
+        .rooms
+        .add_message(&room_id, message_with(vec![recent_attachment_id.clone()]))
+        .await;
+    let (latest_page, has_more) = app_context
+        .rooms
+        .messages(&room_id, None, None, DEFAULT_MESSAGES_PAGE_SIZE)
+        .await
+        .unwrap();
+    assert!(has_more);
+    assert!(latest_page
+        .iter()
+        .all(|message| !message.attachment_ids().contains(&old_attachment_id)));
+
+    let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+    let objects = [
+        &old_attachment_id,
+        &recent_attachment_id,
+        &orphan_attachment_id,
+    ]
+    .into_iter()
+    .flat_map(|attachment_id| uploaded(attachment_id, two_hours_ago))
+    .collect::<Vec<_>>();
+    let (expired_attachment_ids, expired_keys) = find_expired(
+        &objects,
+        &app_context.rooms.attachment_ids_in_use().await,
+        Duration::from_secs(60 * 60),
+        SystemTime::now(),
+    );
+
+    assert_eq!(expired_attachment_ids, vec![orphan_attachment_id.clone()]);
+    assert_eq!(expired_keys.len(), 2);
+    assert!(expired_keys
+        .iter()
+        .all(|key| key.attachment_id() == orphan_attachment_id));
+}
+
+#[test]
+fn test_gc_keeps_recent_uploads_that_are_not_referenced_yet() {
+    let attachment_id = Uuid::new_v4().to_string();
+    let objects = uploaded(&attachment_id, SystemTime::now());
+
+    let (expired_attachment_ids, expired_keys) = find_expired(
+        &objects,
+        &Default::default(),
+        Duration::from_secs(60 * 60),
+        SystemTime::now(),
+    );
+
+    assert!(expired_attachment_ids.is_empty());
+    assert!(expired_keys.is_empty());
+}
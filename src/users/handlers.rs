@@ -1,17 +1,21 @@
 use crate::app_context::{AppContext, RequestContext};
 use crate::map::models::LatLng;
-use crate::rooms::consts::ROUNDS_PER_GAME;
+use crate::rooms::consts::{MAX_BAN_DURATION_SECS, ROUNDS_PER_GAME};
 use crate::rooms::message_types::{
-    self, BotMessagePayload, RoundEndedBotMessagePayload, RoundEndedBotMsg,
-    ServerSentSocketMessage, UserPubIdInfoPayload,
+    self, BotMessagePayload, RemovalReason, RemovedFromRoomPayload, RoundEndedBotMessagePayload,
+    RoundEndedBotMsg, ServerSentSocketMessage, UserPubIdInfoPayload, UserRoleChangedPayload,
 };
 use crate::rooms::models::ChatMessage;
 use crate::storage::interface::IRoomStorage;
+use crate::storage::sockets::Audience;
+use crate::users::models::{Permission, UserRole};
 use crate::users::responses::{
-    BanUserResponse, ChangeScoreResponse, GuessError, GuessRevocationError, IsUserTheHostResponse,
-    MuteUserResponse, RevokeGuessResponse, ScoreChangeError, SubmitGuessResponse,
-    UnmuteUserResponse, UserBanningError, UserMutingError, UserUnmutingError,
+    BanUserResponse, ChangeRoleResponse, ChangeScoreResponse, GuessError, GuessRevocationError,
+    IsUserTheHostResponse, KickUserResponse, MuteUserResponse, RevokeGuessResponse,
+    RoleChangeError, ScoreChangeError, SubmitGuessResponse, UnbanUserResponse, UnmuteUserResponse,
+    UserBanningError, UserKickingError, UserMutingError, UserUnbanningError, UserUnmutingError,
 };
+use std::time::Duration;
 
 use super::responses::SaveGuessResponse;
 
@@ -38,7 +42,10 @@ where
             .exists(&self.request_context.room_id)
             .await
         {
-            return IsUserTheHostResponse { is_host: false };
+            return IsUserTheHostResponse {
+                is_host: false,
+                permissions: vec![],
+            };
         }
         let is_host = self
             .app_context
@@ -48,29 +55,38 @@ where
                 &self.request_context.public_id,
             )
             .await;
-        IsUserTheHostResponse { is_host }
+        let permissions = self
+            .app_context
+            .rooms
+            .permissions(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+            )
+            .await
+            .into_iter()
+            .collect();
+        IsUserTheHostResponse {
+            is_host,
+            permissions,
+        }
     }
 
     pub async fn save_guess(&self, guess: LatLng) -> SaveGuessResponse {
-        if !self
+        if let Err(error) = self
             .app_context
             .rooms
-            .exists(&self.request_context.room_id)
+            .save_guess(
+                &self.request_context.room_id,
+                &self.request_context.private_id,
+                guess,
+            )
             .await
         {
             return SaveGuessResponse {
                 error: true,
-                error_code: Some(GuessError::RoomNotFound),
+                error_code: Some(GuessError::from(error)),
             };
         }
-        self.app_context
-            .rooms
-            .save_guess(
-                &self.request_context.room_id,
-                &self.request_context.private_id,
-                guess,
-            )
-            .await;
         SaveGuessResponse {
             error: false,
             error_code: None,
@@ -78,18 +94,7 @@ where
     }
 
     pub async fn submit_guess(&self, guess: LatLng) -> SubmitGuessResponse {
-        if !self
-            .app_context
-            .rooms
-            .exists(&self.request_context.room_id)
-            .await
-        {
-            return SubmitGuessResponse {
-                error: true,
-                error_code: Some(GuessError::RoomNotFound),
-            };
-        }
-        let round_finished = self
+        let round_finished = match self
             .app_context
             .rooms
             .submit_guess(
@@ -97,26 +102,34 @@ where
                 &self.request_context.private_id,
                 guess,
             )
-            .await;
-        let room_sockets_ids = self
-            .app_context
-            .rooms
-            .all_socket_ids(&self.request_context.room_id)
-            .await;
+            .await
+        {
+            Ok(round_finished) => round_finished,
+            Err(error) => {
+                return SubmitGuessResponse {
+                    error: true,
+                    error_code: Some(GuessError::from(error)),
+                }
+            }
+        };
         let msg = ServerSentSocketMessage::GuessSubmitted {
             r#type: message_types::GuessSubmitted,
         };
-        let msg = serde_json::to_string(&msg).unwrap();
         self.app_context
-            .sockets
-            .broadcast_msg(&msg, &room_sockets_ids)
+            .broadcast_event(&self.request_context.room_id, &msg, Audience::Everyone)
             .await;
         if round_finished {
-            let game_finished = self
+            let Some(game_finished) = self
                 .app_context
                 .rooms
                 .finish_game(&self.request_context.room_id)
-                .await;
+                .await
+            else {
+                return SubmitGuessResponse {
+                    error: true,
+                    error_code: Some(GuessError::RoomNotFound),
+                };
+            };
             let event_msg = match game_finished {
                 true => ServerSentSocketMessage::GameFinished {
                     r#type: message_types::GameFinished,
@@ -125,12 +138,17 @@ where
                     r#type: message_types::RoundFinished,
                 },
             };
-            let raw_event_msg = serde_json::to_string(&event_msg).unwrap();
-            let rounds_left = self
+            let Some(rounds_left) = self
                 .app_context
                 .rooms
                 .current_round_number(&self.request_context.room_id)
-                .await;
+                .await
+            else {
+                return SubmitGuessResponse {
+                    error: true,
+                    error_code: Some(GuessError::RoomNotFound),
+                };
+            };
             let round_number = match rounds_left {
                 ROUNDS_PER_GAME => ROUNDS_PER_GAME,
                 _ => ROUNDS_PER_GAME - rounds_left,
@@ -148,18 +166,23 @@ where
                 id: bot_message.id(),
                 payload: bot_message_payload,
             };
-            let raw_bot_ws_msg = serde_json::to_string(&bot_ws_msg).unwrap();
             self.app_context
                 .rooms
                 .add_message(&self.request_context.room_id, bot_message)
                 .await;
             self.app_context
-                .sockets
-                .broadcast_msg(&raw_bot_ws_msg, &room_sockets_ids)
+                .broadcast_event(
+                    &self.request_context.room_id,
+                    &bot_ws_msg,
+                    Audience::Everyone,
+                )
                 .await;
             self.app_context
-                .sockets
-                .broadcast_msg(&raw_event_msg, &room_sockets_ids)
+                .broadcast_event(
+                    &self.request_context.room_id,
+                    &event_msg,
+                    Audience::Everyone,
+                )
                 .await;
         }
         SubmitGuessResponse {
@@ -169,36 +192,25 @@ where
     }
 
     pub async fn revoke_guess(&self) -> RevokeGuessResponse {
-        if !self
+        if let Err(error) = self
             .app_context
             .rooms
-            .exists(&self.request_context.room_id)
+            .revoke_guess(
+                &self.request_context.room_id,
+                &self.request_context.private_id,
+            )
             .await
         {
             return RevokeGuessResponse {
                 error: true,
-                error_code: Some(GuessRevocationError::RoomNotFound),
+                error_code: Some(GuessRevocationError::from(error)),
             };
         }
-        self.app_context
-            .rooms
-            .revoke_guess(
-                &self.request_context.room_id,
-                &self.request_context.private_id,
-            )
-            .await;
-        let room_sockets_ids = self
-            .app_context
-            .rooms
-            .all_socket_ids(&self.request_context.room_id)
-            .await;
         let msg = ServerSentSocketMessage::GuessRevoked {
             r#type: message_types::GuessRevoked,
         };
-        let msg = serde_json::to_string(&msg).unwrap();
         self.app_context
-            .sockets
-            .broadcast_msg(&msg, &room_sockets_ids)
+            .broadcast_event(&self.request_context.room_id, &msg, Audience::Everyone)
             .await;
         RevokeGuessResponse {
             error: false,
@@ -218,36 +230,38 @@ where
                 error_code: Some(UserMutingError::RoomNotFound),
             };
         }
+        if !self.is_allowed_to(Permission::Mute).await {
+            return MuteUserResponse {
+                error: true,
+                error_code: Some(UserMutingError::YouAreNotTheHost),
+            };
+        }
+        if self.is_co_host_targeting_host(&target_user_public_id).await {
+            return MuteUserResponse {
+                error: true,
+                error_code: Some(UserMutingError::CannotMuteTheHost),
+            };
+        }
         if !self
             .app_context
             .rooms
-            .user_is_host(
-                &self.request_context.room_id,
-                &self.request_context.public_id,
-            )
+            .mute(&self.request_context.room_id, &target_user_public_id)
             .await
         {
             return MuteUserResponse {
                 error: true,
-                error_code: Some(UserMutingError::YouAreNotTheHost),
+                error_code: Some(UserMutingError::UserNotFound),
             };
         }
-        self.app_context
-            .rooms
-            .mute(&self.request_context.room_id, &target_user_public_id)
-            .await;
-        let room_sockets_ids = self
-            .app_context
-            .rooms
-            .all_socket_ids(&self.request_context.room_id)
-            .await;
         let ws_event_msg = ServerSentSocketMessage::UserMuted {
             r#type: message_types::UserMuted,
         };
-        let raw_ws_event_msg = serde_json::to_string(&ws_event_msg).unwrap();
         self.app_context
-            .sockets
-            .broadcast_msg(&raw_ws_event_msg, &room_sockets_ids)
+            .broadcast_event(
+                &self.request_context.room_id,
+                &ws_event_msg,
+                Audience::Everyone,
+            )
             .await;
         MuteUserResponse {
             error: false,
@@ -267,36 +281,32 @@ where
                 error_code: Some(UserUnmutingError::RoomNotFound),
             };
         }
+        if !self.is_allowed_to(Permission::Mute).await {
+            return UnmuteUserResponse {
+                error: true,
+                error_code: Some(UserUnmutingError::YouAreNotTheHost),
+            };
+        }
         if !self
             .app_context
             .rooms
-            .user_is_host(
-                &self.request_context.room_id,
-                &self.request_context.public_id,
-            )
+            .unmute(&self.request_context.room_id, &target_user_public_id)
             .await
         {
             return UnmuteUserResponse {
                 error: true,
-                error_code: Some(UserUnmutingError::YouAreNotTheHost),
+                error_code: Some(UserUnmutingError::UserNotFound),
             };
         }
-        self.app_context
-            .rooms
-            .unmute(&self.request_context.room_id, &target_user_public_id)
-            .await;
-        let room_sockets_ids = self
-            .app_context
-            .rooms
-            .all_socket_ids(&self.request_context.room_id)
-            .await;
         let ws_event_msg = ServerSentSocketMessage::UserUnmuted {
             r#type: message_types::UserUnmuted,
         };
-        let raw_ws_event_msg = serde_json::to_string(&ws_event_msg).unwrap();
         self.app_context
-            .sockets
-            .broadcast_msg(&raw_ws_event_msg, &room_sockets_ids)
+            .broadcast_event(
+                &self.request_context.room_id,
+                &ws_event_msg,
+                Audience::Everyone,
+            )
             .await;
         UnmuteUserResponse {
             error: false,
@@ -304,58 +314,180 @@ where
         }
     }
 
-    pub async fn ban(&self, target_user_public_id: String) -> BanUserResponse {
+    pub async fn kick(&self, target_user_public_id: String) -> KickUserResponse {
         if !self
             .app_context
             .rooms
             .exists(&self.request_context.room_id)
             .await
         {
-            return BanUserResponse {
+            return KickUserResponse {
                 error: true,
-                error_code: Some(UserBanningError::RoomNotFound),
+                error_code: Some(UserKickingError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::Kick).await {
+            return KickUserResponse {
+                error: true,
+                error_code: Some(UserKickingError::YouAreNotTheHost),
             };
         }
+        if self
+            .app_context
+            .rooms
+            .user_is_host(&self.request_context.room_id, &target_user_public_id)
+            .await
+        {
+            return KickUserResponse {
+                error: true,
+                error_code: Some(UserKickingError::CannotRemoveTheHost),
+            };
+        }
+        let Some(removed_user) = self
+            .app_context
+            .rooms
+            .kick(&self.request_context.room_id, &target_user_public_id)
+            .await
+        else {
+            return KickUserResponse {
+                error: true,
+                error_code: Some(UserKickingError::UserNotFound),
+            };
+        };
+        self.app_context.announce_kicked_user(removed_user).await;
+        KickUserResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    pub async fn ban(
+        &self,
+        target_user_public_id: String,
+        duration: Option<Duration>,
+    ) -> BanUserResponse {
         if !self
             .app_context
             .rooms
-            .user_is_host(
-                &self.request_context.room_id,
-                &self.request_context.public_id,
-            )
+            .exists(&self.request_context.room_id)
             .await
         {
+            return BanUserResponse {
+                error: true,
+                error_code: Some(UserBanningError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::Ban).await {
             return BanUserResponse {
                 error: true,
                 error_code: Some(UserBanningError::YouAreNotTheHost),
             };
         }
-        let room_sockets_ids = self
+        if self
             .app_context
             .rooms
-            .all_socket_ids(&self.request_context.room_id)
-            .await;
-        self.app_context
+            .user_is_host(&self.request_context.room_id, &target_user_public_id)
+            .await
+        {
+            return BanUserResponse {
+                error: true,
+                error_code: Some(UserBanningError::CannotRemoveTheHost),
+            };
+        }
+        if duration.is_some_and(|duration| duration.as_secs() > MAX_BAN_DURATION_SECS) {
+            return BanUserResponse {
+                error: true,
+                error_code: Some(UserBanningError::BanDurationTooLong),
+            };
+        }
+        let Some(banned_user) = self
+            .app_context
             .rooms
-            .ban(&self.request_context.room_id, &target_user_public_id)
-            .await;
+            .ban(
+                &self.request_context.room_id,
+                &target_user_public_id,
+                duration,
+            )
+            .await
+        else {
+            return BanUserResponse {
+                error: true,
+                error_code: Some(UserBanningError::RoomNotFound),
+            };
+        };
         let ws_event_msg = ServerSentSocketMessage::UserBanned {
             r#type: message_types::UserBanned,
             payload: UserPubIdInfoPayload {
                 public_id: target_user_public_id,
             },
         };
-        let raw_ws_event_msg = serde_json::to_string(&ws_event_msg).unwrap();
         self.app_context
-            .sockets
-            .broadcast_msg(&raw_ws_event_msg, &room_sockets_ids)
+            .broadcast_event(
+                &self.request_context.room_id,
+                &ws_event_msg,
+                Audience::Everyone,
+            )
             .await;
+        if let Some(socket_id) = banned_user.and_then(|user| user.socket_id) {
+            let payload = RemovedFromRoomPayload {
+                reason: RemovalReason::Banned,
+                ban_duration_secs: duration.map(|duration| duration.as_secs()),
+            };
+            self.app_context
+                .sockets
+                .remove_from_room(socket_id, payload)
+                .await;
+        }
         BanUserResponse {
             error: false,
             error_code: None,
         }
     }
 
+    pub async fn unban(&self, target_user_public_id: String) -> UnbanUserResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return UnbanUserResponse {
+                error: true,
+                error_code: Some(UserUnbanningError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::Ban).await {
+            return UnbanUserResponse {
+                error: true,
+                error_code: Some(UserUnbanningError::YouAreNotTheHost),
+            };
+        }
+        match self
+            .app_context
+            .rooms
+            .unban(&self.request_context.room_id, &target_user_public_id)
+            .await
+        {
+            Some(true) => {}
+            Some(false) => {
+                return UnbanUserResponse {
+                    error: true,
+                    error_code: Some(UserUnbanningError::UserNotBanned),
+                }
+            }
+            None => {
+                return UnbanUserResponse {
+                    error: true,
+                    error_code: Some(UserUnbanningError::RoomNotFound),
+                }
+            }
+        }
+        UnbanUserResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
     pub async fn change_score(
         &self,
         target_user_public_id: String,
@@ -372,44 +504,158 @@ where
                 error_code: Some(ScoreChangeError::RoomNotFound),
             };
         }
-        if !self
-            .app_context
-            .rooms
-            .user_is_host(
-                &self.request_context.room_id,
-                &self.request_context.public_id,
-            )
-            .await
-        {
+        if !self.is_allowed_to(Permission::ChangeScore).await {
             return ChangeScoreResponse {
                 error: true,
                 error_code: Some(ScoreChangeError::YouAreNotTheHost),
             };
         }
-        let room_sockets_ids = self
+        if self.is_co_host_targeting_host(&target_user_public_id).await {
+            return ChangeScoreResponse {
+                error: true,
+                error_code: Some(ScoreChangeError::CannotChangeHostScore),
+            };
+        }
+        if !self
             .app_context
-            .rooms
-            .all_socket_ids(&self.request_context.room_id)
-            .await;
-        self.app_context
             .rooms
             .change_score(
                 &self.request_context.room_id,
                 &target_user_public_id,
                 amount,
             )
-            .await;
+            .await
+        {
+            return ChangeScoreResponse {
+                error: true,
+                error_code: Some(ScoreChangeError::UserNotFound),
+            };
+        }
         let ws_event_msg = ServerSentSocketMessage::UserScoreChanged {
             r#type: message_types::UserScoreChanged,
         };
-        let raw_ws_event_msg = serde_json::to_string(&ws_event_msg).unwrap();
         self.app_context
-            .sockets
-            .broadcast_msg(&raw_ws_event_msg, &room_sockets_ids)
+            .broadcast_event(
+                &self.request_context.room_id,
+                &ws_event_msg,
+                Audience::Everyone,
+            )
             .await;
         ChangeScoreResponse {
             error: false,
             error_code: None,
         }
     }
+
+    /// Appoints or demotes a co-host, or hands the host role over to another user.
+    pub async fn change_role(
+        &self,
+        target_user_public_id: String,
+        role: UserRole,
+    ) -> ChangeRoleResponse {
+        if !self
+            .app_context
+            .rooms
+            .exists(&self.request_context.room_id)
+            .await
+        {
+            return ChangeRoleResponse {
+                error: true,
+                error_code: Some(RoleChangeError::RoomNotFound),
+            };
+        }
+        if !self.is_allowed_to(Permission::ManageRoles).await {
+            return ChangeRoleResponse {
+                error: true,
+                error_code: Some(RoleChangeError::YouAreNotTheHost),
+            };
+        }
+        if self
+            .app_context
+            .rooms
+            .user_is_host(&self.request_context.room_id, &target_user_public_id)
+            .await
+        {
+            return ChangeRoleResponse {
+                error: true,
+                error_code: Some(RoleChangeError::CannotChangeHostRole),
+            };
+        }
+        let user_found = match role {
+            UserRole::Host => {
+                self.app_context
+                    .rooms
+                    .transfer_host(&self.request_context.room_id, &target_user_public_id)
+                    .await
+            }
+            UserRole::CoHost | UserRole::Player => {
+                self.app_context
+                    .rooms
+                    .set_role(&self.request_context.room_id, &target_user_public_id, role)
+                    .await
+            }
+        };
+        match user_found {
+            Some(true) => {}
+            Some(false) => {
+                return ChangeRoleResponse {
+                    error: true,
+                    error_code: Some(RoleChangeError::UserNotFound),
+                }
+            }
+            None => {
+                return ChangeRoleResponse {
+                    error: true,
+                    error_code: Some(RoleChangeError::RoomNotFound),
+                }
+            }
+        }
+        let ws_event_msg = match role {
+            UserRole::Host => ServerSentSocketMessage::HostChanged {
+                r#type: message_types::HostChanged,
+                payload: UserPubIdInfoPayload {
+                    public_id: target_user_public_id,
+                },
+            },
+            UserRole::CoHost | UserRole::Player => ServerSentSocketMessage::UserRoleChanged {
+                r#type: message_types::UserRoleChanged,
+                payload: UserRoleChangedPayload {
+                    public_id: target_user_public_id,
+                    role,
+                },
+            },
+        };
+        self.app_context
+            .broadcast_event(
+                &self.request_context.room_id,
+                &ws_event_msg,
+                Audience::Everyone,
+            )
+            .await;
+        ChangeRoleResponse {
+            error: false,
+            error_code: None,
+        }
+    }
+
+    /// Co-hosts act on the host's behalf, so they can't do that to the host.
+    async fn is_co_host_targeting_host(&self, target_user_public_id: &str) -> bool {
+        target_user_public_id != self.request_context.public_id
+            && self
+                .app_context
+                .rooms
+                .user_is_host(&self.request_context.room_id, target_user_public_id)
+                .await
+    }
+
+    async fn is_allowed_to(&self, permission: Permission) -> bool {
+        self.app_context
+            .rooms
+            .has_permission(
+                &self.request_context.room_id,
+                &self.request_context.public_id,
+                permission,
+            )
+            .await
+    }
 }
@@ -1,3 +1,5 @@
 pub mod handlers;
 pub mod models;
 pub mod responses;
+#[cfg(test)]
+pub mod tests;
@@ -1,6 +1,8 @@
 use crate::map::models::LatLng;
 use crate::rooms::models::RoomStatus;
-use serde::Serialize;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::time::Instant;
 
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -11,16 +13,78 @@ pub struct User {
     pub name: String,
     pub avatar_emoji: String,
     pub score: u64,
-    pub is_host: bool,
+    /// Also serialized as `isHost` for the clients that predate co-hosts.
+    #[serde(flatten, serialize_with = "serialize_role")]
+    pub role: UserRole,
     #[serde(skip_serializing)]
     pub socket_id: Option<usize>,
+    pub presence: Presence,
+    /// When the user lost the connection; `None` while they are online.
+    #[serde(skip_serializing)]
+    pub away_since: Option<Instant>,
     pub last_guess: Option<LatLng>,
     pub submitted_guess: bool,
     pub last_round_score: Option<u64>,
     pub is_muted: bool,
+    /// Users that joined in the middle of a game only watch until the next game starts.
+    pub is_spectating: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Presence {
+    Online,
+    /// Lost the connection, but keeps their seat, score and role until the grace period is over.
+    Away,
+    /// Gone from the room; only ever sent in events.
+    Left,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UserRole {
+    Host,
+    /// Has the subset of the host's permissions that the host chose to share.
+    CoHost,
+    Player,
+}
+
+/// Actions that only the host (and, if allowed, the co-hosts) can perform.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Permission {
+    Mute,
+    Kick,
+    /// Banning, unbanning and seeing the ban list.
+    Ban,
+    ChangeScore,
+    /// Deleting other users' chat messages.
+    DeleteMessages,
+    StartRound,
+    /// Changing the room's access, listing, capacity and so on.
+    ManageRoom,
+    /// Appointing co-hosts and handing over the host role.
+    ManageRoles,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 8] = [
+        Permission::Mute,
+        Permission::Kick,
+        Permission::Ban,
+        Permission::ChangeScore,
+        Permission::DeleteMessages,
+        Permission::StartRound,
+        Permission::ManageRoom,
+        Permission::ManageRoles,
+    ];
 }
 
 impl User {
+    pub fn is_host(&self) -> bool {
+        self.role == UserRole::Host
+    }
+
     pub fn new(
         public_id: String,
         private_id: String,
@@ -28,6 +92,7 @@ impl User {
         avatar_emoji: String,
         room_has_no_members: bool,
         socket_id: usize,
+        is_spectating: bool,
     ) -> Self {
         User {
             private_id,
@@ -35,15 +100,35 @@ impl User {
             name,
             avatar_emoji,
             score: 0,
-            is_host: room_has_no_members,
+            role: if room_has_no_members {
+                UserRole::Host
+            } else {
+                UserRole::Player
+            },
             socket_id: Some(socket_id),
+            presence: Presence::Online,
+            away_since: None,
             last_guess: None,
             submitted_guess: false,
             last_round_score: None,
             is_muted: false,
+            is_spectating,
         }
     }
 
+    /// Returns whether the user was away.
+    pub fn go_online(&mut self, socket_id: usize) -> bool {
+        self.socket_id = Some(socket_id);
+        self.away_since = None;
+        std::mem::replace(&mut self.presence, Presence::Online) == Presence::Away
+    }
+
+    pub fn go_away(&mut self) {
+        self.socket_id = None;
+        self.presence = Presence::Away;
+        self.away_since = Some(Instant::now());
+    }
+
     pub fn save_guess(&mut self, guess: LatLng) {
         self.last_guess = Some(guess);
     }
@@ -75,3 +160,10 @@ impl User {
         }
     }
 }
+
+fn serialize_role<S: Serializer>(role: &UserRole, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(2))?;
+    map.serialize_entry("role", role)?;
+    map.serialize_entry("isHost", &(*role == UserRole::Host))?;
+    map.end()
+}
@@ -1,9 +1,12 @@
+use crate::storage::rooms::UserLookupError;
+use crate::users::models::Permission;
 use serde::Serialize;
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IsUserTheHostResponse {
     pub is_host: bool,
+    pub permissions: Vec<Permission>,
 }
 
 #[derive(Serialize)]
@@ -26,6 +29,16 @@ pub struct SubmitGuessResponse {
 #[serde(rename_all = "camelCase")]
 pub enum GuessError {
     RoomNotFound,
+    UserNotFound,
+}
+
+impl From<UserLookupError> for GuessError {
+    fn from(error: UserLookupError) -> Self {
+        match error {
+            UserLookupError::RoomNotFound => Self::RoomNotFound,
+            UserLookupError::UserNotFound => Self::UserNotFound,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -40,6 +53,16 @@ pub struct RevokeGuessResponse {
 #[serde(rename_all = "camelCase")]
 pub enum GuessRevocationError {
     RoomNotFound,
+    UserNotFound,
+}
+
+impl From<UserLookupError> for GuessRevocationError {
+    fn from(error: UserLookupError) -> Self {
+        match error {
+            UserLookupError::RoomNotFound => Self::RoomNotFound,
+            UserLookupError::UserNotFound => Self::UserNotFound,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -55,6 +78,8 @@ pub struct MuteUserResponse {
 pub enum UserMutingError {
     RoomNotFound,
     YouAreNotTheHost,
+    UserNotFound,
+    CannotMuteTheHost,
 }
 
 #[derive(Serialize)]
@@ -70,6 +95,7 @@ pub struct UnmuteUserResponse {
 pub enum UserUnmutingError {
     RoomNotFound,
     YouAreNotTheHost,
+    UserNotFound,
 }
 
 #[derive(Serialize)]
@@ -85,6 +111,41 @@ pub struct BanUserResponse {
 pub enum UserBanningError {
     RoomNotFound,
     YouAreNotTheHost,
+    CannotRemoveTheHost,
+    BanDurationTooLong,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KickUserResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<UserKickingError>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UserKickingError {
+    RoomNotFound,
+    YouAreNotTheHost,
+    UserNotFound,
+    CannotRemoveTheHost,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnbanUserResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<UserUnbanningError>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UserUnbanningError {
+    RoomNotFound,
+    YouAreNotTheHost,
+    UserNotBanned,
 }
 
 #[derive(Serialize)]
@@ -100,4 +161,24 @@ pub struct ChangeScoreResponse {
 pub enum ScoreChangeError {
     RoomNotFound,
     YouAreNotTheHost,
+    UserNotFound,
+    CannotChangeHostScore,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeRoleResponse {
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<RoleChangeError>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoleChangeError {
+    RoomNotFound,
+    YouAreNotTheHost,
+    UserNotFound,
+    /// The host can only give their role away by transferring it to someone else.
+    CannotChangeHostRole,
 }
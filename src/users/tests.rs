@@ -0,0 +1,140 @@
+use crate::app_context::tests::{test_app_context, test_room, TestSocket};
+use crate::map::models::LatLng;
+use crate::storage::interface::{RoomInfoRepo, UserPermissionsRepo};
+use crate::users::handlers::UsersHttpHandler;
+use crate::users::models::{Permission, UserRole};
+use serde_json::json;
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_ban_duration_out_of_range_is_rejected() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    let player = TestSocket::join(&app_context, &room_id, "player").await;
+    let target_public_id = player.request_context.public_id.clone();
+
+    let response = UsersHttpHandler::new(app_context.clone(), &host.request_context)
+        .ban(
+            target_public_id.clone(),
+            Some(Duration::from_secs(u64::MAX)),
+        )
+        .await;
+
+    assert_eq!(
+        serde_json::to_value(response).unwrap(),
+        json!({ "error": true, "errorCode": "banDurationTooLong" })
+    );
+    assert!(
+        !app_context
+            .rooms
+            .is_banned(&room_id, &target_public_id)
+            .await
+    );
+}
+
+#[tokio::test]
+async fn test_co_host_cannot_mute_host_or_change_their_score() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    let co_host = TestSocket::join(&app_context, &room_id, "coHost").await;
+    let host_public_id = host.request_context.public_id.clone();
+    app_context
+        .rooms
+        .set_role(
+            &room_id,
+            &co_host.request_context.public_id,
+            UserRole::CoHost,
+        )
+        .await;
+    app_context
+        .rooms
+        .set_co_host_permissions(
+            &room_id,
+            HashSet::from([Permission::Mute, Permission::ChangeScore]),
+        )
+        .await;
+    let co_host_handler = UsersHttpHandler::new(app_context.clone(), &co_host.request_context);
+
+    let mute_response = co_host_handler.mute(host_public_id.clone()).await;
+    let score_response = co_host_handler
+        .change_score(host_public_id.clone(), 100)
+        .await;
+
+    assert_eq!(
+        serde_json::to_value(mute_response).unwrap(),
+        json!({ "error": true, "errorCode": "cannotMuteTheHost" })
+    );
+    assert_eq!(
+        serde_json::to_value(score_response).unwrap(),
+        json!({ "error": true, "errorCode": "cannotChangeHostScore" })
+    );
+    assert!(!app_context.rooms.is_muted(&room_id, &host_public_id).await);
+}
+
+#[tokio::test]
+async fn test_actions_on_unknown_user_are_rejected() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    let host_handler = UsersHttpHandler::new(app_context.clone(), &host.request_context);
+
+    let mute_response = host_handler.mute(String::from("nobody")).await;
+    let unmute_response = host_handler.unmute(String::from("nobody")).await;
+    let score_response = host_handler.change_score(String::from("nobody"), 1).await;
+
+    for response in [
+        serde_json::to_value(mute_response).unwrap(),
+        serde_json::to_value(unmute_response).unwrap(),
+        serde_json::to_value(score_response).unwrap(),
+    ] {
+        assert_eq!(
+            response,
+            json!({ "error": true, "errorCode": "userNotFound" })
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_guesses_after_being_kicked_are_rejected() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    let host = TestSocket::join(&app_context, &room_id, "host").await;
+    let player = TestSocket::join(&app_context, &room_id, "player").await;
+    UsersHttpHandler::new(app_context.clone(), &host.request_context)
+        .kick(player.request_context.public_id.clone())
+        .await;
+    let player_handler = UsersHttpHandler::new(app_context.clone(), &player.request_context);
+    let guess = LatLng { lat: 1.0, lng: 2.0 };
+
+    let user_not_found = json!({ "error": true, "errorCode": "userNotFound" });
+    assert_eq!(
+        serde_json::to_value(player_handler.save_guess(guess).await).unwrap(),
+        user_not_found
+    );
+    assert_eq!(
+        serde_json::to_value(player_handler.submit_guess(guess).await).unwrap(),
+        user_not_found
+    );
+    assert_eq!(
+        serde_json::to_value(player_handler.revoke_guess().await).unwrap(),
+        user_not_found
+    );
+}
+
+#[tokio::test]
+async fn test_users_keep_is_host_alongside_role() {
+    let app_context = test_app_context();
+    let room_id = test_room(&app_context).await;
+    TestSocket::join(&app_context, &room_id, "host").await;
+    TestSocket::join(&app_context, &room_id, "player").await;
+
+    let users = serde_json::to_value(app_context.rooms.users(&room_id).await).unwrap();
+
+    assert_eq!(users[0]["role"], "host");
+    assert_eq!(users[0]["isHost"], true);
+    assert_eq!(users[1]["role"], "player");
+    assert_eq!(users[1]["isHost"], false);
+}